@@ -0,0 +1,367 @@
+use rocket::http::{ContentType, Status};
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+
+use serde::Serialize;
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+pub const STATIC_DIR: &str = "../frontend/static/";
+
+/// Directories that may be listed, so the frontend can discover what's available.
+pub const LISTABLE: [&str; 4] = ["shaders", "universes", "replays", "presets"];
+
+/// These change without changing name, so the browser always has to check.
+const ALWAYS_FRESH: [&str; 2] = ["index.html", "universe.json"];
+
+/// The wasm-pack output of `build.sh`, big and always under the same name.
+/// Cached for a bit so reloads don't download it again, revalidated after that.
+const BUNDLE: [&str; 2] = ["wasm_bg.wasm", "wasm.js"];
+
+const NO_CACHE: &str = "no-cache";
+const SHORT: &str = "public, max-age=300";
+
+/// Pre-compressed variants, in order of preference.
+/// `build.sh` produces these next to the original file.
+const ENCODINGS: [(&str, &str); 2] = [("br", "br"), ("gzip", "gz")];
+
+fn content_type(path: &Path) -> ContentType {
+    match path.extension().and_then(|x| x.to_str()) {
+        Some("wasm") => ContentType::new("application", "wasm"),
+        Some("js") => ContentType::JavaScript,
+        Some("json") => ContentType::JSON,
+        Some("frag") | Some("vert") | Some("obj") => ContentType::Plain,
+        Some(ext) => ContentType::from_extension(ext).unwrap_or(ContentType::Binary),
+        None => ContentType::Binary,
+    }
+}
+
+fn cache_control(path: &Path) -> &'static str {
+    let name = path.file_name().and_then(|x| x.to_str()).unwrap_or_default();
+    let is_json = path.extension().and_then(|x| x.to_str()) == Some("json");
+
+    if ALWAYS_FRESH.contains(&name) || is_json {
+        NO_CACHE
+    } else if BUNDLE.contains(&name) {
+        SHORT
+    } else {
+        NO_CACHE
+    }
+}
+
+/// Cheap etag based on size and modification time, the content is never read.
+fn etag(meta: &fs::Metadata) -> String {
+    let mut hasher = DefaultHasher::new();
+    meta.len().hash(&mut hasher);
+
+    if let Some(modified) = meta.modified().ok().and_then(|m| m.duration_since(UNIX_EPOCH).ok()) {
+        modified.as_nanos().hash(&mut hasher);
+    }
+
+    format!("\"{:x}\"", hasher.finish())
+}
+
+fn matches_etag(header: &str, etag: &str) -> bool {
+    header.split(',').map(|x| x.trim()).any(|tag| {
+        tag == "*" || tag.trim_start_matches("W/") == etag
+    })
+}
+
+fn accepts(req: &Request, encoding: &str) -> bool {
+    req.headers()
+        .get("Accept-Encoding")
+        .flat_map(|x| x.split(','))
+        .map(|x| x.split(';').next().unwrap_or_default().trim())
+        .any(|x| x == encoding)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PathError {
+    /// The path contains a `..` component
+    Traversal,
+    /// The path is absolute, joining it would replace the root
+    Absolute,
+    /// One of the components starts with a `.`
+    Hidden,
+    /// The path resolves outside of the root, for example through a symlink
+    Escapes,
+    NotFound,
+}
+
+/// Resolve `requested` inside `root`, the result is guaranteed to be inside `root`.
+///
+/// Rocket already refuses most bad segments, but we don't want to depend on that.
+pub fn resolve<P: AsRef<Path>>(root: &Path, requested: P) -> Result<PathBuf, PathError> {
+    let mut relative = PathBuf::new();
+
+    for component in requested.as_ref().components() {
+        match component {
+            Component::Normal(part) => {
+                if part.to_str().map(|x| x.starts_with('.')).unwrap_or(true) {
+                    return Err(PathError::Hidden);
+                }
+                relative.push(part);
+            }
+            Component::CurDir => {}
+            Component::ParentDir => return Err(PathError::Traversal),
+            Component::RootDir | Component::Prefix(_) => return Err(PathError::Absolute),
+        }
+    }
+
+    let root = root.canonicalize().map_err(|_| PathError::NotFound)?;
+    let path = root
+        .join(relative)
+        .canonicalize()
+        .map_err(|_| PathError::NotFound)?;
+
+    if path.starts_with(&root) {
+        Ok(path)
+    } else {
+        Err(PathError::Escapes)
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct DirEntry {
+    name: String,
+    dir: bool,
+}
+
+/// List the visible entries of `dir`, if it is one of the `LISTABLE` directories.
+pub fn list_dir<P: AsRef<Path>>(root: &Path, dir: P) -> Result<Vec<DirEntry>, PathError> {
+    let path = resolve(root, dir)?;
+    let canonical_root = root.canonicalize().map_err(|_| PathError::NotFound)?;
+
+    let listable = LISTABLE
+        .iter()
+        .any(|x| canonical_root.join(x).canonicalize().ok().as_ref() == Some(&path));
+    if !listable {
+        return Err(PathError::NotFound);
+    }
+
+    let mut entries: Vec<DirEntry> = fs::read_dir(&path)
+        .map_err(|_| PathError::NotFound)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // Hidden files and symlinks out of the root are not served, so don't list them either
+            let path = resolve(root, path.strip_prefix(&canonical_root).ok()?.join(&name)).ok()?;
+
+            Some(DirEntry {
+                name,
+                dir: path.is_dir(),
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(entries)
+}
+
+/// Static file responder that knows about caching and pre-compressed variants.
+///
+/// Files are sent with `no-cache` (or a short max-age for the bundle),
+/// so the browser revalidates them with `If-None-Match` and gets a `304` when nothing changed.
+pub struct StaticFile {
+    root: PathBuf,
+    path: PathBuf,
+}
+
+impl StaticFile {
+    /// Open `requested` inside `root`, see `resolve`.
+    pub fn open<P: AsRef<Path>>(root: &Path, requested: P) -> Option<Self> {
+        let path = resolve(root, requested).ok()?;
+        let root = root.canonicalize().ok()?;
+        if path.is_file() {
+            Some(Self { root, path })
+        } else {
+            None
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Find the best pre-compressed variant the client accepts, if any.
+    fn encoded(&self, req: &Request) -> Option<(&'static str, PathBuf)> {
+        ENCODINGS.iter().find_map(|&(encoding, ext)| {
+            if !accepts(req, encoding) {
+                return None;
+            }
+
+            self.variant(ext).map(|path| (encoding, path))
+        })
+    }
+
+    /// The pre-compressed variant with extension `ext` next to the file.
+    /// It is checked like the file itself, a symlinked `.gz` can't point out of the root.
+    fn variant(&self, ext: &str) -> Option<PathBuf> {
+        let mut name = self.path.file_name()?.to_os_string();
+        name.push(".");
+        name.push(ext);
+
+        let path = self.path.with_file_name(name).canonicalize().ok()?;
+        if path.starts_with(&self.root) && path.is_file() {
+            Some(path)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'r> Responder<'r> for StaticFile {
+    fn respond_to(self, req: &Request) -> response::Result<'r> {
+        let (encoding, path) = match self.encoded(req) {
+            Some((encoding, path)) => (Some(encoding), path),
+            None => (None, self.path.clone()),
+        };
+
+        let meta = fs::metadata(&path).map_err(|_| Status::NotFound)?;
+        let etag = etag(&meta);
+
+        let mut response = Response::build();
+        response
+            .raw_header("ETag", etag.clone())
+            .raw_header("Cache-Control", cache_control(&self.path))
+            .raw_header("Vary", "Accept-Encoding");
+
+        if req
+            .headers()
+            .get_one("If-None-Match")
+            .map(|header| matches_etag(header, &etag))
+            .unwrap_or(false)
+        {
+            return response.status(Status::NotModified).ok();
+        }
+
+        let file = File::open(&path).map_err(|_| Status::NotFound)?;
+
+        if let Some(encoding) = encoding {
+            response.raw_header("Content-Encoding", encoding);
+        }
+
+        response
+            .header(content_type(&self.path))
+            .sized_body(file)
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// root/shaders/basic.vert, root/shaders/.secret, root/.git/config and outside/secret.txt
+    fn tree() -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("root");
+
+        fs::create_dir_all(root.join("shaders")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(dir.path().join("outside")).unwrap();
+
+        fs::write(root.join("index.html"), "").unwrap();
+        fs::write(root.join("shaders/basic.vert"), "").unwrap();
+        fs::write(root.join("shaders/.secret"), "").unwrap();
+        fs::write(root.join(".git/config"), "").unwrap();
+        fs::write(dir.path().join("outside/secret.txt"), "").unwrap();
+
+        (dir, root)
+    }
+
+    #[test]
+    fn resolves_inside_root() {
+        let (_dir, root) = tree();
+
+        let path = resolve(&root, "shaders/basic.vert").unwrap();
+        assert_eq!(path, root.canonicalize().unwrap().join("shaders/basic.vert"));
+        assert!(resolve(&root, "./index.html").is_ok());
+        assert_eq!(resolve(&root, "missing.html"), Err(PathError::NotFound));
+    }
+
+    #[test]
+    fn rejects_traversal() {
+        let (_dir, root) = tree();
+
+        assert_eq!(resolve(&root, "../outside/secret.txt"), Err(PathError::Traversal));
+        assert_eq!(resolve(&root, "shaders/../../outside/secret.txt"), Err(PathError::Traversal));
+        // Even when it would stay inside the root
+        assert_eq!(resolve(&root, "shaders/../index.html"), Err(PathError::Traversal));
+    }
+
+    #[test]
+    fn rejects_absolute() {
+        let (dir, root) = tree();
+
+        assert_eq!(resolve(&root, "/etc/passwd"), Err(PathError::Absolute));
+        assert_eq!(resolve(&root, dir.path().join("outside/secret.txt")), Err(PathError::Absolute));
+    }
+
+    #[test]
+    fn rejects_hidden() {
+        let (_dir, root) = tree();
+
+        assert_eq!(resolve(&root, ".git/config"), Err(PathError::Hidden));
+        assert_eq!(resolve(&root, "shaders/.secret"), Err(PathError::Hidden));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_escaping_symlinks() {
+        use std::os::unix::fs::symlink;
+        let (dir, root) = tree();
+
+        symlink(dir.path().join("outside"), root.join("linked")).unwrap();
+        symlink(dir.path().join("outside/secret.txt"), root.join("secret.txt")).unwrap();
+        symlink(root.join("shaders"), root.join("inside")).unwrap();
+
+        assert_eq!(resolve(&root, "linked/secret.txt"), Err(PathError::Escapes));
+        assert_eq!(resolve(&root, "secret.txt"), Err(PathError::Escapes));
+        assert!(resolve(&root, "inside/basic.vert").is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn lists_only_visible_entries() {
+        use std::os::unix::fs::symlink;
+        let (dir, root) = tree();
+
+        symlink(dir.path().join("outside/secret.txt"), root.join("shaders/secret.vert")).unwrap();
+        fs::create_dir(root.join("shaders/extra")).unwrap();
+
+        let entries = list_dir(&root, "shaders").unwrap();
+        let names: Vec<_> = entries.iter().map(|e| (e.name.as_str(), e.dir)).collect();
+        assert_eq!(names, vec![("basic.vert", false), ("extra", true)]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn variants_stay_inside_root() {
+        use std::os::unix::fs::symlink;
+        let (dir, root) = tree();
+
+        fs::write(root.join("shaders/basic.vert.br"), "").unwrap();
+        symlink(dir.path().join("outside/secret.txt"), root.join("shaders/basic.vert.gz")).unwrap();
+
+        let file = StaticFile::open(&root, "shaders/basic.vert").unwrap();
+        assert_eq!(file.variant("br"), Some(file.path().with_extension("vert.br")));
+        assert_eq!(file.variant("gz"), None);
+        assert!(StaticFile::open(&root, "shaders/.secret").is_none());
+    }
+
+    #[test]
+    fn lists_only_listable_dirs() {
+        let (_dir, root) = tree();
+
+        assert_eq!(list_dir(&root, "").unwrap_err(), PathError::NotFound);
+        assert_eq!(list_dir(&root, ".git").unwrap_err(), PathError::Hidden);
+        assert_eq!(list_dir(&root, "../root/shaders").unwrap_err(), PathError::Traversal);
+        assert!(list_dir(&root, "shaders").is_ok());
+    }
+}
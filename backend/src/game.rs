@@ -0,0 +1,493 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Planets and fleets as in the classic Planet Wars protocol, player 0 is neutral.
+#[derive(Debug, Clone)]
+pub struct Planet {
+    pub x: f64,
+    pub y: f64,
+    pub owner: usize,
+    pub ships: u64,
+    pub growth: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Fleet {
+    pub id: u64,
+    pub owner: usize,
+    pub ships: u64,
+    pub source: usize,
+    pub destination: usize,
+    pub total_turns: u64,
+    pub turns_remaining: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Order {
+    pub source: usize,
+    pub destination: usize,
+    pub ships: u64,
+}
+
+impl Order {
+    /// Orders look like `source destination ships`
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() != 3 {
+            return Err(format!("Expected 'source destination ships', got '{}'", line));
+        }
+
+        let parse = |x: &str| x.parse::<u64>().map_err(|_| format!("'{}' is not a number", x));
+
+        Ok(Self {
+            source: parse(parts[0])? as usize,
+            destination: parse(parts[1])? as usize,
+            ships: parse(parts[2])?,
+        })
+    }
+}
+
+/// Game state as sent to the viewers, see `universe::GameState` in the frontend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameState {
+    pub planets: Vec<PlanetState>,
+    pub expeditions: Vec<Expedition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanetState {
+    pub name: String,
+    pub x: f64,
+    pub y: f64,
+    pub owner: Option<usize>,
+    pub ship_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Expedition {
+    pub id: u64,
+    pub origin: String,
+    pub destination: String,
+    pub owner: usize,
+    pub ship_count: u64,
+    pub turns_remaining: u64,
+}
+
+pub fn planet_name(index: usize) -> String {
+    format!("planet {}", index)
+}
+
+/// Owners as seen by `player`, every player thinks they are player 1.
+fn perspective(owner: usize, player: usize) -> usize {
+    if owner == 0 {
+        0
+    } else if owner == player {
+        1
+    } else if owner < player {
+        owner + 1
+    } else {
+        owner
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlanetWars {
+    pub planets: Vec<Planet>,
+    pub fleets: Vec<Fleet>,
+    pub turn: u64,
+    next_fleet: u64,
+}
+
+impl PlanetWars {
+    /// Parse a classic map, one `P x y owner ships growth` line per planet.
+    /// Fleets (`F ...`) are allowed too, comments start with `#`.
+    pub fn parse_map(text: &str) -> Result<Self, String> {
+        let mut planets = Vec::new();
+        let mut fleets = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let error = || format!("Invalid line {}: '{}'", number + 1, line);
+            let num = |i: usize| parts.get(i).and_then(|x| x.parse::<f64>().ok()).ok_or_else(error);
+
+            match parts[0] {
+                "P" if parts.len() == 6 => planets.push(Planet {
+                    x: num(1)?,
+                    y: num(2)?,
+                    owner: num(3)? as usize,
+                    ships: num(4)? as u64,
+                    growth: num(5)? as u64,
+                }),
+                "F" if parts.len() == 7 => fleets.push(Fleet {
+                    id: fleets.len() as u64,
+                    owner: num(1)? as usize,
+                    ships: num(2)? as u64,
+                    source: num(3)? as usize,
+                    destination: num(4)? as usize,
+                    total_turns: num(5)? as u64,
+                    turns_remaining: num(6)? as u64,
+                }),
+                _ => return Err(error()),
+            }
+        }
+
+        if fleets
+            .iter()
+            .any(|f| f.source >= planets.len() || f.destination >= planets.len())
+        {
+            return Err("Fleet refers to an unknown planet".to_string());
+        }
+
+        Ok(Self {
+            next_fleet: fleets.len() as u64,
+            planets,
+            fleets,
+            turn: 0,
+        })
+    }
+
+    /// Highest player number on the map.
+    pub fn player_count(&self) -> usize {
+        self.planets
+            .iter()
+            .map(|p| p.owner)
+            .chain(self.fleets.iter().map(|f| f.owner))
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn distance(&self, a: usize, b: usize) -> u64 {
+        let (a, b) = (&self.planets[a], &self.planets[b]);
+        (a.x - b.x).hypot(a.y - b.y).ceil() as u64
+    }
+
+    pub fn is_alive(&self, player: usize) -> bool {
+        self.planets.iter().any(|p| p.owner == player) || self.fleets.iter().any(|f| f.owner == player)
+    }
+
+    pub fn ship_count(&self, player: usize) -> u64 {
+        let on_planets: u64 = self.planets.iter().filter(|p| p.owner == player).map(|p| p.ships).sum();
+        let in_fleets: u64 = self.fleets.iter().filter(|f| f.owner == player).map(|f| f.ships).sum();
+
+        on_planets + in_fleets
+    }
+
+    /// The state in the classic protocol, as seen by `player`.
+    pub fn serialize_for(&self, player: usize) -> String {
+        let mut out = String::new();
+
+        for p in &self.planets {
+            out.push_str(&format!(
+                "P {} {} {} {} {}\n",
+                p.x,
+                p.y,
+                perspective(p.owner, player),
+                p.ships,
+                p.growth
+            ));
+        }
+
+        for f in &self.fleets {
+            out.push_str(&format!(
+                "F {} {} {} {} {} {}\n",
+                perspective(f.owner, player),
+                f.ships,
+                f.source,
+                f.destination,
+                f.total_turns,
+                f.turns_remaining
+            ));
+        }
+
+        out
+    }
+
+    /// Send out a fleet for `player`, invalid orders change nothing.
+    pub fn issue(&mut self, player: usize, order: &Order) -> Result<(), String> {
+        if order.source >= self.planets.len() || order.destination >= self.planets.len() {
+            return Err(format!("Unknown planet in {:?}", order));
+        }
+        if order.source == order.destination {
+            return Err(format!("Source and destination are the same in {:?}", order));
+        }
+
+        let distance = self.distance(order.source, order.destination);
+        let source = &mut self.planets[order.source];
+
+        if source.owner != player {
+            return Err(format!("Player {} doesn't own planet {}", player, order.source));
+        }
+        if source.ships < order.ships {
+            return Err(format!(
+                "Planet {} only has {} ships, {} requested",
+                order.source, source.ships, order.ships
+            ));
+        }
+        if order.ships == 0 {
+            return Ok(());
+        }
+
+        source.ships -= order.ships;
+        self.fleets.push(Fleet {
+            id: self.next_fleet,
+            owner: player,
+            ships: order.ships,
+            source: order.source,
+            destination: order.destination,
+            total_turns: distance,
+            turns_remaining: distance,
+        });
+        self.next_fleet += 1;
+
+        Ok(())
+    }
+
+    /// Advance one turn: planets grow, fleets move and arriving fleets fight.
+    pub fn step(&mut self) {
+        for planet in self.planets.iter_mut().filter(|p| p.owner != 0) {
+            planet.ships += planet.growth;
+        }
+
+        let mut arrivals: HashMap<usize, Vec<Fleet>> = HashMap::new();
+        for mut fleet in std::mem::take(&mut self.fleets) {
+            fleet.turns_remaining = fleet.turns_remaining.saturating_sub(1);
+
+            if fleet.turns_remaining == 0 {
+                arrivals.entry(fleet.destination).or_default().push(fleet);
+            } else {
+                self.fleets.push(fleet);
+            }
+        }
+
+        for (index, fleets) in arrivals {
+            self.battle(index, fleets);
+        }
+
+        self.turn += 1;
+    }
+
+    /// The biggest force takes the planet with what remains after fighting the second biggest,
+    /// on a tie the planet stays with its owner without ships.
+    fn battle(&mut self, index: usize, fleets: Vec<Fleet>) {
+        let planet = &mut self.planets[index];
+
+        let mut forces: HashMap<usize, u64> = HashMap::new();
+        forces.insert(planet.owner, planet.ships);
+        for fleet in fleets {
+            *forces.entry(fleet.owner).or_default() += fleet.ships;
+        }
+
+        let mut forces: Vec<(usize, u64)> = forces.into_iter().collect();
+        forces.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        match forces.as_slice() {
+            [(owner, ships)] => {
+                planet.owner = *owner;
+                planet.ships = *ships;
+            }
+            [(_, first), (_, second), ..] if first == second => {
+                planet.ships = 0;
+            }
+            [(owner, first), (_, second), ..] => {
+                planet.owner = *owner;
+                planet.ships = first - second;
+            }
+            [] => {}
+        }
+    }
+
+    /// The state for the viewers.
+    pub fn frame(&self) -> GameState {
+        let planets = self
+            .planets
+            .iter()
+            .enumerate()
+            .map(|(i, p)| PlanetState {
+                name: planet_name(i),
+                x: p.x,
+                y: p.y,
+                owner: if p.owner == 0 { None } else { Some(p.owner) },
+                ship_count: p.ships,
+            })
+            .collect();
+
+        let expeditions = self
+            .fleets
+            .iter()
+            .map(|f| Expedition {
+                id: f.id,
+                origin: planet_name(f.source),
+                destination: planet_name(f.destination),
+                owner: f.owner,
+                ship_count: f.ships,
+                turns_remaining: f.turns_remaining,
+            })
+            .collect();
+
+        GameState {
+            planets,
+            expeditions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = "\
+# x y owner ships growth
+P 0 0 1 10 2
+P 3 4 2 10 3
+P 6 8 0 5 1
+";
+
+    fn fleet(owner: usize, ships: u64, destination: usize) -> Fleet {
+        Fleet {
+            id: 0,
+            owner,
+            ships,
+            source: 0,
+            destination,
+            total_turns: 1,
+            turns_remaining: 1,
+        }
+    }
+
+    #[test]
+    fn parses_maps() {
+        let game = PlanetWars::parse_map(&(MAP.to_string() + "F 1 4 0 2 10 3 # on its way\n")).unwrap();
+
+        assert_eq!(game.planets.len(), 3);
+        assert_eq!(game.fleets.len(), 1);
+        assert_eq!(game.player_count(), 2);
+        assert_eq!(game.distance(0, 1), 5);
+        assert_eq!(game.fleets[0].turns_remaining, 3);
+    }
+
+    #[test]
+    fn rejects_invalid_maps() {
+        assert_eq!(PlanetWars::parse_map("P 0 0 1 10").unwrap_err(), "Invalid line 1: 'P 0 0 1 10'");
+        assert_eq!(PlanetWars::parse_map("\nP 0 0 x 10 2").unwrap_err(), "Invalid line 2: 'P 0 0 x 10 2'");
+        assert!(PlanetWars::parse_map("Q 0 0 1 10 2").is_err());
+        assert_eq!(
+            PlanetWars::parse_map("P 0 0 1 10 2\nF 1 4 0 1 10 3").unwrap_err(),
+            "Fleet refers to an unknown planet"
+        );
+    }
+
+    #[test]
+    fn parses_orders() {
+        let order = Order::parse(" 0  2 5 ").unwrap();
+        assert_eq!(
+            order,
+            Order {
+                source: 0,
+                destination: 2,
+                ships: 5
+            }
+        );
+
+        assert!(Order::parse("0 2").is_err());
+        assert!(Order::parse("0 2 5 1").is_err());
+        assert_eq!(Order::parse("0 2 -5").unwrap_err(), "'-5' is not a number");
+    }
+
+    #[test]
+    fn everybody_is_player_one() {
+        let owners = [0, 1, 2, 3];
+        let seen = |player| owners.iter().map(|&o| perspective(o, player)).collect::<Vec<_>>();
+
+        assert_eq!(seen(1), vec![0, 1, 2, 3]);
+        assert_eq!(seen(2), vec![0, 2, 1, 3]);
+        assert_eq!(seen(3), vec![0, 2, 3, 1]);
+
+        let game = PlanetWars::parse_map(MAP).unwrap();
+        assert_eq!(game.serialize_for(2), "P 0 0 2 10 2\nP 3 4 1 10 3\nP 6 8 0 5 1\n");
+    }
+
+    #[test]
+    fn issues_orders() {
+        let mut game = PlanetWars::parse_map(MAP).unwrap();
+        let order = |source, destination, ships| Order {
+            source,
+            destination,
+            ships,
+        };
+
+        assert!(game.issue(1, &order(1, 0, 5)).is_err(), "not the owner");
+        assert!(game.issue(1, &order(0, 0, 5)).is_err(), "same planet");
+        assert!(game.issue(1, &order(0, 9, 5)).is_err(), "unknown planet");
+        assert!(game.issue(1, &order(0, 1, 11)).is_err(), "too many ships");
+        assert!(game.fleets.is_empty());
+
+        game.issue(1, &order(0, 1, 4)).unwrap();
+        assert_eq!(game.planets[0].ships, 6);
+        assert_eq!(game.fleets.len(), 1);
+        assert_eq!(game.fleets[0].turns_remaining, 5);
+    }
+
+    #[test]
+    fn steps_grow_and_move() {
+        let mut game = PlanetWars::parse_map(MAP).unwrap();
+        game.issue(2, &Order::parse("1 0 10").unwrap()).unwrap();
+
+        for _ in 0..4 {
+            game.step();
+        }
+        // Neutral planets don't grow
+        assert_eq!(game.planets.iter().map(|p| p.ships).collect::<Vec<_>>(), vec![18, 12, 5]);
+        assert_eq!(game.fleets[0].turns_remaining, 1);
+
+        // Planets grow before fleets land, 10 attackers meet 20 defenders
+        game.step();
+        assert!(game.fleets.is_empty());
+        assert_eq!((game.planets[0].owner, game.planets[0].ships), (1, 10));
+        assert_eq!(game.turn, 5);
+        assert_eq!(game.ship_count(2), 15);
+    }
+
+    #[test]
+    fn three_way_battle() {
+        let mut game = PlanetWars::parse_map(MAP).unwrap();
+
+        // Neutral 5 against 8 and 6, the biggest force keeps what's left after the second biggest
+        game.battle(2, vec![fleet(1, 8, 2), fleet(2, 6, 2)]);
+        assert_eq!((game.planets[2].owner, game.planets[2].ships), (1, 2));
+
+        // Fleets of the same player add up
+        game.battle(2, vec![fleet(2, 3, 2), fleet(2, 3, 2), fleet(3, 5, 2)]);
+        assert_eq!((game.planets[2].owner, game.planets[2].ships), (2, 1));
+    }
+
+    #[test]
+    fn ties_keep_the_owner() {
+        let mut game = PlanetWars::parse_map(MAP).unwrap();
+
+        game.battle(2, vec![fleet(1, 5, 2)]);
+        assert_eq!((game.planets[2].owner, game.planets[2].ships), (0, 0));
+
+        // A tie between the attackers, the owner was weaker
+        game.battle(0, vec![fleet(2, 12, 0), fleet(3, 12, 0)]);
+        assert_eq!((game.planets[0].owner, game.planets[0].ships), (1, 0));
+    }
+
+    #[test]
+    fn frames_name_planets() {
+        let mut game = PlanetWars::parse_map(MAP).unwrap();
+        game.issue(1, &Order::parse("0 2 3").unwrap()).unwrap();
+
+        let frame = game.frame();
+        assert_eq!(frame.planets[2].name, "planet 2");
+        assert_eq!(frame.planets[2].owner, None);
+        assert_eq!(frame.planets[0].owner, Some(1));
+        assert_eq!(frame.expeditions[0].origin, "planet 0");
+        assert_eq!(frame.expeditions[0].destination, "planet 2");
+        assert_eq!(frame.expeditions[0].ship_count, 3);
+    }
+}
@@ -3,19 +3,85 @@
 #[macro_use]
 extern crate rocket;
 
-use rocket::response::NamedFile;
+use rocket::response::content::Json;
 use std::path::{Path, PathBuf};
 
+mod files;
+use files::{StaticFile, STATIC_DIR};
+
+mod relay;
+use relay::Relay;
+
+mod game;
+mod runner;
+mod universes;
+mod presets;
+
+#[derive(Responder)]
+enum Static {
+    File(StaticFile),
+    Listing(Json<String>),
+}
+
 #[get("/<file..>")]
-fn files(file: PathBuf) -> Option<NamedFile> {
-    NamedFile::open(Path::new("../frontend/static/").join(file)).ok()
+fn files(file: PathBuf) -> Option<Static> {
+    let root = Path::new(STATIC_DIR);
+    let path = files::resolve(root, &file).ok()?;
+
+    if path.is_dir() {
+        let entries = files::list_dir(root, &file).ok()?;
+        serde_json::to_string(&entries).ok().map(|x| Static::Listing(Json(x)))
+    } else {
+        StaticFile::open(root, &file).map(Static::File)
+    }
 }
 
 #[get("/")]
-fn index() -> Option<NamedFile> {
-    NamedFile::open(Path::new("../frontend/static/index.html")).ok()
+fn index() -> Option<StaticFile> {
+    StaticFile::open(Path::new(STATIC_DIR), "index.html")
+}
+
+/// A relay that can't bind only loses the spectators, the web server still starts
+fn start_relay(viewer_addr: &str, runner_addr: &str, stdin: bool) {
+    let relay = Relay::new();
+
+    if let Err(e) = relay::serve_viewers(relay.clone(), viewer_addr) {
+        eprintln!("Couldn't start viewer relay on {}: {}", viewer_addr, e);
+        return;
+    }
+
+    if let Err(e) = relay::serve_runners(relay.clone(), runner_addr) {
+        eprintln!("Couldn't start runner relay on {}: {}", runner_addr, e);
+    }
+
+    if stdin {
+        relay::serve_stdin(relay);
+    }
 }
 
 fn main() {
-    rocket::ignite().mount("/", routes![index, files]).launch();
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(|x| x.as_str()) == Some("match") {
+        if let Err(e) = runner::run(&args[2..]) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // The spectator relay is opt-in, `--relay-stdin` implies it
+    if args.iter().any(|x| x == "--relay" || x == "--relay-stdin") {
+        let value = |flag: &str| {
+            args.iter()
+                .position(|x| x == flag)
+                .and_then(|i| args.get(i + 1))
+                .map(|x| x.as_str())
+        };
+        let viewer_addr = value("--relay-addr").unwrap_or(relay::VIEWER_ADDR);
+        let runner_addr = value("--relay-runner-addr").unwrap_or(relay::RUNNER_ADDR);
+
+        start_relay(viewer_addr, runner_addr, args.iter().any(|x| x == "--relay-stdin"));
+    }
+
+    rocket::ignite().mount("/", routes![index, files, universes::upload, presets::upload]).launch();
 }
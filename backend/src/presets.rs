@@ -0,0 +1,44 @@
+//! Shared settings presets, one file per settings class under `presets/`.
+
+use rocket::data::Data;
+use rocket::http::Status;
+use rocket::response::status;
+
+use std::fs;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::path::Path;
+
+use crate::files::{self, STATIC_DIR};
+use crate::universes::{check_local, check_name, reject, Rejection};
+
+const PRESET_DIR: &str = "presets";
+
+/// Presets are small, this is plenty
+const MAX_SIZE: u64 = 256 * 1024;
+
+/// Replace the presets of a class, like `PUT /presets/planets.json`.
+/// The body has to be a `pw_settings::Presets`, what's in the presets is checked when they're loaded.
+#[put("/presets/<name>", data = "<data>")]
+pub fn upload(name: String, data: Data, remote: SocketAddr) -> Result<status::Created<()>, Rejection> {
+    check_local(remote)?;
+    check_name(&name)?;
+
+    let mut content = String::new();
+    data.open()
+        .take(MAX_SIZE + 1)
+        .read_to_string(&mut content)
+        .map_err(|e| reject(Status::BadRequest, e))?;
+    if content.len() as u64 > MAX_SIZE {
+        return Err(reject(Status::PayloadTooLarge, "presets are too big"));
+    }
+
+    serde_json::from_str::<pw_settings::Presets>(&content).map_err(|e| reject(Status::UnprocessableEntity, e))?;
+
+    let root = Path::new(STATIC_DIR);
+    fs::create_dir_all(root.join(PRESET_DIR)).map_err(|e| reject(Status::InternalServerError, e))?;
+    let dir = files::resolve(root, PRESET_DIR).map_err(|e| reject(Status::InternalServerError, format!("{:?}", e)))?;
+    fs::write(dir.join(&name), content).map_err(|e| reject(Status::InternalServerError, e))?;
+
+    Ok(status::Created(format!("/{}/{}", PRESET_DIR, name), None))
+}
@@ -0,0 +1,208 @@
+use std::io::{BufRead, BufReader, Read};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tungstenite::{Error, Message};
+
+/// Viewers connect here with a websocket, pass `--relay-addr 0.0.0.0:8001` to let teammates in
+pub const VIEWER_ADDR: &str = "127.0.0.1:8001";
+/// A match runner connects here and writes one json game state per line,
+/// change it with `--relay-runner-addr` on both the server and `backend match`
+pub const RUNNER_ADDR: &str = "127.0.0.1:8002";
+
+/// How long a viewer waits for a message from the browser before it checks for new frames
+const VIEWER_POLL: Duration = Duration::from_millis(50);
+
+#[derive(Default)]
+struct RelayState {
+    snapshot: Option<String>,
+    viewers: Vec<mpsc::Sender<String>>,
+}
+
+/// Fans out game state frames from a match runner to all connected viewers.
+/// Late joiners start with the latest frame.
+#[derive(Clone, Default)]
+pub struct Relay {
+    inner: Arc<Mutex<RelayState>>,
+}
+
+impl Relay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn publish(&self, frame: String) {
+        let mut state = self.inner.lock().unwrap();
+
+        // Disconnected viewers are dropped here
+        state.viewers.retain(|tx| tx.send(frame.clone()).is_ok());
+        state.snapshot = Some(frame);
+    }
+
+    pub fn subscribe(&self) -> mpsc::Receiver<String> {
+        let (tx, rx) = mpsc::channel();
+        let mut state = self.inner.lock().unwrap();
+
+        if let Some(snapshot) = &state.snapshot {
+            tx.send(snapshot.clone()).unwrap();
+        }
+        state.viewers.push(tx);
+
+        rx
+    }
+
+    /// Publish every line of `input` that is valid json, other lines are logged and skipped.
+    pub fn publish_lines<R: Read>(&self, input: R) {
+        for line in BufReader::new(input).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    eprintln!("Relay input failed {:?}", e);
+                    break;
+                }
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<serde_json::Value>(&line) {
+                Ok(_) => self.publish(line),
+                Err(e) => eprintln!("Relay dropped invalid frame {:?}", e),
+            }
+        }
+    }
+}
+
+fn serve_viewer(relay: Relay, stream: TcpStream) {
+    let mut socket = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(e) => {
+            eprintln!("Websocket handshake failed {:?}", e);
+            return;
+        }
+    };
+
+    // Reads time out, so pings and closes get handled in between frames
+    if let Err(e) = socket.get_ref().set_read_timeout(Some(VIEWER_POLL)) {
+        eprintln!("Viewer setup failed {:?}", e);
+        return;
+    }
+
+    let frames = relay.subscribe();
+    loop {
+        // Viewers don't send anything we need, tungstenite answers pings and closes by itself
+        match socket.read_message() {
+            Ok(_) => {}
+            Err(Error::Io(ref e))
+                if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(_) => break,
+        }
+
+        loop {
+            match frames.try_recv() {
+                Ok(frame) => {
+                    if socket.write_message(Message::Text(frame)).is_err() {
+                        return;
+                    }
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => return,
+            }
+        }
+
+        // Pongs and close replies are only queued by the read
+        if socket.write_pending().is_err() {
+            break;
+        }
+    }
+}
+
+/// Accept websocket viewers on `addr`, every viewer gets its own thread.
+pub fn serve_viewers<A: ToSocketAddrs>(relay: Relay, addr: A) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming().filter_map(|x| x.ok()) {
+            let relay = relay.clone();
+            thread::spawn(move || serve_viewer(relay, stream));
+        }
+    });
+
+    Ok(())
+}
+
+/// Accept match runners on `addr`, they send one json frame per line.
+pub fn serve_runners<A: ToSocketAddrs>(relay: Relay, addr: A) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming().filter_map(|x| x.ok()) {
+            let relay = relay.clone();
+            thread::spawn(move || relay.publish_lines(stream));
+        }
+    });
+
+    Ok(())
+}
+
+/// Stand-in for a match runner, frames are read from stdin.
+pub fn serve_stdin(relay: Relay) {
+    thread::spawn(move || relay.publish_lines(std::io::stdin()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publishes_to_every_viewer() {
+        let relay = Relay::new();
+        let first = relay.subscribe();
+        let second = relay.subscribe();
+
+        relay.publish("1".to_string());
+        relay.publish("2".to_string());
+
+        for viewer in &[first, second] {
+            assert_eq!(viewer.try_iter().collect::<Vec<_>>(), vec!["1", "2"]);
+        }
+    }
+
+    #[test]
+    fn late_viewers_start_with_the_snapshot() {
+        let relay = Relay::new();
+        relay.publish("1".to_string());
+        relay.publish("2".to_string());
+
+        let late = relay.subscribe();
+        relay.publish("3".to_string());
+
+        assert_eq!(late.try_iter().collect::<Vec<_>>(), vec!["2", "3"]);
+    }
+
+    #[test]
+    fn drops_disconnected_viewers() {
+        let relay = Relay::new();
+        drop(relay.subscribe());
+        let viewer = relay.subscribe();
+
+        relay.publish("1".to_string());
+
+        assert_eq!(relay.inner.lock().unwrap().viewers.len(), 1);
+        assert_eq!(viewer.try_recv(), Ok("1".to_string()));
+    }
+
+    #[test]
+    fn skips_invalid_lines() {
+        let relay = Relay::new();
+        let viewer = relay.subscribe();
+
+        let input = "{\"turn\": 1}\nnot json\n\n{\"turn\": 2\n[2]\n";
+        relay.publish_lines(input.as_bytes());
+
+        assert_eq!(viewer.try_iter().collect::<Vec<_>>(), vec!["{\"turn\": 1}", "[2]"]);
+    }
+}
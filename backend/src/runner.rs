@@ -0,0 +1,358 @@
+use crate::files::STATIC_DIR;
+use crate::game::{GameState, Order, PlanetWars};
+use crate::relay::RUNNER_ADDR;
+
+use serde::Serialize;
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const USAGE: &str = "usage: backend match <map> <bot command>... \
+    [--turns N] [--timeout MS] [--delay MS] [--out FILE] [--relay] [--relay-runner-addr ADDR]";
+
+const DEFAULT_TURNS: u64 = 200;
+const DEFAULT_TIMEOUT_MS: u64 = 1000;
+
+struct Config {
+    map: PathBuf,
+    bots: Vec<String>,
+    max_turns: u64,
+    timeout: Duration,
+    delay: Duration,
+    out: Option<PathBuf>,
+    /// Where to send frames for the spectators, if anywhere
+    relay: Option<String>,
+}
+
+impl Config {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut max_turns = DEFAULT_TURNS;
+        let mut timeout = DEFAULT_TIMEOUT_MS;
+        let mut delay = 0;
+        let mut out = None;
+        let mut relay = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} expects a value", arg));
+            let number = |x: &String| x.parse::<u64>().map_err(|_| format!("'{}' is not a number", x));
+
+            match arg.as_str() {
+                "--turns" => max_turns = number(value()?)?,
+                "--timeout" => timeout = number(value()?)?,
+                "--delay" => delay = number(value()?)?,
+                "--out" => out = Some(PathBuf::from(value()?)),
+                "--relay" => relay = relay.or_else(|| Some(RUNNER_ADDR.to_string())),
+                "--relay-runner-addr" => relay = Some(value()?.clone()),
+                _ => positional.push(arg.clone()),
+            }
+        }
+
+        if positional.len() < 3 {
+            return Err(USAGE.to_string());
+        }
+
+        Ok(Self {
+            map: PathBuf::from(positional.remove(0)),
+            bots: positional,
+            max_turns,
+            timeout: Duration::from_millis(timeout),
+            delay: Duration::from_millis(delay),
+            out,
+            relay,
+        })
+    }
+}
+
+/// A bot child process, speaking the line based protocol on stdin and stdout.
+struct Bot {
+    name: String,
+    child: Child,
+    input: mpsc::Sender<String>,
+    lines: mpsc::Receiver<String>,
+    alive: bool,
+}
+
+impl Bot {
+    /// `command` is split like a shell would, so quoted paths with spaces work
+    fn spawn(command: &str) -> Result<Self, String> {
+        let parts = shell_words::split(command).map_err(|e| format!("Invalid bot command '{}': {}", command, e))?;
+        let (program, args) = parts.split_first().ok_or("Empty bot command")?;
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| format!("Couldn't start '{}': {}", command, e))?;
+
+        let mut stdin = child.stdin.take().ok_or("Bot has no stdin")?;
+        let stdout = child.stdout.take().ok_or("Bot has no stdout")?;
+
+        // Writing too, a bot that doesn't read would block us once the pipe is full
+        let (input, states) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            for state in states {
+                if stdin.write_all(state.as_bytes()).and_then(|_| stdin.flush()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        // Reading happens on its own thread, so a turn can time out
+        let (tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let sent = line.ok().map(|line| tx.send(line).is_ok());
+                if sent != Some(true) {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            name: command.to_string(),
+            child,
+            input,
+            lines,
+            alive: true,
+        })
+    }
+
+    /// Send the state and collect order lines until `go`.
+    /// `None` means the bot crashed or didn't answer in time.
+    fn turn(&mut self, state: &str, timeout: Duration) -> Option<Vec<String>> {
+        // Anything printed after the last `go` doesn't belong to this turn
+        let late = self.lines.try_iter().filter(|x| !x.trim().is_empty()).count();
+        if late > 0 {
+            eprintln!("{} printed {} line(s) after go, ignoring them", self.name, late);
+        }
+
+        // Fails once the writer gave up on a closed pipe
+        self.input.send(format!("{}go\n", state)).ok()?;
+
+        let deadline = Instant::now() + timeout;
+        let mut orders = Vec::new();
+
+        loop {
+            let left = deadline.checked_duration_since(Instant::now())?;
+            let line = self.lines.recv_timeout(left).ok()?;
+
+            match line.trim() {
+                "go" => return Some(orders),
+                "" => {}
+                order => orders.push(order.to_string()),
+            }
+        }
+    }
+
+    fn kill(&mut self) {
+        self.alive = false;
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Everything the viewer needs to play back a match.
+#[derive(Serialize)]
+pub struct Replay {
+    players: Vec<String>,
+    map: String,
+    winner: Option<usize>,
+    turns: Vec<GameState>,
+}
+
+/// Player with the most ships left, if there is exactly one.
+fn winner(game: &PlanetWars, bots: &[Bot]) -> Option<usize> {
+    let standing: Vec<(usize, u64)> = (1..=bots.len())
+        .filter(|&p| bots[p - 1].alive && game.is_alive(p))
+        .map(|p| (p, game.ship_count(p)))
+        .collect();
+
+    let most = standing.iter().map(|x| x.1).max()?;
+    let mut best = standing.iter().filter(|x| x.1 == most);
+
+    match (best.next(), best.next()) {
+        (Some(&(player, _)), None) => Some(player),
+        _ => None,
+    }
+}
+
+fn default_out() -> PathBuf {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default();
+
+    Path::new(STATIC_DIR)
+        .join("replays")
+        .join(format!("match-{}.json", now))
+}
+
+/// Run a full match between local bots and write the replay.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let config = Config::parse(args)?;
+
+    let map = fs::read_to_string(&config.map)
+        .map_err(|e| format!("Couldn't read map {}: {}", config.map.display(), e))?;
+    let mut game = PlanetWars::parse_map(&map)?;
+
+    if game.player_count() != config.bots.len() {
+        return Err(format!(
+            "Map has {} players, but {} bots were given",
+            game.player_count(),
+            config.bots.len()
+        ));
+    }
+
+    let mut bots = config
+        .bots
+        .iter()
+        .map(|command| Bot::spawn(command))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut relay = if let Some(addr) = &config.relay {
+        match TcpStream::connect(addr) {
+            Ok(stream) => Some(stream),
+            Err(e) => {
+                eprintln!("Couldn't connect to the relay, continuing without: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut turns = Vec::new();
+    let mut record = |frame: GameState| {
+        if let Some(stream) = relay.as_mut() {
+            let line = serde_json::to_string(&frame).unwrap() + "\n";
+            if stream.write_all(line.as_bytes()).is_err() {
+                eprintln!("Lost the relay, continuing without");
+                relay = None;
+            }
+        }
+        turns.push(frame);
+    };
+
+    record(game.frame());
+
+    while game.turn < config.max_turns {
+        let playing: Vec<usize> = (1..=bots.len())
+            .filter(|&p| bots[p - 1].alive && game.is_alive(p))
+            .collect();
+
+        if playing.len() <= 1 {
+            break;
+        }
+
+        // Everybody sees the same state, orders are only executed afterwards
+        let mut orders = Vec::new();
+        for &player in &playing {
+            let bot = &mut bots[player - 1];
+            match bot.turn(&game.serialize_for(player), config.timeout) {
+                Some(lines) => orders.push((player, lines)),
+                None => {
+                    eprintln!("{} crashed or timed out in turn {}", bot.name, game.turn);
+                    bot.kill();
+                }
+            }
+        }
+
+        for (player, lines) in orders {
+            for line in lines {
+                if let Err(e) = Order::parse(&line).and_then(|order| game.issue(player, &order)) {
+                    eprintln!("Ignoring order of {}: {}", bots[player - 1].name, e);
+                }
+            }
+        }
+
+        game.step();
+        record(game.frame());
+
+        thread::sleep(config.delay);
+    }
+
+    let winner = winner(&game, &bots);
+    bots.iter_mut().for_each(Bot::kill);
+
+    let replay = Replay {
+        players: config.bots.clone(),
+        map,
+        winner,
+        turns,
+    };
+
+    let out = config.out.clone().unwrap_or_else(default_out);
+    if let Some(parent) = out.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(&out, serde_json::to_string(&replay).unwrap()).map_err(|e| e.to_string())?;
+
+    match winner {
+        Some(player) => println!("Winner: {}", config.bots[player - 1]),
+        None => println!("Draw"),
+    }
+    println!("Replay written to {}", out.display());
+
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Answers every turn without orders
+    const IDLE_BOT: &str = r#"sh -c 'while read line; do [ "$line" = go ] && echo go; done'"#;
+    /// Never reads or answers
+    const STALLING_BOT: &str = "sleep 10";
+
+    #[test]
+    fn stalling_bots_lose() {
+        let dir = TempDir::new().unwrap();
+        let map = dir.path().join("map.txt");
+        let out = dir.path().join("replays/match.json");
+        fs::write(&map, "P 0 0 1 10 1\nP 5 0 2 50 5\n").unwrap();
+
+        let args: Vec<String> = vec![
+            map.to_str().unwrap(),
+            IDLE_BOT,
+            STALLING_BOT,
+            "--timeout",
+            "200",
+            "--out",
+            out.to_str().unwrap(),
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+        let started = Instant::now();
+        run(&args).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let replay: serde_json::Value = serde_json::from_str(&fs::read_to_string(&out).unwrap()).unwrap();
+        assert_eq!(replay["winner"], 1);
+        assert_eq!(replay["players"][1], STALLING_BOT);
+        // The start and the turn the stalling bot was dropped in
+        assert_eq!(replay["turns"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let args = |x: &[&str]| x.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(run(&args(&["map.txt", IDLE_BOT])).unwrap_err(), USAGE);
+        assert!(run(&args(&["map.txt", IDLE_BOT, IDLE_BOT, "--turns", "x"])).is_err());
+        assert!(run(&args(&["missing.txt", IDLE_BOT, IDLE_BOT])).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+//! Uploading universe files, they're checked against the schema the frontend generates.
+
+use rocket::data::Data;
+use rocket::http::Status;
+use rocket::response::status;
+
+use std::fs;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::path::{Component, Path};
+
+use crate::files::{self, STATIC_DIR};
+
+/// Written out by `WebGl.universe_schema()` in the frontend, a frontend test checks it's up to date.
+const SCHEMA: &str = "universe.schema.json";
+const UNIVERSE_DIR: &str = "universes";
+/// Shipped with the frontend, uploads can't replace it
+const DEFAULT_UNIVERSE: &str = "universe.json";
+
+/// Way bigger than any real universe
+const MAX_SIZE: u64 = 1 << 20;
+
+pub type Rejection = status::Custom<String>;
+
+pub fn reject<E: ToString>(status: Status, e: E) -> Rejection {
+    status::Custom(status, e.to_string())
+}
+
+/// Only plain `something.json` names, uploads can't create directories.
+pub fn check_name(name: &str) -> Result<(), Rejection> {
+    let mut components = Path::new(name).components();
+    let plain = match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => true,
+        _ => false,
+    };
+
+    if !plain || name.starts_with('.') || !name.ends_with(".json") {
+        return Err(reject(Status::BadRequest, format!("invalid file name '{}'", name)));
+    }
+    Ok(())
+}
+
+fn schema() -> Result<serde_json::Value, Rejection> {
+    let path = Path::new(STATIC_DIR).join(SCHEMA);
+    let content = fs::read_to_string(&path)
+        .map_err(|e| reject(Status::InternalServerError, format!("can't read {:?}: {}", path, e)))?;
+    serde_json::from_str(&content).map_err(|e| reject(Status::InternalServerError, e))
+}
+
+/// There are no accounts, only whoever runs the backend can change what it serves
+pub fn check_local(remote: SocketAddr) -> Result<(), Rejection> {
+    if remote.ip().is_loopback() {
+        Ok(())
+    } else {
+        Err(reject(Status::Forbidden, "uploads are only accepted from this machine"))
+    }
+}
+
+/// Store a universe under `universes/`, replacing one with the same name, except for the default one.
+/// Invalid files are refused with the reason, like `'planets[2].owner': 12 is not between 0 and 8`.
+#[put("/universes/<name>", data = "<data>")]
+pub fn upload(name: String, data: Data, remote: SocketAddr) -> Result<status::Created<()>, Rejection> {
+    check_local(remote)?;
+    check_name(&name)?;
+    if name == DEFAULT_UNIVERSE {
+        return Err(reject(Status::Forbidden, format!("'{}' is the default universe, pick another name", name)));
+    }
+
+    let mut content = String::new();
+    data.open()
+        .take(MAX_SIZE + 1)
+        .read_to_string(&mut content)
+        .map_err(|e| reject(Status::BadRequest, e))?;
+    if content.len() as u64 > MAX_SIZE {
+        return Err(reject(Status::PayloadTooLarge, "universe is too big"));
+    }
+
+    let value: serde_json::Value = serde_json::from_str(&content).map_err(|e| reject(Status::BadRequest, e))?;
+    pw_settings::validate(&schema()?, &value).map_err(|e| reject(Status::UnprocessableEntity, e))?;
+
+    let dir = files::resolve(Path::new(STATIC_DIR), UNIVERSE_DIR)
+        .map_err(|e| reject(Status::InternalServerError, format!("{:?}", e)))?;
+    fs::write(dir.join(&name), content).map_err(|e| reject(Status::InternalServerError, e))?;
+
+    Ok(status::Created(format!("/{}/{}", UNIVERSE_DIR, name), None))
+}
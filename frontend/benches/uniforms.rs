@@ -0,0 +1,155 @@
+//! Setting the world matrix and center of every instance, like `Scene::update_uniforms` does every frame.
+//! `channel_*` runs the handles and the `BatchRenderable::update` drain loop from before the instance store,
+//! copied here as they were, with the real `UniformMat4`. Drawing needs a GL context, so it isn't measured.
+//! Run with `cargo +nightly bench`.
+
+#![feature(test)]
+extern crate test;
+
+use cgmath::{Matrix4, Vector3, Vector4};
+use frontend::instances::Instances;
+use frontend::uniform::{Uniform, UniformMat4};
+use test::Bencher;
+
+const INSTANCES: usize = 1000;
+
+fn world(i: usize) -> Matrix4<f32> {
+    Matrix4::from_translation(Vector3::new(i as f32, 0.0, 0.0))
+}
+
+fn center(world: Matrix4<f32>) -> Vector3<f32> {
+    (world * Vector4::new(0.0, 0.0, 0.0, 1.0)).truncate()
+}
+
+/// The removed channel path, trimmed to what's used here
+mod channel {
+    use super::*;
+    use std::{collections::HashMap, sync::mpsc};
+
+    #[derive(Debug, Clone)]
+    pub struct UniformsHandle {
+        inner: mpsc::Sender<UniformUpdate>,
+    }
+
+    impl UniformsHandle {
+        pub fn single<S: Into<String>, U: Uniform + 'static>(&self, name: S, uniform: U) -> Option<()> {
+            self.inner
+                .send(UniformUpdate::Single(name.into(), Box::new(uniform)))
+                .ok()
+        }
+        pub fn center(&self, center: Vector3<f32>) -> Option<()> {
+            self.inner.send(UniformUpdate::Center(center)).ok()
+        }
+    }
+
+    // Only two of them are sent here, the drain loop still matches all of them
+    #[allow(dead_code)]
+    pub enum UniformUpdate {
+        Single(String, Box<dyn Uniform>),
+        Batch(HashMap<String, Box<dyn Uniform>>),
+        Disable,
+        Enable,
+        Remove,
+        Center(Vector3<f32>),
+    }
+
+    #[derive(Default)]
+    pub struct BatchRenderable {
+        uniforms: Vec<(mpsc::Receiver<UniformUpdate>, HashMap<String, Box<dyn Uniform>>)>,
+        disabled: Vec<bool>,
+        centers: Vec<Vector3<f32>>,
+    }
+
+    impl BatchRenderable {
+        pub fn push(&mut self) -> UniformsHandle {
+            let (tx, rx) = mpsc::channel();
+            self.uniforms.push((rx, HashMap::new()));
+            self.disabled.push(false);
+            self.centers.push(Vector3::new(0.0, 0.0, 0.0));
+            UniformsHandle { inner: tx }
+        }
+
+        pub fn update(&mut self) -> Option<()> {
+            let mut removed = Vec::new();
+            let instances = self.uniforms.iter_mut().zip(&mut self.disabled).zip(&mut self.centers);
+            for (i, (((rx, uniforms), ref mut disabled), center)) in instances.enumerate() {
+                loop {
+                    match rx.try_recv() {
+                        Ok(UniformUpdate::Batch(context)) => {
+                            uniforms.extend(context.into_iter());
+                        }
+                        Ok(UniformUpdate::Single(name, uniform)) => {
+                            uniforms.insert(name, uniform);
+                        }
+                        Ok(UniformUpdate::Disable) => {
+                            **disabled = true;
+                        }
+                        Ok(UniformUpdate::Enable) => {
+                            **disabled = false;
+                        }
+                        Ok(UniformUpdate::Remove) => {
+                            removed.push(i);
+                            break;
+                        }
+                        Ok(UniformUpdate::Center(c)) => {
+                            *center = c;
+                        }
+                        Err(mpsc::TryRecvError::Disconnected) => return None,
+                        Err(mpsc::TryRecvError::Empty) => break,
+                    }
+                }
+            }
+
+            for i in removed.into_iter().rev() {
+                self.uniforms.remove(i);
+                self.disabled.remove(i);
+                self.centers.remove(i);
+            }
+            Some(())
+        }
+    }
+}
+
+#[bench]
+fn channel_world_matrices(b: &mut Bencher) {
+    let mut batch = channel::BatchRenderable::default();
+    let handles: Vec<_> = (0..INSTANCES).map(|_| batch.push()).collect();
+
+    b.iter(|| {
+        for (i, handle) in handles.iter().enumerate() {
+            let world = world(i);
+            handle.single("u_world", UniformMat4::new_mat4(world));
+            handle.center(center(world));
+        }
+        batch.update();
+    });
+}
+
+#[bench]
+fn named_world_matrices(b: &mut Bencher) {
+    let instances = Instances::new();
+    let handles: Vec<_> = (0..INSTANCES).map(|_| instances.push()).collect();
+
+    b.iter(|| {
+        for (i, handle) in handles.iter().enumerate() {
+            let world = world(i);
+            handle.single("u_world", world);
+            handle.center(center(world));
+        }
+    });
+}
+
+#[bench]
+fn slot_world_matrices(b: &mut Bencher) {
+    let instances = Instances::new();
+    let handles: Vec<_> = (0..INSTANCES).map(|_| instances.push()).collect();
+    let slot = instances.slot::<Matrix4<f32>>("u_world");
+
+    b.iter(|| {
+        for (i, handle) in handles.iter().enumerate() {
+            let world = world(i);
+            handle.set(slot, world);
+            handle.center(center(world));
+        }
+    });
+}
@@ -6,3 +6,10 @@ cd "$SRCDIR" # "$SRCDIR" ensures that this script can be run from anywhere.
 
 wasm-pack build --target web --out-name wasm --out-dir ./static
 
+# Pre-compressed variants, the backend serves these when the browser accepts them
+for file in static/wasm_bg.wasm static/wasm.js; do
+    gzip -9 -k -f "$file"
+    if command -v brotli > /dev/null; then
+        brotli -q 11 -k -f "$file"
+    fi
+done
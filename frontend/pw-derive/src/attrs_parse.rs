@@ -14,6 +14,11 @@ enum MAttrPriv {
 impl Parse for MAttrPriv {
     fn parse(input: ParseStream<'_>) -> Result<Self> {
         let ident: Ident = input.parse()?;
+
+        // A bare key like `skip` is a flag
+        if !input.peek(Token![=]) {
+            return Ok(MAttrPriv::Lit(ident, quote!{true}));
+        }
         input.parse::<Token![=]>()?;
 
         if input.peek(syn::token::Bracket) {
@@ -24,8 +29,10 @@ impl Parse for MAttrPriv {
             let value = match input.parse::<Option<Ident>>()? {
                 Some(ident) => quote!{#ident},
                 None => {
+                    // Negative numbers are not literals on their own
+                    let minus = input.parse::<Option<Token![-]>>()?;
                     let thing: syn::Lit = input.parse()?;
-                    quote!{#thing}
+                    quote!{#minus #thing}
                 },
             };
 
@@ -48,17 +55,46 @@ impl MAttr {
         }
     }
 
-    pub fn lit(self) -> TokenStream2 {
+    pub fn lit(self, key: &Ident) -> Result<TokenStream2> {
         match self {
-            MAttr::Lit(x) => x,
-            MAttr::Stream(_) => panic!("Expected literal, found bracketed"),
+            MAttr::Lit(x) => Ok(x),
+            MAttr::Stream(_) => Err(Error::new(
+                key.span(),
+                format!("expected a value like `{} = ...`, found brackets", key),
+            )),
         }
     }
 
-    pub fn stream(self) -> TokenStream2 {
+    pub fn stream(self, key: &Ident) -> Result<TokenStream2> {
         match self {
-            MAttr::Stream(x) => x,
-            MAttr::Lit(_) => panic!("Expected bracketed, found literal"),
+            MAttr::Stream(x) => Ok(x),
+            MAttr::Lit(_) => Err(Error::new(
+                key.span(),
+                format!("expected brackets like `{} = [...]`, found a value", key),
+            )),
+        }
+    }
+
+    /// The value as a number, if it is a number literal
+    pub fn number(&self) -> Option<f64> {
+        let lit = match self {
+            MAttr::Lit(x) => x.clone(),
+            MAttr::Stream(_) => return None,
+        };
+
+        let number = |lit: &syn::Lit| match lit {
+            syn::Lit::Float(x) => x.base10_parse::<f64>().ok(),
+            syn::Lit::Int(x) => x.base10_parse::<f64>().ok(),
+            _ => None,
+        };
+
+        match syn::parse2::<syn::Expr>(lit).ok()? {
+            syn::Expr::Lit(x) => number(&x.lit),
+            syn::Expr::Unary(syn::ExprUnary { op: syn::UnOp::Neg(_), expr, .. }) => match *expr {
+                syn::Expr::Lit(x) => number(&x.lit).map(|x| -x),
+                _ => None,
+            },
+            _ => None,
         }
     }
 
@@ -78,17 +114,34 @@ impl MAttr {
 }
 
 pub struct MAttrs(pub HashMap<Ident, MAttr>);
+
+impl MAttrs {
+    /// Back to `key = value, key = [...]`, for when some keys were taken out
+    pub fn into_stream(self) -> TokenStream2 {
+        let pairs = self.0.into_iter().map(|(key, value)| match value {
+            MAttr::Lit(x) => quote! { #key = #x },
+            MAttr::Stream(s) => quote! { #key = [#s] },
+        });
+        quote! { #(#pairs),* }
+    }
+}
+
 impl Parse for MAttrs {
     fn parse(input: ParseStream<'_>) -> Result<Self> {
         let punc: Punctuated<MAttrPriv, Token![,]> = Punctuated::parse_separated_nonempty(input)?;
         let mut inner = HashMap::new();
 
-        punc.into_pairs().for_each(|p| {
-            match p.into_value() {
-                MAttrPriv::Lit(key, value) => inner.insert(key, MAttr::Lit(value)),
-                MAttrPriv::Stream(key, stream) => inner.insert(key, MAttr::Stream(stream)),
+        for p in punc.into_pairs() {
+            let (key, value) = match p.into_value() {
+                MAttrPriv::Lit(key, value) => (key, MAttr::Lit(value)),
+                MAttrPriv::Stream(key, stream) => (key, MAttr::Stream(stream)),
             };
-        });
+
+            if inner.contains_key(&key) {
+                return Err(Error::new(key.span(), format!("duplicate key `{}`", key)));
+            }
+            inner.insert(key, value);
+        }
 
         Ok(Self(inner))
     }
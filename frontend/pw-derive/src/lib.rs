@@ -4,7 +4,7 @@ use self::proc_macro::TokenStream;
 
 use quote::quote;
 use std::collections::HashMap;
-use syn::{parse_macro_input, Data, DataStruct, DeriveInput, Fields};
+use syn::{parse_macro_input, Data, DataEnum, DataStruct, DeriveInput, Fields};
 
 use proc_macro2::TokenStream as TokenStream2;
 use proc_macro2::Span;
@@ -48,12 +48,59 @@ fn from_cammel_case(orig: &str) -> String {
     }).collect()
 }
 
+/// All `#[settings(...)]` attributes merged, keys can only be set once.
 fn parse_attrs(attrs: &Vec<syn::Attribute>) -> syn::Result<HashMap<syn::Ident, MAttr>> {
-    attrs
-        .iter()
-        .find(|i| i.path.is_ident("settings"))
-        .map(|attr| attr.parse_args::<MAttrs>().map(|x| x.0))
-        .unwrap_or(Ok(HashMap::new()))
+    let mut out = HashMap::new();
+
+    for attr in attrs.iter().filter(|i| i.path.is_ident("settings")) {
+        for (key, value) in attr.parse_args::<MAttrs>()?.0 {
+            if out.contains_key(&key) {
+                return Err(syn::Error::new(key.span(), format!("duplicate key `{}`", key)));
+            }
+            out.insert(key, value);
+        }
+    }
+
+    Ok(out)
+}
+
+/// Attributes on a struct, an enum or a variant: `lits` are plain values, `fields` get bracketed field configs.
+fn check_attrs(attrs: &HashMap<syn::Ident, MAttr>, lits: &[&str], fields: &[String]) -> syn::Result<()> {
+    for (key, value) in attrs {
+        let name = key.to_string();
+
+        if lits.contains(&name.as_str()) {
+            value.clone().lit(key)?;
+        } else if fields.contains(&name) {
+            value.clone().stream(key)?;
+        } else {
+            let expected: Vec<&str> = lits.iter().cloned().chain(fields.iter().map(|x| x.as_str())).collect();
+            return Err(syn::Error::new(
+                key.span(),
+                format!("unknown key `{}`, expected one of: {}", key, expected.join(", ")),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// `min` and `max` can only be compared when they are both number literals
+fn check_range(map: &HashMap<syn::Ident, MAttr>) -> syn::Result<()> {
+    let get = |name: &str| map.iter().find(|(k, _)| *k == name);
+
+    if let (Some((min_key, min)), Some((_, max))) = (get("min"), get("max")) {
+        if let (Some(min), Some(max)) = (min.number(), max.number()) {
+            if min > max {
+                return Err(syn::Error::new(
+                    min_key.span(),
+                    format!("`min` ({}) is bigger than `max` ({})", min, max),
+                ));
+            }
+        }
+    }
+
+    Ok(())
 }
 
 #[derive(Default)]
@@ -89,7 +136,7 @@ impl AliasGenerator {
         ident
     }
 
-    fn reset(&mut self, i: &syn::Ident, ty: &syn::Type) -> syn::Ident {
+    fn reset<S: std::fmt::Display>(&mut self, i: S, ty: &syn::Type) -> syn::Ident {
         self.recurse.clear();
         self.recurse.push(quote! { #ty });
         self.push(i)
@@ -110,12 +157,29 @@ impl AliasGenerator {
     }
 }
 
-const IGNORE_KEYS: [&'static str;3] = ["name", "ty", "class"];
-fn gen_config_default_fields(alias_generator: &mut AliasGenerator, map: &HashMap<syn::Ident, MAttr>) -> syn::parse::Result<TokenStream2> {
+/// Config of a single field, `top` is the field itself and not something nested in it.
+/// Other keys are fields of the config struct, rustc points at them when they don't exist.
+fn gen_config_default_fields(alias_generator: &mut AliasGenerator, map: &HashMap<syn::Ident, MAttr>, top: bool) -> syn::parse::Result<TokenStream2> {
+    check_range(map)?;
+
     let mut out = Vec::new();
 
     for (key, value) in map {
-        if IGNORE_KEYS[..].contains(&key.to_string().as_str()) { continue; }
+        match key.to_string().as_str() {
+            "name" if top => {
+                value.clone().lit(key)?;
+                continue;
+            }
+            "ty" if !top => {
+                value.clone().stream(key)?;
+                continue;
+            }
+            "name" => return Err(syn::Error::new(key.span(), "`name` can only be set on the field itself")),
+            "ty" => return Err(syn::Error::new(key.span(), "`ty` is only needed for nested configs, the type of a field is known")),
+            "class" => return Err(syn::Error::new(key.span(), "`class` can only be set on the struct or enum")),
+            _ => {}
+        }
+
         match value {
             MAttr::Lit(ref lit) => {
                 out.push(quote!{
@@ -135,7 +199,7 @@ fn gen_config_default_fields(alias_generator: &mut AliasGenerator, map: &HashMap
                     }
                 };
 
-                let defaults = gen_config_default_fields(alias_generator, &attrs)?;
+                let defaults = gen_config_default_fields(alias_generator, &attrs, false)?;
                 out.push(quote! {
                     #key: #alias {
                         #defaults
@@ -151,143 +215,1045 @@ fn gen_config_default_fields(alias_generator: &mut AliasGenerator, map: &HashMap
     Ok(out.into_iter().collect())
 }
 
-#[proc_macro_derive(Settings, attributes(settings))]
-pub fn settings_derive(input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input as DeriveInput);
+/// Type parameters of the deriving type, the generated configs are generic over the same ones.
+struct GenericParams {
+    params: Vec<syn::Ident>,
+}
+
+impl GenericParams {
+    fn new(generics: &syn::Generics) -> syn::Result<Self> {
+        let mut params = Vec::new();
 
-    let attrs = match parse_attrs(&input.attrs) {
-        Ok(attrs) => attrs,
-        Err(e) => return e.to_compile_error().into(),
+        for param in &generics.params {
+            match param {
+                syn::GenericParam::Type(ty) => params.push(ty.ident.clone()),
+                _ => return Err(syn::Error::new_spanned(param, "only type parameters are supported")),
+            }
+        }
+
+        Ok(Self { params })
+    }
+
+    /// `<T, U>`, or nothing
+    fn tokens(&self) -> TokenStream2 {
+        if self.params.is_empty() {
+            quote! {}
+        } else {
+            let params = &self.params;
+            quote! { <#(#params),*> }
+        }
+    }
+
+    fn mentioned_by<T: quote::ToTokens>(&self, ty: &T) -> bool {
+        fn mentions(tokens: TokenStream2, params: &[syn::Ident]) -> bool {
+            tokens.into_iter().any(|t| match t {
+                proc_macro2::TokenTree::Ident(i) => params.contains(&i),
+                proc_macro2::TokenTree::Group(g) => mentions(g.stream(), params),
+                _ => false,
+            })
+        }
+
+        mentions(quote! { #ty }, &self.params)
+    }
+}
+
+/// The serde attributes that change the serialized form, the schema has to follow them.
+/// Malformed ones are left for serde to complain about.
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    default: bool,
+    skip: bool,
+    flatten: bool,
+}
+
+fn serde_attrs(attrs: &[syn::Attribute]) -> SerdeAttrs {
+    use syn::{Lit, Meta, NestedMeta};
+
+    let mut out = SerdeAttrs::default();
+    let metas = attrs
+        .iter()
+        .filter(|a| a.path.is_ident("serde"))
+        .filter_map(|a| a.parse_meta().ok());
+
+    for meta in metas {
+        let nested = match meta {
+            Meta::List(list) => list.nested,
+            _ => continue,
+        };
+
+        for item in nested {
+            let meta = match item {
+                NestedMeta::Meta(meta) => meta,
+                _ => continue,
+            };
+
+            let name = meta.path().get_ident().map(|i| i.to_string()).unwrap_or_default();
+            match (name.as_str(), meta) {
+                ("rename", Meta::NameValue(nv)) => {
+                    if let Lit::Str(lit) = nv.lit {
+                        out.rename = Some(lit.value());
+                    }
+                }
+                // Files are read, so the deserialize name is the one that counts
+                ("rename", Meta::List(list)) => {
+                    for item in list.nested {
+                        if let NestedMeta::Meta(Meta::NameValue(nv)) = item {
+                            if let (true, Lit::Str(lit)) = (nv.path.is_ident("deserialize"), nv.lit) {
+                                out.rename = Some(lit.value());
+                            }
+                        }
+                    }
+                }
+                ("default", _) => out.default = true,
+                ("skip", _) | ("skip_deserializing", _) => out.skip = true,
+                ("flatten", _) => out.flatten = true,
+                _ => {}
+            }
+        }
+    }
+
+    out
+}
+
+/// Everything generated for the fields of a struct or an enum variant.
+/// Fields are bound as `__<field>` so they can't clash with the generated locals.
+#[derive(Default)]
+struct FieldsCode {
+    config_fields: TokenStream2,
+    config_defaults: TokenStream2,
+    config_clones: TokenStream2,
+    aliases: TokenStream2,
+    bounds: Vec<TokenStream2>,
+    bindings: TokenStream2,
+    defaults: TokenStream2,
+    to_settings: TokenStream2,
+    from_settings: TokenStream2,
+    apply: TokenStream2,
+    apply_flattened: TokenStream2,
+    schema: TokenStream2,
+    schema_items: Vec<TokenStream2>,
+}
+
+/// `skip` or `flatten = true`
+fn flag(attrs: &mut HashMap<syn::Ident, MAttr>, name: &str) -> syn::Result<bool> {
+    let key = match attrs.keys().find(|k| *k == name) {
+        Some(key) => key.clone(),
+        None => return Ok(false),
     };
 
-    let fields = match &input.data {
-        Data::Struct(DataStruct {
-            fields: Fields::Named(fields),
-            ..
-        }) => &fields.named,
-        _ => panic!("expected a struct with named fields"),
+    let value = attrs.remove(&key).unwrap().lit(&key)?;
+    match value.to_string().as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(syn::Error::new(key.span(), format!("expected `{}`, `{} = true` or `{} = false`", key, key, key))),
+    }
+}
+
+/// A plain value like `name = "Size"`, taken out of `attrs`
+fn take_lit(attrs: &mut HashMap<syn::Ident, MAttr>, name: &str) -> syn::Result<Option<(syn::Ident, TokenStream2)>> {
+    let key = match attrs.keys().find(|k| *k == name) {
+        Some(key) => key.clone(),
+        None => return Ok(None),
     };
 
-    let mut config_fields = Vec::new();
-    let mut config_default_fields = Vec::new();
-    let mut default_settings = Vec::new();
-    let mut to_settings = Vec::new();
+    let value = attrs.remove(&key).unwrap().lit(&key)?;
+    Ok(Some((key, value)))
+}
+
+/// `///` comments as help text, wrapped lines are joined and paragraphs are kept
+fn doc_comment(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|a| a.path.is_ident("doc"))
+        .filter_map(|a| match a.parse_meta().ok()? {
+            syn::Meta::NameValue(syn::MetaNameValue { lit: syn::Lit::Str(lit), .. }) => Some(lit.value().trim().to_string()),
+            _ => None,
+        })
+        .collect();
+
+    let text = lines
+        .split(|line| line.is_empty())
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| paragraph.join(" "))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// `visible_if = "owner != 0"`, a Rust expression over the other fields of the same struct or variant.
+/// Conditions in a nested config, like `location = [ang_speed = [enabled_if = "!disabled"]]`, see the same fields.
+/// Field names are replaced by the fields themselves, anything else is left to rustc.
+fn condition(lit: TokenStream2, fields: &[String]) -> syn::Result<TokenStream2> {
+    let lit: syn::LitStr = syn::parse2(lit)?;
+    let expr: syn::Expr = lit.parse()?;
+    Ok(bind_fields(quote! { #expr }, fields))
+}
+
+fn bind_fields(tokens: TokenStream2, fields: &[String]) -> TokenStream2 {
+    use proc_macro2::{Group, TokenTree};
+
+    let mut out = TokenStream2::new();
+    // `planet.owner` and `Owner::None` aren't fields, even when the name matches
+    let mut after_access = false;
+
+    for token in tokens {
+        let access = match &token {
+            TokenTree::Punct(p) => p.as_char() == '.' || p.as_char() == ':',
+            _ => false,
+        };
+
+        match token {
+            TokenTree::Ident(ident) if !after_access && fields.contains(&ident.to_string()) => {
+                let binding = syn::Ident::new(&format!("__{}", ident), ident.span());
+                out.extend(quote::quote_spanned! { ident.span()=> (*#binding) });
+            }
+            TokenTree::Group(group) => {
+                let mut inner = Group::new(group.delimiter(), bind_fields(group.stream(), fields));
+                inner.set_span(group.span());
+                out.extend(Some(TokenTree::Group(inner)));
+            }
+            token => out.extend(Some(token)),
+        }
+
+        after_access = access;
+    }
+
+    out
+}
+
+/// `serde_default` is a `#[serde(default)]` on the container, no field is required then.
+fn gen_fields(fields: &Fields, attrs: &HashMap<syn::Ident, MAttr>, lits: &[&str], generics: &GenericParams, serde_default: bool) -> syn::Result<FieldsCode> {
+    let fields: Vec<&syn::Field> = match fields {
+        Fields::Named(fields) => fields.named.iter().collect(),
+        Fields::Unnamed(fields) => fields.unnamed.iter().collect(),
+        Fields::Unit => Vec::new(),
+    };
 
+    // Tuple fields can't be named in the struct attribute, they only have field attributes
+    let names: Vec<String> = fields.iter().filter_map(|f| f.ident.as_ref()).map(|i| i.to_string()).collect();
+    check_attrs(attrs, lits, &names)?;
+
+    let mut code = FieldsCode::default();
     let mut alias_generator = AliasGenerator::default();
 
-    for field in fields {
-        let ident = &field.ident.as_ref().expect("wtf no ident");
+    for (index, field) in fields.into_iter().enumerate() {
         let ty = &field.ty;
 
+        let (member, id, config_ident) = match &field.ident {
+            Some(ident) => (syn::Member::Named(ident.clone()), ident.to_string(), ident.clone()),
+            None => (
+                syn::Member::Unnamed(index.into()),
+                index.to_string(),
+                syn::Ident::new(&format!("_{}", index), Span::call_site()),
+            ),
+        };
+        let binding = syn::Ident::new(&format!("__{}", id), Span::call_site());
+
+        // Skipped fields are bound too, conditions can still look at them
+        code.bindings.extend(quote! {
+            #member: #binding,
+        });
+
+        let serde = serde_attrs(&field.attrs);
+        let serde_name = serde.rename.clone().unwrap_or_else(|| id.clone());
+        let required = !(serde_default || serde.default || serde.skip);
 
-        let attrs = match parse_attrs(&field.attrs).or_else(|_|
-            attrs.get(ident).cloned().map(|x| syn::parse2::<MAttrs>(x.stream()).map(|x| x.0)).unwrap_or(Ok(HashMap::new()))
-        ) {
-            Ok(e) => e,
-            Err(e) => return e.to_compile_error().into(),
+        // A field is configured on itself, or by its name in the attribute on the struct
+        let field_attrs = parse_attrs(&field.attrs)?;
+        let mut attrs = match attrs.iter().find(|(k, _)| Some(*k) == field.ident.as_ref()) {
+            Some((key, _)) if !field_attrs.is_empty() => {
+                return Err(syn::Error::new(key.span(), format!("`{}` is configured twice, here and on the field", key)));
+            }
+            Some((key, value)) => syn::parse2::<MAttrs>(value.clone().stream(key)?)?.0,
+            None => field_attrs,
         };
 
-        let alias_ident = alias_generator.reset(ident, ty);
+        // Skipped fields are left out of everything, they just get their default
+        if flag(&mut attrs, "skip")? {
+            if let Some(key) = attrs.keys().next() {
+                return Err(syn::Error::new(key.span(), "skipped fields can't be configured"));
+            }
 
-        let id = ident.to_string();
-        let name = attrs
-            .get(&syn::Ident::new("name", Span::call_site())).map(|x| x.clone().lit())
-            .unwrap_or(quote! { #id });
+            code.defaults.extend(quote! {
+                #member: Default::default(),
+            });
+            code.from_settings.extend(quote! {
+                #member: Default::default(),
+            });
 
+            // Still in the file, but its type says nothing about it
+            if field.ident.is_none() {
+                code.schema_items.push(quote! { ::pw_settings::Value::Bool(true) });
+            } else if !serde.skip {
+                code.schema.extend(quote! {
+                    schema.add_property(#serde_name, ::pw_settings::Value::Bool(true), #required);
+                });
+            }
+            continue;
+        }
 
-        config_fields.push(quote! {
-            pub #ident: <#ty as ::pw_settings::FieldTrait>::Config,
-        });
+        let flatten = flag(&mut attrs, "flatten")?;
+        let read_only = flag(&mut attrs, "read_only")?;
+        if flatten && read_only {
+            return Err(syn::Error::new_spanned(field, "flattened fields can't be read only, their fields can"));
+        }
+
+        // Hints for the panel, the rest of the attributes is config
+        let name = take_lit(&mut attrs, "name")?;
+        let help = take_lit(&mut attrs, "help")?;
+        let unit = take_lit(&mut attrs, "unit")?;
+        let group = take_lit(&mut attrs, "group")?;
+        let order = take_lit(&mut attrs, "order")?;
+        let visible_if = take_lit(&mut attrs, "visible_if")?;
+        let enabled_if = take_lit(&mut attrs, "enabled_if")?;
+
+        // The nested type can't see the fields next to this one, so this one checks its nested conditions
+        let mut nested = Vec::new();
+        for (key, value) in attrs.iter_mut() {
+            let mut inner = match value {
+                MAttr::Stream(stream) => syn::parse2::<MAttrs>(stream.clone())?,
+                MAttr::Lit(_) => continue,
+            };
+            let nested_visible = take_lit(&mut inner.0, "visible_if")?;
+            let nested_enabled = take_lit(&mut inner.0, "enabled_if")?;
+
+            if let Some((cond, _)) = nested_visible.iter().chain(&nested_enabled).next() {
+                if flatten {
+                    return Err(syn::Error::new(cond.span(), format!("flattened fields have no {} of their own", cond)));
+                }
+                nested.push((key.clone(), nested_visible, nested_enabled));
+                *value = MAttr::Stream(inner.into_stream());
+            }
+        }
+        // Nothing left to configure
+        attrs.retain(|_, value| !matches!(value, MAttr::Stream(s) if s.is_empty()));
 
-        let defaults = match gen_config_default_fields(&mut alias_generator, &attrs) {
-            Ok(x) => x,
-            Err(e) => return e.to_compile_error().into(),
+        if flatten {
+            let hints = name.iter().chain(&help).chain(&unit).chain(&group).chain(&order);
+            if let Some((key, _)) = hints.chain(&visible_if).chain(&enabled_if).next() {
+                return Err(syn::Error::new(key.span(), format!("flattened fields have no {} of their own", key)));
+            }
+        }
+
+        let help = match help {
+            Some((_, help)) => Some(help),
+            None => doc_comment(&field.attrs).map(|doc| quote! { #doc }),
+        };
+        let some_string = |x: &Option<TokenStream2>| match x {
+            Some(x) => quote! { Some((#x).to_string()) },
+            None => quote! { None },
+        };
+        let info = {
+            let help = some_string(&help);
+            let unit = some_string(&unit.map(|(_, x)| x));
+            let group = some_string(&group.map(|(_, x)| x));
+            let order = match order {
+                Some((_, x)) => quote! { Some(#x) },
+                None => quote! { None },
+            };
+            quote! {
+                ::pw_settings::FieldInfo {
+                    help: #help,
+                    unit: #unit,
+                    group: #group,
+                    order: #order,
+                }
+            }
         };
 
-        config_default_fields.push(quote! {
-            #ident: #alias_ident {
-                #defaults
-                ..Default::default()
-            },
+        // Hidden or disabled fields refuse edits from the panel, which gets the evaluated state
+        let conditional = visible_if.is_some() || enabled_if.is_some();
+        let visible = match visible_if {
+            Some((_, x)) => condition(x, &names)?,
+            None => quote! { true },
+        };
+        let enabled = match enabled_if {
+            Some((_, x)) => condition(x, &names)?,
+            None => quote! { true },
+        };
+
+        let mut nested_states = TokenStream2::new();
+        let mut nested_apply = TokenStream2::new();
+        for (key, visible_if, enabled_if) in nested {
+            let nested_id = key.to_string();
+            let visible = match visible_if {
+                Some((_, x)) => condition(x, &names)?,
+                None => quote! { true },
+            };
+            let enabled = match enabled_if {
+                Some((_, x)) => condition(x, &names)?,
+                None => quote! { true },
+            };
+
+            // The config has a field for every nested field, rustc points at misspelled ones
+            nested_states.extend(quote::quote_spanned! { key.span()=>
+                let _ = &config.#config_ident.#key;
+            });
+            nested_states.extend(quote! {
+                let found = field.add_nested_state(#nested_id, ::pw_settings::FieldState {
+                    hidden: !(#visible),
+                    disabled: !(#enabled),
+                });
+                debug_assert!(found, "{} has no settings field {}", #id, #nested_id);
+            });
+            nested_apply.extend(quote! {
+                #id if access == ::pw_settings::Access::Edit
+                    && ::pw_settings::edits_field(rest, &value, #nested_id)
+                    && !((#visible) && (#enabled)) =>
+                {
+                    Err(SettingsError::new(ErrorKind::Disabled)
+                        .at(PathSegment::Field(#nested_id.to_string()))
+                        .at(segment))
+                }
+            });
+        }
+
+        let field_trait = if flatten {
+            quote! { ::pw_settings::SettingsTrait }
+        } else {
+            quote! { ::pw_settings::FieldTrait }
+        };
+
+        if generics.mentioned_by(ty) {
+            code.bounds.push(quote! { #ty: #field_trait });
+        }
+
+        let name = match name {
+            Some((_, name)) => name,
+            None => quote! { #id },
+        };
+
+        code.config_fields.extend(quote! {
+            pub #config_ident: <#ty as #field_trait>::Config,
         });
 
-        default_settings.push(quote! {
-            #ident: <#ty as ::pw_settings::FieldTrait>::default_self(&config.#ident),
+        code.config_clones.extend(quote! {
+            #config_ident: self.#config_ident.clone(),
         });
 
-        // if attrs.contains_key("data") {
-        //     to_settings.push(quote! {
-        //         settings.add_data(
-        //             #id,
-        //             &self.#ident
-        //         );
-        //     });
-        // } else {
-            to_settings.push(quote! {
-                settings.add_field(
-                    #id, #name,
-                    self.#ident.to_field(&config.#ident)
-                );
+        if attrs.is_empty() {
+            code.config_defaults.extend(quote! {
+                #config_ident: Default::default(),
             });
-        // }
+        } else if generics.mentioned_by(ty) {
+            // The config type of a type parameter can't be named in a struct literal
+            let key = attrs.keys().next().unwrap();
+            return Err(syn::Error::new(key.span(), "fields with a generic type can't be configured"));
+        } else {
+            let alias_ident = alias_generator.reset(format!("field_{}", config_ident), ty);
+            let defaults = gen_config_default_fields(&mut alias_generator, &attrs, true)?;
+
+            code.config_defaults.extend(quote! {
+                #config_ident: #alias_ident {
+                    #defaults
+                    ..Default::default()
+                },
+            });
+        }
+
+        let field_schema = if flatten {
+            quote! { <#ty as ::pw_settings::SettingsTrait>::json_schema_with(&config.#config_ident) }
+        } else if read_only {
+            quote! { ::pw_settings::read_only_schema(<#ty as ::pw_settings::FieldTrait>::field_schema(&config.#config_ident)) }
+        } else {
+            quote! { <#ty as ::pw_settings::FieldTrait>::field_schema(&config.#config_ident) }
+        };
+        let field_schema = match &help {
+            Some(help) if !flatten => quote! { ::pw_settings::describe_schema(#field_schema, &(#help).to_string()) },
+            _ => field_schema,
+        };
+
+        // Settings flattening is only about the ui, the file follows serde
+        if field.ident.is_none() {
+            code.schema_items.push(field_schema);
+        } else if serde.flatten {
+            code.schema.extend(quote! {
+                schema.add_flattened(#field_schema);
+            });
+        } else if !serde.skip {
+            code.schema.extend(quote! {
+                schema.add_property(#serde_name, #field_schema, #required);
+            });
+        }
+
+        if flatten {
+            code.defaults.extend(quote! {
+                #member: <#ty as ::pw_settings::SettingsTrait>::default_settings_with(&config.#config_ident),
+            });
+
+            code.to_settings.extend(quote! {
+                settings.extend(<#ty as ::pw_settings::SettingsTrait>::to_settings_with(#binding, &config.#config_ident));
+            });
+
+            code.from_settings.extend(quote! {
+                #member: <#ty as ::pw_settings::SettingsTrait>::from_settings_with(settings, &config.#config_ident)?,
+            });
+
+            code.apply_flattened.extend(quote! {
+                if <#ty as ::pw_settings::SettingsTrait>::to_settings_with(#binding, &config.#config_ident).get(name).is_some() {
+                    return <#ty as ::pw_settings::SettingsTrait>::apply_field_as(#binding, path, value, &config.#config_ident, access);
+                }
+            });
+        } else {
+            code.defaults.extend(quote! {
+                #member: <#ty as ::pw_settings::FieldTrait>::default_self(&config.#config_ident),
+            });
+
+            let field = quote! {
+                <#ty as ::pw_settings::FieldTrait>::to_field(#binding, &config.#config_ident)
+            };
+            let field = if read_only {
+                quote! { ::pw_settings::FieldType::ReadOnly(Box::new(#field)) }
+            } else {
+                field
+            };
+            let field = if nested_states.is_empty() {
+                field
+            } else {
+                quote! {
+                    {
+                        let mut field = #field;
+                        #nested_states
+                        field
+                    }
+                }
+            };
+
+            if conditional {
+                code.to_settings.extend(quote! {
+                    settings.add_field_with_state(#id, #name, #field, #info, ::pw_settings::FieldState {
+                        hidden: !(#visible),
+                        disabled: !(#enabled),
+                    });
+                });
+                code.apply.extend(quote! {
+                    #id if access == ::pw_settings::Access::Edit && !((#visible) && (#enabled)) => {
+                        Err(SettingsError::new(ErrorKind::Disabled).at(segment))
+                    }
+                });
+            } else {
+                code.to_settings.extend(quote! {
+                    settings.add_field_with_info(#id, #name, #field, #info);
+                });
+            }
+
+            code.from_settings.extend(quote! {
+                #member: match settings.get(#id) {
+                    Some(field) => <#ty as ::pw_settings::FieldTrait>::from_field(field.inner(), &config.#config_ident)
+                        .map_err(|e| e.at(::pw_settings::PathSegment::Field(#id.to_string())))?,
+                    None => <#ty as ::pw_settings::FieldTrait>::default_self(&config.#config_ident),
+                },
+            });
+
+            if read_only {
+                code.apply.extend(quote! {
+                    #id if access == ::pw_settings::Access::Edit => Err(SettingsError::new(ErrorKind::ReadOnly).at(segment)),
+                });
+            }
+            code.apply.extend(nested_apply);
+            code.apply.extend(quote! {
+                #id => <#ty as ::pw_settings::FieldTrait>::apply_path_as(#binding, rest, value, &config.#config_ident, access)
+                    .map_err(|e| e.at(segment)),
+            });
+        }
     }
 
-    let struct_stream: TokenStream2 = config_fields.into_iter().collect();
-    let aliases: TokenStream2 = alias_generator.into_aliases().into_iter().collect();
-    let struct_stream_default: TokenStream2 = config_default_fields.into_iter().collect();
+    code.aliases = alias_generator.into_aliases().into_iter().collect();
 
-    let default_stream: TokenStream2 = default_settings.into_iter().collect();
-    let into_stream: TokenStream2 = to_settings.into_iter().collect();
+    Ok(code)
+}
 
-    let generics = input.generics;
-    let struct_name = input.ident;
+/// Schema of the serialized fields, like serde writes a struct or a variant with these fields
+fn fields_schema(fields: &Fields, code: &FieldsCode, title: &TokenStream2) -> TokenStream2 {
+    let FieldsCode { schema, schema_items, .. } = code;
 
-    let type_ident = syn::Ident::new(&format!("{}Config", struct_name), Span::call_site());
+    match fields {
+        Fields::Named(_) => quote! {
+            {
+                let mut schema = ::pw_settings::ObjectSchema::new(#title);
+                #schema
+                schema.into_value()
+            }
+        },
+        Fields::Unnamed(_) if schema_items.len() == 1 => schema_items[0].clone(),
+        Fields::Unnamed(_) => quote! {
+            ::pw_settings::tuple_schema(vec![#(#schema_items),*])
+        },
+        Fields::Unit => quote! { ::pw_settings::unit_schema() },
+    }
+}
 
+fn where_clause(generics: &syn::Generics, bounds: &[TokenStream2]) -> TokenStream2 {
+    let mut predicates: Vec<TokenStream2> = generics
+        .where_clause
+        .iter()
+        .flat_map(|w| w.predicates.iter().map(|p| quote! { #p }))
+        .collect();
+    predicates.extend(bounds.iter().cloned());
 
-    let str_struct_name = from_cammel_case(&struct_name.to_string());
+    if predicates.is_empty() {
+        quote! {}
+    } else {
+        quote! { where #(#predicates),* }
+    }
+}
 
-    let class = attrs
-        .get(&syn::Ident::new("class", Span::call_site())).map(|x| x.clone().lit())
-        .unwrap_or(quote! { #str_struct_name });
+fn gen_config(ident: &syn::Ident, code: &FieldsCode, generics: &GenericParams) -> TokenStream2 {
+    let FieldsCode { config_fields, config_defaults, config_clones, aliases, bounds, .. } = code;
 
+    let params = generics.tokens();
+    let where_clause = if bounds.is_empty() {
+        quote! {}
+    } else {
+        quote! { where #(#bounds),* }
+    };
 
-    let inner = quote! {
-        #[derive(Clone)]
-        pub struct #type_ident {
-            #struct_stream
+    // Configs don't always use every type parameter
+    let (phantom, phantom_value) = if generics.params.is_empty() {
+        (quote! {}, quote! {})
+    } else {
+        let ps = &generics.params;
+        (
+            quote! {
+                #[doc(hidden)]
+                pub __phantom: ::std::marker::PhantomData<fn() -> (#(#ps,)*)>,
+            },
+            quote! {
+                __phantom: ::std::marker::PhantomData,
+            },
+        )
+    };
+
+    quote! {
+        pub struct #ident #params #where_clause {
+            #config_fields
+            #phantom
+        }
+
+        impl #params Clone for #ident #params #where_clause {
+            fn clone(&self) -> Self {
+                Self {
+                    #config_clones
+                    #phantom_value
+                }
+            }
         }
 
-        impl Default for #type_ident {
+        impl #params Default for #ident #params #where_clause {
             fn default() -> Self {
                 #aliases
                 Self {
-                    #struct_stream_default
+                    #config_defaults
+                    #phantom_value
                 }
             }
         }
+    }
+}
 
-        impl #generics ::pw_settings::SettingsTrait for #struct_name {
-            type Config = #type_ident;
+/// `#[settings(default)]` on the struct: new ones come from its `Default` impl instead of the field configs
+fn gen_struct(input: &DeriveInput, attrs: &HashMap<syn::Ident, MAttr>, class: TokenStream2, fields: &Fields) -> syn::Result<TokenStream2> {
+    let generics = GenericParams::new(&input.generics)?;
+    let code = gen_fields(fields, attrs, &["class", "default"], &generics, serde_attrs(&input.attrs).default)?;
+    let use_default = flag(&mut attrs.clone(), "default")?;
+    let schema = fields_schema(fields, &code, &class);
+
+    let struct_name = &input.ident;
+    let type_ident = syn::Ident::new(&format!("{}Config", struct_name), Span::call_site());
+
+    let config = gen_config(&type_ident, &code, &generics);
+    let params = generics.tokens();
+    let (impl_generics, ty_generics, _) = input.generics.split_for_impl();
+    let where_clause = where_clause(&input.generics, &code.bounds);
+
+    let FieldsCode { bindings, defaults, to_settings, from_settings, apply, apply_flattened, .. } = code;
+    let defaults = if use_default {
+        quote! { ..Default::default() }
+    } else {
+        defaults
+    };
+
+    Ok(quote! {
+        #config
+
+        #[allow(unused_variables)]
+        impl #impl_generics ::pw_settings::SettingsTrait for #struct_name #ty_generics #where_clause {
+            type Config = #type_ident #params;
 
             fn default_settings_with(config: &Self::Config) -> Self {
                 Self {
-                    #default_stream
+                    #defaults
                 }
             }
 
             fn to_settings_with(&self, config: &Self::Config) -> ::pw_settings::Settings {
-                use ::pw_settings::FieldTrait;
+                let Self { #bindings .. } = self;
                 let mut settings = ::pw_settings::Settings::new(#class);
 
-                #into_stream
+                #to_settings
 
                 settings
             }
+
+            fn from_settings_with(settings: &::pw_settings::Settings, config: &Self::Config) -> Result<Self, ::pw_settings::SettingsError> {
+                Ok(Self {
+                    #from_settings
+                })
+            }
+
+            fn json_schema_with(config: &Self::Config) -> ::pw_settings::Value {
+                #schema
+            }
+
+            fn apply_field_as(
+                &mut self,
+                path: &[::pw_settings::PathSegment],
+                value: ::pw_settings::Value,
+                config: &Self::Config,
+                access: ::pw_settings::Access,
+            ) -> Result<(), ::pw_settings::SettingsError> {
+                use ::pw_settings::{PathSegment, SettingsError, ErrorKind, Value};
+
+                match path.split_first() {
+                    // A whole object, every key is a field
+                    None => match value {
+                        Value::Object(map) => ::pw_settings::apply_fields(map, |segment, value| {
+                            self.apply_field_as(std::slice::from_ref(segment), value, config, access)
+                        }),
+                        _ => Err(SettingsError::new(ErrorKind::WrongType("object"))),
+                    },
+                    Some((PathSegment::Field(name), rest)) => {
+                        let segment = PathSegment::Field(name.clone());
+                        let Self { #bindings .. } = self;
+                        match name.as_str() {
+                            #apply
+                            _ => {
+                                #apply_flattened
+                                Err(SettingsError::new(ErrorKind::UnknownField(name.clone())))
+                            }
+                        }
+                    }
+                    Some((segment, _)) => Err(SettingsError::new(ErrorKind::UnknownField(segment.to_string()))),
+                }
+            }
         }
+    })
+}
+
+/// Enums become a `Select`, the selected variant is stored as the `variant` data field.
+/// The first variant is the default one.
+fn gen_enum(input: &DeriveInput, class: TokenStream2, data: &DataEnum) -> syn::Result<TokenStream2> {
+    let generics = GenericParams::new(&input.generics)?;
+    let params = generics.tokens();
+
+    let enum_name = &input.ident;
+    let type_ident = syn::Ident::new(&format!("{}Config", enum_name), Span::call_site());
+
+    let mut configs = Vec::new();
+    let mut config = FieldsCode::default();
+    let mut current = Vec::new();
+    let mut default_variant = None;
+    let mut to_settings = Vec::new();
+    let mut from_settings = Vec::new();
+    let mut switch = Vec::new();
+    let mut apply = Vec::new();
+    let mut options = Vec::new();
+    let mut schemas = Vec::new();
+
+    for variant in &data.variants {
+        let ident = &variant.ident;
+        let attrs = parse_attrs(&variant.attrs)?;
+        let code = gen_fields(&variant.fields, &attrs, &["name"], &generics, false)?;
+
+        let id = ident.to_string();
+
+        let name = match attrs.iter().find(|(k, _)| *k == "name") {
+            Some((key, value)) => value.clone().lit(key)?,
+            None => quote! { #id },
+        };
+
+        let config_field = syn::Ident::new(&from_cammel_case(&id), Span::call_site());
+        let config_ident = syn::Ident::new(&format!("{}{}Config", enum_name, ident), Span::call_site());
+
+        let serde = serde_attrs(&variant.attrs);
+        if !serde.skip {
+            let serde_name = serde.rename.unwrap_or_else(|| id.clone());
+            let content = match variant.fields {
+                Fields::Unit => quote! { None },
+                _ => {
+                    let schema = fields_schema(&variant.fields, &code, &quote! { #serde_name });
+                    quote! { Some(#schema) }
+                }
+            };
+
+            schemas.push(quote! {
+                {
+                    let config = &config.#config_field;
+                    ::pw_settings::variant_schema(#serde_name, #content)
+                }
+            });
+        }
+
+        configs.push(gen_config(&config_ident, &code, &generics));
+        config.config_fields.extend(quote! {
+            pub #config_field: #config_ident #params,
+        });
+        config.config_defaults.extend(quote! {
+            #config_field: Default::default(),
+        });
+        config.config_clones.extend(quote! {
+            #config_field: self.#config_field.clone(),
+        });
+
+        let FieldsCode {
+            bounds,
+            bindings,
+            defaults,
+            to_settings: to_fields,
+            from_settings: from_fields,
+            apply: apply_fields,
+            apply_flattened,
+            ..
+        } = code;
+        config.bounds.extend(bounds);
+
+        let default = quote! {
+            {
+                let config = &config.#config_field;
+                Self::#ident { #defaults }
+            }
+        };
+
+        if default_variant.is_none() {
+            default_variant = Some(default.clone());
+        }
+
+        current.push(quote! {
+            Self::#ident { .. } => #id,
+        });
+
+        to_settings.push(quote! {
+            Self::#ident { #bindings .. } => {
+                let config = &config.#config_field;
+                settings.add_data("variant", #id);
+                #to_fields
+            }
+        });
+
+        from_settings.push(quote! {
+            #id => {
+                let config = &config.#config_field;
+                Ok(Self::#ident { #from_fields })
+            }
+        });
+
+        switch.push(quote! {
+            #id => {
+                if let Self::#ident { .. } = self {
+                    return Ok(());
+                }
+                #default
+            }
+        });
+
+        apply.push(quote! {
+            Self::#ident { #bindings .. } => {
+                let config = &config.#config_field;
+                match name.as_str() {
+                    #apply_fields
+                    _ => {
+                        #apply_flattened
+                        Err(SettingsError::new(ErrorKind::UnknownField(name.clone())))
+                    }
+                }
+            }
+        });
+
+        options.push(quote! {
+            options.push(::pw_settings::SelectOption::new(
+                #id, #name,
+                if let Self::#ident { .. } = self {
+                    self.to_settings_with(config)
+                } else {
+                    (#default).to_settings_with(config)
+                },
+            ));
+        });
+    }
+
+    let default_variant = match default_variant {
+        Some(default) => default,
+        None => return Err(syn::Error::new_spanned(enum_name, "expected an enum with variants")),
+    };
+
+    let config_struct = gen_config(&type_ident, &config, &generics);
+    let (impl_generics, ty_generics, _) = input.generics.split_for_impl();
+    let where_clause = where_clause(&input.generics, &config.bounds);
+
+    let configs: TokenStream2 = configs.into_iter().collect();
+    let current: TokenStream2 = current.into_iter().collect();
+    let to_settings: TokenStream2 = to_settings.into_iter().collect();
+    let from_settings: TokenStream2 = from_settings.into_iter().collect();
+    let switch: TokenStream2 = switch.into_iter().collect();
+    let apply: TokenStream2 = apply.into_iter().collect();
+    let options: TokenStream2 = options.into_iter().collect();
+
+    Ok(quote! {
+        #configs
+
+        #config_struct
+
+        #[allow(unused_variables)]
+        impl #impl_generics ::pw_settings::SettingsTrait for #enum_name #ty_generics #where_clause {
+            type Config = #type_ident #params;
+
+            fn default_settings_with(config: &Self::Config) -> Self {
+                #default_variant
+            }
+
+            fn to_settings_with(&self, config: &Self::Config) -> ::pw_settings::Settings {
+                let mut settings = ::pw_settings::Settings::new(#class);
+
+                match self {
+                    #to_settings
+                }
+
+                settings
+            }
+
+            fn from_settings_with(settings: &::pw_settings::Settings, config: &Self::Config) -> Result<Self, ::pw_settings::SettingsError> {
+                use ::pw_settings::{FieldType, PathSegment, SettingsError, ErrorKind, Value};
+
+                let segment = PathSegment::Field("variant".to_string());
+                let variant = match settings.get("variant") {
+                    Some(FieldType::Data(Value::String(variant))) => variant.as_str(),
+                    Some(_) => return Err(SettingsError::new(ErrorKind::WrongType("variant")).at(segment)),
+                    None => return Ok(Self::default_settings_with(config)),
+                };
+
+                match variant {
+                    #from_settings
+                    _ => Err(SettingsError::new(ErrorKind::UnknownVariant(variant.to_string())).at(segment)),
+                }
+            }
+
+            fn json_schema_with(config: &Self::Config) -> ::pw_settings::Value {
+                ::pw_settings::enum_schema(#class, vec![#(#schemas),*])
+            }
+
+            fn apply_field_as(
+                &mut self,
+                path: &[::pw_settings::PathSegment],
+                value: ::pw_settings::Value,
+                config: &Self::Config,
+                access: ::pw_settings::Access,
+            ) -> Result<(), ::pw_settings::SettingsError> {
+                use ::pw_settings::{PathSegment, SettingsError, ErrorKind, Value};
+
+                let variant_segment = PathSegment::Field("variant".to_string());
+
+                match path.split_first() {
+                    // A variant name, or an object with an optional variant and its fields
+                    None => match value {
+                        Value::String(_) => self.apply_field_as(std::slice::from_ref(&variant_segment), value, config, access),
+                        Value::Object(mut map) => {
+                            if let Some(variant) = map.remove("variant") {
+                                self.apply_field_as(std::slice::from_ref(&variant_segment), variant, config, access)?;
+                            }
+                            ::pw_settings::apply_fields(map, |segment, value| {
+                                self.apply_field_as(std::slice::from_ref(segment), value, config, access)
+                            })
+                        }
+                        _ => Err(SettingsError::new(ErrorKind::WrongType("object"))),
+                    },
+                    // Switching variants starts from the defaults of the new variant
+                    Some((segment, rest)) if *segment == variant_segment => {
+                        ::pw_settings::expect_leaf(rest).map_err(|e| e.at(variant_segment.clone()))?;
+                        let variant = match value {
+                            Value::String(variant) => variant,
+                            _ => return Err(SettingsError::new(ErrorKind::WrongType("variant")).at(variant_segment)),
+                        };
+
+                        let next = match variant.as_str() {
+                            #switch
+                            _ => return Err(SettingsError::new(ErrorKind::UnknownVariant(variant)).at(variant_segment)),
+                        };
+
+                        *self = next;
+                        Ok(())
+                    }
+                    Some((PathSegment::Field(name), rest)) => {
+                        let segment = PathSegment::Field(name.clone());
+                        match self {
+                            #apply
+                        }
+                    }
+                    Some((segment, _)) => Err(SettingsError::new(ErrorKind::UnknownField(segment.to_string()))),
+                }
+            }
+
+            fn to_field_with(&self, config: &Self::Config) -> ::pw_settings::FieldType {
+                let mut options = Vec::new();
+                #options
+
+                let value = match self {
+                    #current
+                };
+
+                ::pw_settings::FieldType::Select {
+                    value: value.to_string(),
+                    options,
+                }
+            }
+
+            fn from_field_with(field: &::pw_settings::FieldType, config: &Self::Config) -> Result<Self, ::pw_settings::SettingsError> {
+                use ::pw_settings::{FieldType, SettingsError, ErrorKind};
+
+                match field {
+                    FieldType::Select { value, options } => match options.iter().find(|o| o.id() == value) {
+                        Some(option) => Self::from_settings_with(option.settings(), config),
+                        None => Err(SettingsError::new(ErrorKind::UnknownVariant(value.clone()))),
+                    },
+                    FieldType::Settings(settings) => Self::from_settings_with(settings, config),
+                    _ => Err(SettingsError::new(ErrorKind::WrongType("select"))),
+                }
+            }
+        }
+    })
+}
+
+fn derive(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let attrs = parse_attrs(&input.attrs)?;
+
+    let str_struct_name = from_cammel_case(&input.ident.to_string());
+
+    let class = match attrs.iter().find(|(k, _)| *k == "class") {
+        Some((key, value)) => value.clone().lit(key)?,
+        None => quote! { #str_struct_name },
     };
 
-    println!("{}", inner);
+    match &input.data {
+        Data::Struct(DataStruct { fields, .. }) => gen_struct(input, &attrs, class, fields),
+        Data::Enum(data) => {
+            check_attrs(&attrs, &["class"], &[])?;
+            gen_enum(input, class, data)
+        }
+        Data::Union(data) => Err(syn::Error::new_spanned(data.union_token, "unions can't derive Settings")),
+    }
+}
+
+#[proc_macro_derive(Settings, attributes(settings))]
+pub fn settings_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
 
-    TokenStream::from(inner)
+    match derive(&input) {
+        Ok(inner) => TokenStream::from(inner),
+        Err(e) => e.to_compile_error().into(),
+    }
 }
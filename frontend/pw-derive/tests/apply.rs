@@ -0,0 +1,124 @@
+//! Edits by path, with `FieldTrait` and `SettingsTrait` both in scope like in the frontend.
+
+use pw_derive::Settings;
+use pw_settings::{Access, ErrorKind, FieldState, FieldTrait, FieldType, SettingsTrait, Value};
+
+#[derive(Settings, Clone, Debug)]
+struct Moon {
+    #[settings(min = 0.0, max = 10.0)]
+    size: f32,
+}
+
+#[derive(Settings, Clone, Debug)]
+struct Planet {
+    moon: Moon,
+    moons: Vec<Moon>,
+}
+
+#[derive(Settings, Clone, Debug)]
+struct Ring {
+    #[settings(read_only)]
+    name: String,
+    enabled: bool,
+    #[settings(visible_if = "enabled")]
+    width: f32,
+}
+
+#[derive(Settings, Clone, Debug)]
+struct Station {
+    docked: bool,
+    #[settings(max = 10.0, enabled_if = "!docked")]
+    thrust: f32,
+    /// Its moon can't be changed while docked, its other moons can
+    #[settings(moon = [enabled_if = "!docked"])]
+    planet: Planet,
+}
+
+fn json(text: &str) -> Value {
+    text.parse().unwrap()
+}
+
+#[test]
+fn applies_by_path() {
+    let mut planet = Planet::default_settings(None);
+
+    planet.apply_field("moon.size", Value::from(5.0), None).unwrap();
+    assert_eq!(planet.moon.size, 5.0);
+
+    assert!(planet.apply_field("moon.size", Value::from(20.0), None).is_err());
+    assert!(planet.apply_field("moon.mass", Value::from(1.0), None).is_err());
+    assert_eq!(planet.moon.size, 5.0);
+}
+
+#[test]
+fn applies_nested_fields() {
+    let mut moon = Moon::d_self();
+    let path = pw_settings::parse_path("size").unwrap();
+
+    moon.apply_path(&path, Value::from(2.0), &Default::default()).unwrap();
+    assert_eq!(moon.size, 2.0);
+}
+
+#[test]
+fn restores_past_the_panel_rules() {
+    let mut ring = Ring::default_settings(None);
+    let path = pw_settings::parse_path("width").unwrap();
+
+    assert!(ring.apply_field_with(&path, Value::from(0.5), &Default::default()).is_err());
+    assert!(ring.apply_field("name", Value::from("outer"), None).is_err());
+
+    ring.apply_field_as(&path, Value::from(0.5), &Default::default(), Access::Restore).unwrap();
+    let name = pw_settings::parse_path("name").unwrap();
+    ring.apply_field_as(&name, Value::from("outer"), &Default::default(), Access::Restore).unwrap();
+    assert_eq!((ring.width, ring.name.as_str()), (0.5, "outer"));
+
+    // Bounds still hold
+    assert!(ring.apply_field_as(&path, Value::from(2.0), &Default::default(), Access::Restore).is_err());
+}
+
+#[test]
+fn refuses_disabled_fields() {
+    let mut station = Station::default_settings(None);
+    station.apply_field("docked", Value::from(true), None).unwrap();
+
+    let error = station.apply_field("thrust", Value::from(5.0), None).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Disabled);
+    assert_eq!(error.path_string(), "thrust");
+
+    // Shown, but greyed out
+    let settings = station.to_settings(None);
+    let thrust = settings.fields().iter().find(|f| f.id() == "thrust").unwrap();
+    assert_eq!(thrust.state(), FieldState { hidden: false, disabled: true });
+
+    station.apply_field("docked", Value::from(false), None).unwrap();
+    station.apply_field("thrust", Value::from(5.0), None).unwrap();
+    assert_eq!(station.thrust, 5.0);
+}
+
+#[test]
+fn refuses_disabled_nested_fields() {
+    let mut station = Station::default_settings(None);
+    station.apply_field("docked", Value::from(true), None).unwrap();
+
+    let error = station.apply_field("planet.moon.size", Value::from(5.0), None).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Disabled);
+    assert_eq!(error.path_string(), "planet.moon");
+    // As a key of the whole planet too
+    let whole = json(r#"{ "moon": { "size": 5.0 } }"#);
+    assert!(station.apply_field("planet", whole, None).is_err());
+    station.apply_field("planet.moons", json(r#"{ "insert": { "index": 0 } }"#), None).unwrap();
+
+    let settings = station.to_settings(None);
+    let planet = match settings.get("planet") {
+        Some(FieldType::Settings(planet)) => planet,
+        field => panic!("{:?} should be settings", field),
+    };
+    let state = |id: &str| planet.fields().iter().find(|f| f.id() == id).unwrap().state();
+    assert!(state("moon").disabled);
+    assert!(!state("moons").disabled);
+
+    // Docking and the moon in one object, in either order
+    let undock = json(r#"{ "planet": { "moon": { "size": 5.0 } }, "docked": false }"#);
+    station.apply_field("", undock, None).unwrap();
+    assert_eq!(station.planet.moon.size, 5.0);
+}
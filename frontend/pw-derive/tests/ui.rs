@@ -0,0 +1,7 @@
+//! The errors the derive reports for attribute mistakes, run `TRYBUILD=overwrite cargo test` after changing one.
+
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,10 @@
+use pw_derive::Settings;
+
+#[derive(Settings, Clone)]
+enum Motion {
+    Still,
+    #[settings(mas = [min = 0.0])]
+    Gravity { mass: f32 },
+}
+
+fn main() {}
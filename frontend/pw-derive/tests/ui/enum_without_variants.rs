@@ -0,0 +1,6 @@
+use pw_derive::Settings;
+
+#[derive(Settings, Clone)]
+enum Motion {}
+
+fn main() {}
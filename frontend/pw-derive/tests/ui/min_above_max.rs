@@ -0,0 +1,9 @@
+use pw_derive::Settings;
+
+#[derive(Settings, Clone)]
+struct Planet {
+    #[settings(min = 10.0, max = 1.0)]
+    size: f32,
+}
+
+fn main() {}
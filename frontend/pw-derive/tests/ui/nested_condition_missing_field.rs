@@ -0,0 +1,15 @@
+use pw_derive::Settings;
+
+#[derive(Settings, Clone)]
+struct Moon {
+    size: f32,
+}
+
+#[derive(Settings, Clone)]
+struct Planet {
+    disabled: bool,
+    #[settings(szie = [enabled_if = "!disabled"])]
+    moon: Moon,
+}
+
+fn main() {}
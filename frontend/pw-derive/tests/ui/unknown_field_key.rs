@@ -0,0 +1,9 @@
+use pw_derive::Settings;
+
+#[derive(Settings, Clone)]
+struct Planet {
+    #[settings(mni = 1.0)]
+    size: f32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use pw_derive::Settings;
+
+#[derive(Settings, Clone)]
+#[settings(clas = "planet")]
+struct Planet {
+    size: f32,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use pw_derive::Settings;
+
+#[derive(Settings, Clone)]
+struct Ring {
+    enabled: bool,
+    #[settings(visible_if = "enabeld")]
+    size: f32,
+}
+
+fn main() {}
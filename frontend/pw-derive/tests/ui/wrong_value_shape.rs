@@ -0,0 +1,9 @@
+use pw_derive::Settings;
+
+#[derive(Settings, Clone)]
+#[settings(size = 1.0)]
+struct Planet {
+    size: f32,
+}
+
+fn main() {}
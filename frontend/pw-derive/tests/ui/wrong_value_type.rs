@@ -0,0 +1,9 @@
+use pw_derive::Settings;
+
+#[derive(Settings, Clone)]
+struct Planet {
+    #[settings(read_only = "yes")]
+    name: String,
+}
+
+fn main() {}
@@ -0,0 +1,241 @@
+use crate::*;
+use serde::Deserialize;
+
+static INT_MAX: i64 = 100;
+
+/// Integer spinners, `inc` is the step
+macro_rules! int_field {
+    ($($t:ty),*) => {$(
+        impl FieldTrait for $t {
+            type Config = FieldConfig<$t>;
+
+            fn default_self(config: &Self::Config) -> Self {
+                config.value.unwrap_or_default().max(config.min.unwrap_or_default())
+            }
+            fn to_field(&self, config: &Self::Config) -> FieldType {
+                FieldType::Int {
+                    value: *self as i64,
+                    min: config.min.map(|x| x as i64).unwrap_or(0),
+                    max: config.max.map(|x| x as i64).unwrap_or(INT_MAX),
+                    inc: config.inc.map(|x| x as i64).unwrap_or(1),
+                }
+            }
+
+            fn from_field(field: &FieldType, _: &Self::Config) -> Result<Self, SettingsError> {
+                match field {
+                    FieldType::Int { value, .. } => Ok(*value as $t),
+                    _ => Err(SettingsError::new(ErrorKind::WrongType("int"))),
+                }
+            }
+
+            fn field_schema(config: &Self::Config) -> Value {
+                number_schema(
+                    "integer",
+                    config.min.map(|x| x as f32).unwrap_or(0.0),
+                    config.max.map(|x| x as f32).unwrap_or(INT_MAX as f32),
+                    config.inc.map(|x| x as f32).unwrap_or(1.0),
+                )
+            }
+
+            fn apply_path(&mut self, path: &[PathSegment], value: Value, config: &Self::Config) -> Result<(), SettingsError> {
+                expect_leaf(path)?;
+                let value = value
+                    .as_f64()
+                    .filter(|x| x.fract() == 0.0)
+                    .ok_or(SettingsError::new(ErrorKind::WrongType("integer")))?;
+
+                check_range(
+                    value,
+                    config.min.map(|x| x as f32).unwrap_or(0.0),
+                    config.max.map(|x| x as f32).unwrap_or(INT_MAX as f32),
+                    config.inc.map(|x| x as f32).unwrap_or(1.0),
+                )?;
+
+                *self = value as $t;
+                Ok(())
+            }
+        }
+    )*};
+}
+
+int_field!(u32, i32, usize);
+
+/// Rgb color, every channel between 0 and 1
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Color(pub [f32; 3]);
+
+impl Default for Color {
+    fn default() -> Self {
+        Color([1.0, 1.0, 1.0])
+    }
+}
+
+impl Color {
+    /// Parse `#rrggbb`, like the browser's color picker
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix('#')?;
+        if hex.len() != 6 {
+            return None;
+        }
+
+        let channel = |i: usize| {
+            u8::from_str_radix(hex.get(i..i + 2)?, 16)
+                .ok()
+                .map(|x| x as f32 / 255.0)
+        };
+        Some(Color([channel(0)?, channel(2)?, channel(4)?]))
+    }
+}
+
+impl From<[f32; 3]> for Color {
+    fn from(rgb: [f32; 3]) -> Self {
+        Color(rgb)
+    }
+}
+
+fn expect_number(value: &Value) -> Result<f64, SettingsError> {
+    value.as_f64().ok_or(SettingsError::new(ErrorKind::WrongType("number")))
+}
+
+impl FieldTrait for Color {
+    type Config = DefaultConfig<Color>;
+
+    fn default_self(config: &Self::Config) -> Self {
+        config.inner.unwrap_or_default()
+    }
+
+    fn to_field(&self, _: &Self::Config) -> FieldType {
+        FieldType::Color(self.0)
+    }
+
+    fn from_field(field: &FieldType, _: &Self::Config) -> Result<Self, SettingsError> {
+        match field {
+            FieldType::Color(rgb) => Ok(Color(*rgb)),
+            _ => Err(SettingsError::new(ErrorKind::WrongType("color"))),
+        }
+    }
+
+    /// Serialized as `[r, g, b]`, hex strings are only for edits
+    fn field_schema(_: &Self::Config) -> Value {
+        let channel = number_schema("number", 0.0, 1.0, 0.0);
+        tuple_schema(vec![channel.clone(), channel.clone(), channel])
+    }
+
+    /// Either `#rrggbb` or `[r, g, b]`
+    fn apply_path(&mut self, path: &[PathSegment], value: Value, _: &Self::Config) -> Result<(), SettingsError> {
+        expect_leaf(path)?;
+
+        let color = match value {
+            Value::String(hex) => Color::from_hex(&hex).ok_or(SettingsError::new(ErrorKind::WrongType("#rrggbb")))?,
+            Value::Array(ref rgb) if rgb.len() == 3 => {
+                let mut out = [0.0; 3];
+                for (i, channel) in rgb.iter().enumerate() {
+                    let channel = expect_number(channel).map_err(|e| e.at(PathSegment::Index(i)))?;
+                    check_range(channel, 0.0, 1.0, 0.0).map_err(|e| e.at(PathSegment::Index(i)))?;
+                    out[i] = channel as f32;
+                }
+                Color(out)
+            }
+            _ => return Err(SettingsError::new(ErrorKind::WrongType("color"))),
+        };
+
+        *self = color;
+        Ok(())
+    }
+}
+
+/// Every axis is configured like an `f32`
+#[derive(Default, Clone)]
+pub struct Vec3Config {
+    pub x: FloatFieldConfig,
+    pub y: FloatFieldConfig,
+    pub z: FloatFieldConfig,
+}
+
+impl Vec3Config {
+    fn axes(&self) -> [&FloatFieldConfig; 3] {
+        [&self.x, &self.y, &self.z]
+    }
+}
+
+const AXES: [&str; 3] = ["x", "y", "z"];
+
+/// Shown as one vec3 widget, edits can be a whole `[x, y, z]` or a single axis like `scale.x`
+impl FieldTrait for [f32; 3] {
+    type Config = Vec3Config;
+
+    fn default_self(config: &Self::Config) -> Self {
+        let [x, y, z] = config.axes();
+        [f32::default_self(x), f32::default_self(y), f32::default_self(z)]
+    }
+
+    fn to_field(&self, config: &Self::Config) -> FieldType {
+        let mut out = FieldType::Vec3 {
+            value: *self,
+            min: [0.0; 3],
+            max: [0.0; 3],
+            inc: [0.0; 3],
+        };
+
+        if let FieldType::Vec3 { min, max, inc, .. } = &mut out {
+            for (i, axis) in config.axes().iter().enumerate() {
+                min[i] = axis.min.unwrap_or(FLOAT_MIN);
+                max[i] = axis.max.unwrap_or(FLOAT_MAX);
+                inc[i] = axis.inc.unwrap_or(FLOAT_INC);
+            }
+        }
+
+        out
+    }
+
+    fn from_field(field: &FieldType, _: &Self::Config) -> Result<Self, SettingsError> {
+        match field {
+            FieldType::Vec3 { value, .. } => Ok(*value),
+            _ => Err(SettingsError::new(ErrorKind::WrongType("vec3"))),
+        }
+    }
+
+    fn field_schema(config: &Self::Config) -> Value {
+        tuple_schema(config.axes().iter().map(|axis| f32::field_schema(axis)).collect())
+    }
+
+    fn apply_path(&mut self, path: &[PathSegment], value: Value, config: &Self::Config) -> Result<(), SettingsError> {
+        let axes = config.axes();
+
+        match path.split_first() {
+            // All axes at once, either checks out or nothing changes
+            None => {
+                let values: Vec<Value> = match value {
+                    Value::Array(values) if values.len() == 3 => values,
+                    Value::Object(mut map) => AXES
+                        .iter()
+                        .zip(self.iter())
+                        .map(|(axis, current)| map.remove(*axis).unwrap_or(Value::from(*current as f64)))
+                        .collect(),
+                    _ => return Err(SettingsError::new(ErrorKind::WrongType("vec3"))),
+                };
+
+                let mut next = *self;
+                for (i, value) in values.into_iter().enumerate() {
+                    let segment = PathSegment::Field(AXES[i].to_string());
+                    next[i].apply_path(&[], value, axes[i]).map_err(|e| e.at(segment))?;
+                }
+
+                *self = next;
+                Ok(())
+            }
+            Some((segment, rest)) => {
+                let i = match segment {
+                    PathSegment::Field(axis) => AXES.iter().position(|x| x == axis),
+                    PathSegment::Index(i) if *i < 3 => Some(*i),
+                    PathSegment::Index(_) => None,
+                };
+                let i = i.ok_or(SettingsError::new(ErrorKind::UnknownField(segment.to_string())))?;
+
+                self[i]
+                    .apply_path(rest, value, axes[i])
+                    .map_err(|e| e.at(segment.clone()))
+            }
+        }
+    }
+}
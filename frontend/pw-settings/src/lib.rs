@@ -1,5 +1,14 @@
-use serde::{Serialize};
-use serde_json::Value;
+use serde::{Deserialize, Serialize};
+pub use serde_json::Value;
+
+mod path;
+pub use path::*;
+mod fields;
+pub use fields::*;
+mod schema;
+pub use schema::*;
+mod presets;
+pub use presets::*;
 
 static FLOAT_DEFAULT: f32 = 0.0;
 static FLOAT_MIN: f32 = 0.0;
@@ -26,11 +35,161 @@ pub enum FieldType {
     #[serde(rename = "settings")]
     Settings(Settings),
 
+    /// New items start as `template`
     #[serde(rename = "array")]
-    Array(Vec<FieldType>),
+    Array {
+        items: Vec<FieldType>,
+        template: Box<FieldType>,
+        min_len: usize,
+        max_len: Option<usize>,
+    },
 
     #[serde(rename = "check")]
     Bool(bool),
+
+    /// Whole numbers, shown as a spinner
+    #[serde(rename = "int")]
+    Int {
+        value: i64,
+        min: i64,
+        max: i64,
+        inc: i64,
+    },
+
+    /// Rgb, every channel between 0 and 1
+    #[serde(rename = "color")]
+    Color([f32; 3]),
+
+    /// Three sliders, every axis has its own bounds
+    #[serde(rename = "vec3")]
+    Vec3 {
+        value: [f32; 3],
+        min: [f32; 3],
+        max: [f32; 3],
+        inc: [f32; 3],
+    },
+
+    /// Text, but only one of `options`
+    #[serde(rename = "dropdown")]
+    Dropdown {
+        value: String,
+        options: Vec<String>,
+    },
+
+    /// Shown, but edits are refused
+    #[serde(rename = "readonly")]
+    ReadOnly(Box<FieldType>),
+
+    /// One of `options`, only the fields of the selected option are shown
+    #[serde(rename = "select")]
+    Select {
+        value: String,
+        options: Vec<SelectOption>,
+    },
+}
+
+impl FieldType {
+    /// The field itself, without a `ReadOnly` around it
+    pub fn inner(&self) -> &FieldType {
+        match self {
+            FieldType::ReadOnly(inner) => inner.inner(),
+            field => field,
+        }
+    }
+
+    /// Add to the state of the nested field `id`, for conditions that its parent evaluates.
+    /// Returns `false` when there is no such field.
+    pub fn add_nested_state(&mut self, id: &str, state: FieldState) -> bool {
+        match self {
+            FieldType::ReadOnly(inner) => inner.add_nested_state(id, state),
+            FieldType::Settings(settings) => match settings.fields.iter_mut().find(|f| f.id == id) {
+                Some(field) => {
+                    field.state.hidden |= state.hidden;
+                    field.state.disabled |= state.disabled;
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn collect_states(&self, out: &mut Vec<FieldState>) {
+        match self.inner() {
+            FieldType::Settings(settings) => settings.collect_states(out),
+            FieldType::Array { items, .. } => items.iter().for_each(|x| x.collect_states(out)),
+            FieldType::Select { options, .. } => options.iter().for_each(|x| x.settings.collect_states(out)),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct SelectOption {
+    id: String,
+    name: String,
+    settings: Settings,
+}
+
+impl SelectOption {
+    pub fn new<S1: Into<String>, S2: Into<String>>(id: S1, name: S2, settings: Settings) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            settings,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+}
+
+/// Hints for the panel, none of them change what a field accepts
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct FieldInfo {
+    /// Tooltip, from `help = "..."` or the doc comment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub help: Option<String>,
+    /// Like `degrees` or `units/s`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    /// Fields with the same group are shown together in a collapsible section
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Lower comes first, fields without an order stay where they are declared
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<i32>,
+}
+
+fn is_false(x: &bool) -> bool {
+    !*x
+}
+
+/// From `visible_if` and `enabled_if`, evaluated when the settings are made.
+/// Edits to hidden or disabled fields are refused.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct FieldState {
+    #[serde(skip_serializing_if = "is_false")]
+    pub hidden: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub disabled: bool,
+}
+
+/// Where an edit comes from. Edits from the panel can't touch hidden, disabled or read only fields,
+/// values that were read out before, like presets and undo steps, are put back regardless.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Edit,
+    Restore,
 }
 
 #[derive(Serialize, Debug)]
@@ -38,10 +197,38 @@ pub struct Field {
     id: String,
     name: String,
 
+    #[serde(flatten)]
+    info: FieldInfo,
+
+    #[serde(flatten)]
+    state: FieldState,
+
     #[serde(flatten)]
     field_type: FieldType,
 }
 
+impl Field {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn info(&self) -> &FieldInfo {
+        &self.info
+    }
+
+    pub fn state(&self) -> FieldState {
+        self.state
+    }
+
+    pub fn field_type(&self) -> &FieldType {
+        &self.field_type
+    }
+}
+
 #[derive(Serialize, Debug)]
 pub struct Settings {
     class: String,
@@ -49,6 +236,18 @@ pub struct Settings {
 }
 
 impl Settings {
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    pub fn get(&self, id: &str) -> Option<&FieldType> {
+        self.fields.iter().find(|f| f.id == id).map(|f| &f.field_type)
+    }
+
     pub fn new<S1: Into<String>>(class: S1) -> Self {
         Settings { fields: Vec::new(), class: class.into() }
     }
@@ -58,19 +257,63 @@ impl Settings {
         id: S1,
         name: S2,
         field: FieldType,
+    ) {
+        self.add_field_with_info(id, name, field, FieldInfo::default());
+    }
+
+    pub fn add_field_with_info<S1: Into<String>, S2: Into<String>>(
+        &mut self,
+        id: S1,
+        name: S2,
+        field: FieldType,
+        info: FieldInfo,
+    ) {
+        self.add_field_with_state(id, name, field, info, FieldState::default());
+    }
+
+    pub fn add_field_with_state<S1: Into<String>, S2: Into<String>>(
+        &mut self,
+        id: S1,
+        name: S2,
+        field: FieldType,
+        info: FieldInfo,
+        state: FieldState,
     ) {
         self.fields.push(Field {
             id: id.into(),
             name: name.into(),
+            info,
+            state,
             field_type: field,
         });
     }
 
+    /// Add all fields of `other`, used for flattened fields.
+    pub fn extend(&mut self, other: Settings) {
+        self.fields.extend(other.fields);
+    }
+
+    /// States of all fields, nested ones too. When these change the panel has to be rebuilt.
+    pub fn states(&self) -> Vec<FieldState> {
+        let mut out = Vec::new();
+        self.collect_states(&mut out);
+        out
+    }
+
+    fn collect_states(&self, out: &mut Vec<FieldState>) {
+        for field in &self.fields {
+            out.push(field.state);
+            field.field_type.collect_states(out);
+        }
+    }
+
     pub fn add_data<S1: Into<String>, V: Serialize>(&mut self, id: S1, value: V) {
         let id = id.into();
         self.fields.push(Field {
             id: id.clone(),
             name: id,
+            info: FieldInfo::default(),
+            state: FieldState::default(),
             field_type: FieldType::Data(
                 serde_json::to_value(value).expect("Expected serializable json"),
             ),
@@ -116,6 +359,45 @@ pub trait SettingsTrait: Sized {
     fn new_settings() -> Settings {
         Self::default_settings(None).to_settings(None)
     }
+
+    /// Build `Self` back from a `Settings` description, missing fields get their default.
+    fn from_settings<T: Into<Option<Self::Config>>>(settings: &Settings, config: T) -> Result<Self, SettingsError> {
+        Self::from_settings_with(settings, &config.into().unwrap_or_default())
+    }
+    fn from_settings_with(settings: &Settings, config: &Self::Config) -> Result<Self, SettingsError>;
+
+    /// Change a single field, `path` looks like `planets[2].location.scale.x`.
+    /// The value is checked against the config of that field.
+    fn apply_field<T: Into<Option<Self::Config>>>(&mut self, path: &str, value: Value, config: T) -> Result<(), SettingsError> {
+        let path = parse_path(path)?;
+        self.apply_field_with(&path, value, &config.into().unwrap_or_default())
+    }
+    fn apply_field_with(&mut self, path: &[PathSegment], value: Value, config: &Self::Config) -> Result<(), SettingsError> {
+        self.apply_field_as(path, value, config, Access::Edit)
+    }
+    fn apply_field_as(&mut self, path: &[PathSegment], value: Value, config: &Self::Config, access: Access) -> Result<(), SettingsError>;
+
+    /// JSON Schema of the serialized form, to check files before loading them.
+    fn json_schema<T: Into<Option<Self::Config>>>(config: T) -> Value {
+        let mut schema = Self::json_schema_with(&config.into().unwrap_or_default());
+        if let Value::Object(map) = &mut schema {
+            map.insert("$schema".to_string(), Value::from(SCHEMA_DIALECT));
+        }
+        schema
+    }
+    fn json_schema_with(config: &Self::Config) -> Value;
+
+    /// How this shows up as a field of something else, enums override this to become a `Select`.
+    fn to_field_with(&self, config: &Self::Config) -> FieldType {
+        FieldType::Settings(self.to_settings_with(config))
+    }
+
+    fn from_field_with(field: &FieldType, config: &Self::Config) -> Result<Self, SettingsError> {
+        match field {
+            FieldType::Settings(settings) => Self::from_settings_with(settings, config),
+            _ => Err(SettingsError::new(ErrorKind::WrongType("settings"))),
+        }
+    }
 }
 
 impl<T: SettingsTrait + Clone> FieldTrait for T {
@@ -126,7 +408,31 @@ impl<T: SettingsTrait + Clone> FieldTrait for T {
     }
 
     fn to_field(&self, config: &Self::Config) -> FieldType {
-        FieldType::Settings(self.to_settings_with(config))
+        self.to_field_with(config)
+    }
+
+    fn from_field(field: &FieldType, config: &Self::Config) -> Result<Self, SettingsError> {
+        T::from_field_with(field, config)
+    }
+
+    fn field_schema(config: &Self::Config) -> Value {
+        T::json_schema_with(config)
+    }
+
+    fn apply_path(&mut self, path: &[PathSegment], value: Value, config: &Self::Config) -> Result<(), SettingsError> {
+        self.apply_path_as(path, value, config, Access::Edit)
+    }
+
+    fn apply_path_as(&mut self, path: &[PathSegment], value: Value, config: &Self::Config, access: Access) -> Result<(), SettingsError> {
+        if path.is_empty() {
+            // A whole object at once, don't leave it half applied when something fails
+            let mut next = self.clone();
+            next.apply_field_as(path, value, config, access)?;
+            *self = next;
+            Ok(())
+        } else {
+            self.apply_field_as(path, value, config, access)
+        }
     }
 }
 
@@ -136,6 +442,17 @@ pub trait FieldTrait: Sized {
     fn default_self(config: &Self::Config) -> Self;
     fn to_field(&self, config: &Self::Config) -> FieldType;
 
+    fn from_field(field: &FieldType, config: &Self::Config) -> Result<Self, SettingsError>;
+    fn apply_path(&mut self, path: &[PathSegment], value: Value, config: &Self::Config) -> Result<(), SettingsError>;
+
+    /// Only fields with fields of their own care about `access`
+    fn apply_path_as(&mut self, path: &[PathSegment], value: Value, config: &Self::Config, _: Access) -> Result<(), SettingsError> {
+        self.apply_path(path, value, config)
+    }
+
+    /// Schema of the serialized value, with the same bounds that `apply_path` checks
+    fn field_schema(config: &Self::Config) -> Value;
+
     fn d_self() -> Self {
         Self::default_self(&Self::Config::default())
     }
@@ -167,8 +484,9 @@ impl Default for FloatFieldConfig {
 impl FieldTrait for f32 {
     type Config = FloatFieldConfig;
 
+    /// Never below `min`, a new scale of 0 would be invisible
     fn default_self(settings: &Self::Config) -> Self {
-        settings.value.clone().unwrap_or(FLOAT_DEFAULT)
+        settings.value.unwrap_or(FLOAT_DEFAULT).max(settings.min.unwrap_or(FLOAT_MIN))
     }
     fn to_field(&self, config: &Self::Config) -> FieldType {
         FieldType::Slider {
@@ -178,6 +496,37 @@ impl FieldTrait for f32 {
             inc: config.inc.unwrap_or(FLOAT_INC),
         }
     }
+
+    fn from_field(field: &FieldType, _: &Self::Config) -> Result<Self, SettingsError> {
+        match field {
+            FieldType::Slider { value, .. } => Ok(*value),
+            _ => Err(SettingsError::new(ErrorKind::WrongType("slider"))),
+        }
+    }
+
+    fn field_schema(config: &Self::Config) -> Value {
+        number_schema(
+            "number",
+            config.min.unwrap_or(FLOAT_MIN),
+            config.max.unwrap_or(FLOAT_MAX),
+            config.inc.unwrap_or(FLOAT_INC),
+        )
+    }
+
+    fn apply_path(&mut self, path: &[PathSegment], value: Value, config: &Self::Config) -> Result<(), SettingsError> {
+        expect_leaf(path)?;
+        let value = value.as_f64().ok_or(SettingsError::new(ErrorKind::WrongType("number")))?;
+
+        check_range(
+            value,
+            config.min.unwrap_or(FLOAT_MIN),
+            config.max.unwrap_or(FLOAT_MAX),
+            config.inc.unwrap_or(FLOAT_INC),
+        )?;
+
+        *self = value as f32;
+        Ok(())
+    }
 }
 
 #[derive(Default, Clone)]
@@ -194,36 +543,234 @@ impl FieldTrait for bool {
     fn to_field(&self, _: &DefaultConfig<Self>) -> FieldType {
         FieldType::Bool(*self)
     }
+
+    fn from_field(field: &FieldType, _: &Self::Config) -> Result<Self, SettingsError> {
+        match field {
+            FieldType::Bool(value) => Ok(*value),
+            _ => Err(SettingsError::new(ErrorKind::WrongType("check"))),
+        }
+    }
+
+    fn field_schema(_: &Self::Config) -> Value {
+        serde_json::json!({ "type": "boolean" })
+    }
+
+    fn apply_path(&mut self, path: &[PathSegment], value: Value, _: &Self::Config) -> Result<(), SettingsError> {
+        expect_leaf(path)?;
+        *self = value.as_bool().ok_or(SettingsError::new(ErrorKind::WrongType("bool")))?;
+        Ok(())
+    }
+}
+
+/// Text fields with `options` become a dropdown
+#[derive(Default, Clone)]
+pub struct TextConfig {
+    pub value: Option<&'static str>,
+    pub options: Option<&'static [&'static str]>,
 }
 
 impl FieldTrait for String {
-    type Config = DefaultConfig<String>;
+    type Config = TextConfig;
 
-    fn default_self(settings: &DefaultConfig<Self>) -> Self {
-        settings.inner.clone().unwrap_or_default()
+    fn default_self(config: &Self::Config) -> Self {
+        config
+            .value
+            .or(config.options.and_then(|x| x.first().cloned()))
+            .unwrap_or_default()
+            .to_string()
     }
-    fn to_field(&self, _: &DefaultConfig<Self>) -> FieldType {
-        FieldType::Text(self.clone())
+    fn to_field(&self, config: &Self::Config) -> FieldType {
+        match config.options {
+            Some(options) => FieldType::Dropdown {
+                value: self.clone(),
+                options: options.iter().map(|x| x.to_string()).collect(),
+            },
+            None => FieldType::Text(self.clone()),
+        }
+    }
+
+    fn from_field(field: &FieldType, _: &Self::Config) -> Result<Self, SettingsError> {
+        match field {
+            FieldType::Text(value) => Ok(value.clone()),
+            FieldType::Dropdown { value, .. } => Ok(value.clone()),
+            _ => Err(SettingsError::new(ErrorKind::WrongType("text"))),
+        }
+    }
+
+    fn field_schema(config: &Self::Config) -> Value {
+        match config.options {
+            Some(options) => serde_json::json!({ "type": "string", "enum": options }),
+            None => serde_json::json!({ "type": "string" }),
+        }
+    }
+
+    fn apply_path(&mut self, path: &[PathSegment], value: Value, config: &Self::Config) -> Result<(), SettingsError> {
+        expect_leaf(path)?;
+        let value = match value {
+            Value::String(value) => value,
+            _ => return Err(SettingsError::new(ErrorKind::WrongType("string"))),
+        };
+
+        if let Some(options) = config.options {
+            if !options.contains(&value.as_str()) {
+                return Err(SettingsError::new(ErrorKind::UnknownOption(value)));
+            }
+        }
+
+        *self = value;
+        Ok(())
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct VecConfig<C> {
+    pub element: C,
+    pub min_len: Option<usize>,
+    pub max_len: Option<usize>,
+}
+
+impl<C> VecConfig<C> {
+    fn check_len(&self, len: usize) -> Result<(), SettingsError> {
+        let min = self.min_len.unwrap_or(0);
+        if len < min || self.max_len.map(|max| len > max).unwrap_or(false) {
+            return Err(SettingsError::new(ErrorKind::LengthOutOfRange { len, min, max: self.max_len }));
+        }
+        Ok(())
     }
 }
 
+/// Structural edits on an array, sent as the value for the path of the array itself,
+/// like `{"insert": {"index": 2}}`, `{"remove": {"index": 0}}` or `{"move": {"from": 3, "to": 0}}`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum ArrayOp {
+    /// `value` is applied on top of the template
+    Insert { index: usize, value: Option<Value> },
+    Remove { index: usize },
+    Move { from: usize, to: usize },
+}
+
 impl<T: FieldTrait + Clone> FieldTrait for Vec<T> {
-    type Config = <T as FieldTrait>::Config;
-
-    // Maybe instantiate some entries?
-    fn default_self(_config: &Self::Config) -> Self {
-        Vec::new()
-    }
-
-    fn to_field(
-        &self,
-        config: &Self::Config,
-    ) -> FieldType {
-        FieldType::Array(
-            self.iter()
-                .map(|x| x.to_field(
-                &config)
-            ).collect(),
-        )
+    type Config = VecConfig<<T as FieldTrait>::Config>;
+
+    fn default_self(config: &Self::Config) -> Self {
+        vec![T::default_self(&config.element); config.min_len.unwrap_or(0)]
     }
+
+    fn to_field(&self, config: &Self::Config) -> FieldType {
+        FieldType::Array {
+            items: self.iter().map(|x| x.to_field(&config.element)).collect(),
+            template: Box::new(T::default_self(&config.element).to_field(&config.element)),
+            min_len: config.min_len.unwrap_or(0),
+            max_len: config.max_len,
+        }
+    }
+
+    fn from_field(field: &FieldType, config: &Self::Config) -> Result<Self, SettingsError> {
+        match field {
+            FieldType::Array { items, .. } => items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| T::from_field(item, &config.element).map_err(|e| e.at(PathSegment::Index(i))))
+                .collect(),
+            _ => Err(SettingsError::new(ErrorKind::WrongType("array"))),
+        }
+    }
+
+    fn field_schema(config: &Self::Config) -> Value {
+        let mut schema = serde_json::json!({
+            "type": "array",
+            "items": T::field_schema(&config.element),
+            "minItems": config.min_len.unwrap_or(0),
+        });
+        if let Some(max) = config.max_len {
+            schema["maxItems"] = Value::from(max);
+        }
+        schema
+    }
+
+    fn apply_path(&mut self, path: &[PathSegment], value: Value, config: &Self::Config) -> Result<(), SettingsError> {
+        self.apply_path_as(path, value, config, Access::Edit)
+    }
+
+    fn apply_path_as(&mut self, path: &[PathSegment], value: Value, config: &Self::Config, access: Access) -> Result<(), SettingsError> {
+        match path.split_first() {
+            None => match value {
+                // Replace everything, existing entries are updated so partial objects work too
+                Value::Array(items) => {
+                    config.check_len(items.len())?;
+
+                    let mut next = Vec::with_capacity(items.len());
+                    for (i, item) in items.into_iter().enumerate() {
+                        let mut element = self.get(i).cloned().unwrap_or_else(|| T::default_self(&config.element));
+                        element
+                            .apply_path_as(&[], item, &config.element, access)
+                            .map_err(|e| e.at(PathSegment::Index(i)))?;
+                        next.push(element);
+                    }
+
+                    *self = next;
+                    Ok(())
+                }
+                value => {
+                    let op: ArrayOp = serde_json::from_value(value)
+                        .map_err(|_| SettingsError::new(ErrorKind::WrongType("array or array operation")))?;
+                    apply_op(self, op, config, access)
+                }
+            },
+            Some((PathSegment::Index(i), rest)) => match self.get_mut(*i) {
+                Some(element) => element.apply_path_as(rest, value, &config.element, access),
+                None => Err(SettingsError::new(ErrorKind::IndexOutOfBounds(*i))),
+            }
+            .map_err(|e| e.at(PathSegment::Index(*i))),
+            Some((segment, _)) => Err(SettingsError::new(ErrorKind::UnknownField(segment.to_string()))),
+        }
+    }
+}
+
+fn apply_op<T: FieldTrait + Clone>(
+    items: &mut Vec<T>,
+    op: ArrayOp,
+    config: &VecConfig<T::Config>,
+    access: Access,
+) -> Result<(), SettingsError> {
+    let out_of_bounds = |i: usize| SettingsError::new(ErrorKind::IndexOutOfBounds(i)).at(PathSegment::Index(i));
+
+    match op {
+        ArrayOp::Insert { index, value } => {
+            if index > items.len() {
+                return Err(out_of_bounds(index));
+            }
+            config.check_len(items.len() + 1)?;
+
+            let mut element = T::default_self(&config.element);
+            if let Some(value) = value {
+                element
+                    .apply_path_as(&[], value, &config.element, access)
+                    .map_err(|e| e.at(PathSegment::Index(index)))?;
+            }
+            items.insert(index, element);
+        }
+        ArrayOp::Remove { index } => {
+            if index >= items.len() {
+                return Err(out_of_bounds(index));
+            }
+            config.check_len(items.len() - 1)?;
+
+            items.remove(index);
+        }
+        ArrayOp::Move { from, to } => {
+            if from >= items.len() {
+                return Err(out_of_bounds(from));
+            }
+            if to >= items.len() {
+                return Err(out_of_bounds(to));
+            }
+
+            let element = items.remove(from);
+            items.insert(to, element);
+        }
+    }
+
+    Ok(())
 }
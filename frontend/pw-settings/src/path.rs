@@ -0,0 +1,202 @@
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// One step in a field path like `planets[2].location.scale.x`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Field(String),
+    Index(usize),
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Field(name) => write!(f, "{}", name),
+            PathSegment::Index(index) => write!(f, "[{}]", index),
+        }
+    }
+}
+
+pub fn parse_path(path: &str) -> Result<Vec<PathSegment>, SettingsError> {
+    let invalid = || SettingsError::new(ErrorKind::InvalidPath(path.to_string()));
+    let mut out = Vec::new();
+
+    if path.is_empty() {
+        return Ok(out);
+    }
+
+    for part in path.split('.') {
+        let mut pieces = part.split('[');
+
+        match pieces.next() {
+            Some("") if out.is_empty() && part.starts_with('[') => {}
+            Some("") | None => return Err(invalid()),
+            Some(name) => out.push(PathSegment::Field(name.to_string())),
+        }
+
+        for piece in pieces {
+            let index = piece
+                .strip_suffix(']')
+                .and_then(|x| x.parse().ok())
+                .ok_or_else(invalid)?;
+            out.push(PathSegment::Index(index));
+        }
+    }
+
+    Ok(out)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    InvalidPath(String),
+    UnknownField(String),
+    MissingField(String),
+    UnknownVariant(String),
+    UnknownOption(String),
+    ReadOnly,
+    Disabled,
+    IndexOutOfBounds(usize),
+    LengthOutOfRange { len: usize, min: usize, max: Option<usize> },
+    WrongType(&'static str),
+    OutOfRange { value: f32, min: f32, max: f32 },
+    NotAnIncrement { value: f32, inc: f32 },
+    NoMatch,
+}
+
+/// Something went wrong at `path` while applying or reading settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingsError {
+    pub path: Vec<PathSegment>,
+    pub kind: ErrorKind,
+}
+
+impl SettingsError {
+    pub fn new(kind: ErrorKind) -> Self {
+        Self {
+            path: Vec::new(),
+            kind,
+        }
+    }
+
+    /// Errors bubble up, every level prepends its own segment.
+    pub fn at(mut self, segment: PathSegment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+
+    pub fn path_string(&self) -> String {
+        let mut out = String::new();
+        for segment in &self.path {
+            if let PathSegment::Field(_) = segment {
+                if !out.is_empty() {
+                    out.push('.');
+                }
+            }
+            out.push_str(&segment.to_string());
+        }
+        out
+    }
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.path.is_empty() {
+            write!(f, "'{}': ", self.path_string())?;
+        }
+
+        match &self.kind {
+            ErrorKind::InvalidPath(path) => write!(f, "invalid path '{}'", path),
+            ErrorKind::UnknownField(name) => write!(f, "unknown field '{}'", name),
+            ErrorKind::MissingField(name) => write!(f, "missing field '{}'", name),
+            ErrorKind::UnknownVariant(name) => write!(f, "unknown variant '{}'", name),
+            ErrorKind::UnknownOption(name) => write!(f, "'{}' is not one of the options", name),
+            ErrorKind::ReadOnly => write!(f, "field is read only"),
+            ErrorKind::Disabled => write!(f, "field is disabled"),
+            ErrorKind::IndexOutOfBounds(index) => write!(f, "index {} is out of bounds", index),
+            ErrorKind::LengthOutOfRange { len, min, max: Some(max) } => {
+                write!(f, "length {} is not between {} and {}", len, min, max)
+            }
+            ErrorKind::LengthOutOfRange { len, min, max: None } => {
+                write!(f, "length {} is less than {}", len, min)
+            }
+            ErrorKind::WrongType(expected) => write!(f, "expected {}", expected),
+            ErrorKind::OutOfRange { value, min, max } => {
+                write!(f, "{} is not between {} and {}", value, min, max)
+            }
+            ErrorKind::NotAnIncrement { value, inc } => {
+                write!(f, "{} is not a multiple of {}", value, inc)
+            }
+            ErrorKind::NoMatch => write!(f, "doesn't match exactly one of the allowed shapes"),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+/// Leaf fields have no children, so the path has to end here.
+pub fn expect_leaf(path: &[PathSegment]) -> Result<(), SettingsError> {
+    match path.first() {
+        None => Ok(()),
+        Some(segment) => Err(SettingsError::new(ErrorKind::UnknownField(segment.to_string()))),
+    }
+}
+
+/// Whether setting `value` at `path` changes the field `id`, on its own or as a key of a whole object.
+pub fn edits_field(path: &[PathSegment], value: &Value, id: &str) -> bool {
+    match path.first() {
+        Some(PathSegment::Field(name)) => name == id,
+        Some(PathSegment::Index(_)) => false,
+        None => value.get(id).is_some(),
+    }
+}
+
+/// Apply every key of an object as a field. Keys that are disabled by others in the same object
+/// are tried again once those are in, so the order of the keys doesn't matter.
+pub fn apply_fields<F>(map: Map<String, Value>, mut apply: F) -> Result<(), SettingsError>
+where
+    F: FnMut(&PathSegment, Value) -> Result<(), SettingsError>,
+{
+    let mut pending: Vec<(PathSegment, Value)> = map.into_iter().map(|(k, v)| (PathSegment::Field(k), v)).collect();
+
+    loop {
+        let count = pending.len();
+        let mut disabled = None;
+        let mut next = Vec::new();
+
+        for (segment, value) in pending {
+            match apply(&segment, value.clone()) {
+                Ok(()) => {}
+                Err(e) if e.kind == ErrorKind::Disabled => {
+                    disabled = Some(e);
+                    next.push((segment, value));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        match disabled {
+            None => return Ok(()),
+            Some(e) if next.len() == count => return Err(e),
+            Some(_) => pending = next,
+        }
+    }
+}
+
+/// Check `value` against the bounds a slider would have.
+pub fn check_range(value: f64, min: f32, max: f32, inc: f32) -> Result<(), SettingsError> {
+    if value < min as f64 || value > max as f64 {
+        let value = value as f32;
+        return Err(SettingsError::new(ErrorKind::OutOfRange { value, min, max }));
+    }
+
+    if inc > 0.0 {
+        // inc is an f32, so allow for its rounding error on every step
+        let steps = (value - min as f64) / inc as f64;
+        if (steps - steps.round()).abs() > 1e-2 + steps.abs() * 1e-6 {
+            let value = value as f32;
+            return Err(SettingsError::new(ErrorKind::NotAnIncrement { value, inc }));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,220 @@
+//! Named snapshots of settings, only the fields that differ from the defaults are stored.
+//! Loading skips what doesn't fit anymore, so presets survive fields being added or removed.
+
+use crate::*;
+use serde::Deserialize;
+use serde_json::{json, Map};
+
+impl FieldType {
+    /// The value in the shape `apply_field` takes. Read only fields only take it back with `Access::Restore`.
+    pub fn to_value(&self) -> Value {
+        match self {
+            FieldType::Text(value) => Value::from(value.clone()),
+            FieldType::Slider { value, .. } => number(*value),
+            FieldType::Int { value, .. } => Value::from(*value),
+            FieldType::Bool(value) => Value::from(*value),
+            FieldType::Color(rgb) => Value::Array(rgb.iter().map(|x| number(*x)).collect()),
+            FieldType::Vec3 { value, .. } => Value::Array(value.iter().map(|x| number(*x)).collect()),
+            FieldType::Dropdown { value, .. } => Value::from(value.clone()),
+            FieldType::Data(value) => value.clone(),
+            FieldType::Settings(settings) => settings.to_value(),
+            FieldType::Array { items, .. } => Value::Array(items.iter().map(|x| x.to_value()).collect()),
+            FieldType::Select { value, options } => {
+                let mut out = options
+                    .iter()
+                    .find(|o| o.id() == value)
+                    .map(|o| o.settings().to_value())
+                    .unwrap_or_else(|| json!({}));
+                out["variant"] = Value::from(value.clone());
+                out
+            }
+            FieldType::ReadOnly(inner) => inner.to_value(),
+        }
+    }
+}
+
+impl Settings {
+    /// All fields as one object, like a settings edit with an empty path
+    pub fn to_value(&self) -> Value {
+        let fields: Map<String, Value> = self
+            .fields()
+            .iter()
+            .map(|f| (f.id().to_string(), f.field_type().to_value()))
+            .collect();
+        Value::Object(fields)
+    }
+
+    /// Value of the field at `path`, like `to_value` of that field
+    pub fn value_at(&self, path: &[PathSegment]) -> Option<Value> {
+        lookup(&self.to_value(), path).cloned()
+    }
+}
+
+/// What changed from `default` to `current`. Arrays and switched variants are kept whole,
+/// parts of them mean nothing on their own.
+pub fn diff(default: &Value, current: &Value) -> Option<Value> {
+    match (default, current) {
+        (Value::Object(default), Value::Object(current)) if default.get("variant") == current.get("variant") => {
+            let changed: Map<String, Value> = current
+                .iter()
+                .filter_map(|(key, value)| match default.get(key) {
+                    Some(default) => Some((key.clone(), diff(default, value)?)),
+                    None => Some((key.clone(), value.clone())),
+                })
+                .collect();
+
+            if changed.is_empty() {
+                None
+            } else {
+                Some(Value::Object(changed))
+            }
+        }
+        (default, current) if default == current => None,
+        (_, current) => Some(current.clone()),
+    }
+}
+
+fn lookup<'a>(value: &'a Value, path: &[PathSegment]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, segment| match segment {
+        PathSegment::Field(name) => value.get(name),
+        PathSegment::Index(i) => value.get(i),
+    })
+}
+
+/// Apply as much of `value` as still fits, one field at a time. Whatever doesn't is collected in `errors`.
+/// The values were read out of the settings, so they go back in as a restore.
+fn apply_tolerant<T: SettingsTrait>(
+    target: &mut T,
+    path: &mut Vec<PathSegment>,
+    value: Value,
+    config: &T::Config,
+    errors: &mut Vec<SettingsError>,
+) {
+    match value {
+        Value::Object(mut map) => {
+            // Fields belong to a variant, so it has to be selected first
+            if let Some(variant) = map.remove("variant") {
+                path.push(PathSegment::Field("variant".to_string()));
+                let result = target.apply_field_as(path, variant, config, Access::Restore);
+                path.pop();
+
+                if let Err(e) = result {
+                    errors.push(e);
+                    return;
+                }
+            }
+
+            for (key, value) in map {
+                path.push(PathSegment::Field(key));
+                apply_tolerant(target, path, value, config, errors);
+                path.pop();
+            }
+        }
+        Value::Array(items) => {
+            if target.apply_field_as(path, Value::Array(items.clone()), config, Access::Restore).is_ok() {
+                return;
+            }
+
+            // Get the length right, then go over the items like over fields
+            let current = target.to_settings_with(config).to_value();
+            let len = match lookup(&current, path).and_then(|x| x.as_array()) {
+                Some(current) => current.len(),
+                None => {
+                    errors.push(SettingsError { path: path.clone(), kind: ErrorKind::WrongType("array") });
+                    return;
+                }
+            };
+
+            for index in (items.len()..len).rev() {
+                if let Err(e) = target.apply_field_as(path, json!({ "remove": { "index": index } }), config, Access::Restore) {
+                    errors.push(e);
+                }
+            }
+            for index in len..items.len() {
+                if let Err(e) = target.apply_field_as(path, json!({ "insert": { "index": index } }), config, Access::Restore) {
+                    errors.push(e);
+                }
+            }
+
+            for (index, item) in items.into_iter().enumerate() {
+                path.push(PathSegment::Index(index));
+                apply_tolerant(target, path, item, config, errors);
+                path.pop();
+            }
+        }
+        value => {
+            if let Err(e) = target.apply_field_as(path, value, config, Access::Restore) {
+                errors.push(e);
+            }
+        }
+    }
+}
+
+/// Changed values of one settings struct, `class` tells what they belong to.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Preset {
+    pub name: String,
+    pub class: String,
+    pub values: Value,
+}
+
+impl Preset {
+    pub fn capture<T: SettingsTrait, C: Into<Option<T::Config>>>(name: &str, value: &T, config: C) -> Self {
+        let config = config.into().unwrap_or_default();
+        let current = value.to_settings_with(&config);
+        let default = T::default_settings_with(&config).to_settings_with(&config).to_value();
+
+        Preset {
+            name: name.to_string(),
+            class: current.class().to_string(),
+            values: diff(&default, &current.to_value()).unwrap_or_else(|| json!({})),
+        }
+    }
+
+    /// Defaults with the preset on top. Stored fields that were removed or don't fit anymore are skipped,
+    /// they are returned so they can be reported.
+    pub fn load<T: SettingsTrait, C: Into<Option<T::Config>>>(&self, config: C) -> (T, Vec<SettingsError>) {
+        let config = config.into().unwrap_or_default();
+        let mut out = T::default_settings_with(&config);
+        let errors = self.apply_to(&mut out, &config);
+        (out, errors)
+    }
+
+    /// Like `load`, but on top of `target` instead of the defaults
+    pub fn apply_to<T: SettingsTrait>(&self, target: &mut T, config: &T::Config) -> Vec<SettingsError> {
+        let mut errors = Vec::new();
+        apply_tolerant(target, &mut Vec::new(), self.values.clone(), config, &mut errors);
+        errors
+    }
+}
+
+/// A set of presets, stored as one document
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Presets {
+    presets: Vec<Preset>,
+}
+
+impl Presets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces a preset with the same name and class
+    pub fn insert(&mut self, preset: Preset) {
+        self.remove(&preset.class, &preset.name);
+        self.presets.push(preset);
+    }
+
+    pub fn get(&self, class: &str, name: &str) -> Option<&Preset> {
+        self.presets.iter().find(|p| p.class == class && p.name == name)
+    }
+
+    pub fn remove(&mut self, class: &str, name: &str) -> Option<Preset> {
+        let index = self.presets.iter().position(|p| p.class == class && p.name == name)?;
+        Some(self.presets.remove(index))
+    }
+
+    pub fn names<'a>(&'a self, class: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.presets.iter().filter(move |p| p.class == class).map(|p| p.name.as_str())
+    }
+}
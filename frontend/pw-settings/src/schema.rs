@@ -0,0 +1,288 @@
+//! JSON Schema (draft 2020-12) for the serialized form of settings types,
+//! and a small validator for the part of the spec that is generated here.
+
+use crate::*;
+use serde_json::{json, Map};
+
+pub const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// `f32` bounds as they are written, `0.1` instead of `0.10000000149011612`
+pub(crate) fn number(x: f32) -> Value {
+    if x.fract() == 0.0 && x.abs() < 1e15 {
+        return Value::from(x as i64);
+    }
+    x.to_string().parse::<f64>().map(Value::from).unwrap_or(Value::Null)
+}
+
+/// Bounds of a slider. Sliders count from `min`, so `multipleOf` is only there when `min` is a step too.
+pub fn number_schema(ty: &str, min: f32, max: f32, inc: f32) -> Value {
+    let mut schema = json!({
+        "type": ty,
+        "minimum": number(min),
+        "maximum": number(max),
+    });
+
+    let steps = min as f64 / inc as f64;
+    if inc > 0.0 && (steps - steps.round()).abs() < 1e-6 {
+        schema["multipleOf"] = number(inc);
+    }
+
+    schema
+}
+
+/// Read only fields are still in the file, tools just shouldn't change them
+pub fn read_only_schema(mut schema: Value) -> Value {
+    if let Value::Object(map) = &mut schema {
+        map.insert("readOnly".to_string(), Value::Bool(true));
+    }
+    schema
+}
+
+/// Help text of a field, shown by editors that know about schemas
+pub fn describe_schema(mut schema: Value, description: &str) -> Value {
+    if let Value::Object(map) = &mut schema {
+        map.insert("description".to_string(), Value::from(description));
+    }
+    schema
+}
+
+/// Tuples are fixed length arrays, like serde writes them
+pub fn tuple_schema(items: Vec<Value>) -> Value {
+    let len = items.len();
+    json!({
+        "type": "array",
+        "prefixItems": items,
+        "items": false,
+        "minItems": len,
+        "maxItems": len,
+    })
+}
+
+/// Schema of a struct, built a field at a time like `Settings`
+pub struct ObjectSchema {
+    title: String,
+    properties: Map<String, Value>,
+    required: Vec<String>,
+    flattened: Vec<Value>,
+}
+
+impl ObjectSchema {
+    pub fn new<S: Into<String>>(title: S) -> Self {
+        Self {
+            title: title.into(),
+            properties: Map::new(),
+            required: Vec::new(),
+            flattened: Vec::new(),
+        }
+    }
+
+    pub fn add_property<S: Into<String>>(&mut self, name: S, schema: Value, required: bool) {
+        let name = name.into();
+        if required {
+            self.required.push(name.clone());
+        }
+        self.properties.insert(name, schema);
+    }
+
+    /// Fields that serde flattens, their properties end up next to ours
+    pub fn add_flattened(&mut self, schema: Value) {
+        self.flattened.push(schema);
+    }
+
+    pub fn into_value(self) -> Value {
+        let mut schema = json!({
+            "title": self.title,
+            "type": "object",
+            "properties": self.properties,
+            "required": self.required,
+        });
+
+        if !self.flattened.is_empty() {
+            schema["allOf"] = Value::Array(self.flattened);
+        }
+
+        schema
+    }
+}
+
+/// Unit structs are written as `null`
+pub fn unit_schema() -> Value {
+    json!({ "type": "null" })
+}
+
+/// Exactly one of the variants
+pub fn enum_schema<S: Into<String>>(title: S, variants: Vec<Value>) -> Value {
+    json!({
+        "title": title.into(),
+        "oneOf": variants,
+    })
+}
+
+/// Variants as serde writes them by default: unit variants are their name,
+/// others are an object with the name as only key.
+pub fn variant_schema(name: &str, content: Option<Value>) -> Value {
+    match content {
+        None => json!({ "const": name }),
+        Some(content) => json!({
+            "type": "object",
+            "properties": { name: content },
+            "required": [name],
+            "additionalProperties": false,
+        }),
+    }
+}
+
+fn type_name(ty: &str) -> &'static str {
+    match ty {
+        "object" => "object",
+        "array" => "array",
+        "string" => "string",
+        "number" => "number",
+        "integer" => "integer",
+        "boolean" => "boolean",
+        "null" => "null",
+        _ => "a known type",
+    }
+}
+
+fn has_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.as_f64().map(|x| x.fract() == 0.0).unwrap_or(false),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => false,
+    }
+}
+
+fn error(kind: ErrorKind) -> Result<(), SettingsError> {
+    Err(SettingsError::new(kind))
+}
+
+fn bound(schema: &Map<String, Value>, key: &str) -> Option<f64> {
+    schema.get(key).and_then(|x| x.as_f64())
+}
+
+/// Check `value` against `schema`, only the keywords that the generated schemas use are supported.
+/// The error points at the first thing that is wrong.
+pub fn validate(schema: &Value, value: &Value) -> Result<(), SettingsError> {
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return error(ErrorKind::WrongType("nothing")),
+        Value::Object(schema) => schema,
+        _ => return error(ErrorKind::WrongType("schema")),
+    };
+
+    match schema.get("type") {
+        Some(Value::String(ty)) if !has_type(value, ty) => return error(ErrorKind::WrongType(type_name(ty))),
+        _ => {}
+    }
+
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            return error(ErrorKind::UnknownOption(value.to_string()));
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            return error(ErrorKind::UnknownOption(value.to_string()));
+        }
+    }
+
+    if let Some(x) = value.as_f64() {
+        let min = bound(schema, "minimum");
+        let max = bound(schema, "maximum");
+        if min.map(|min| x < min).unwrap_or(false) || max.map(|max| x > max).unwrap_or(false) {
+            return error(ErrorKind::OutOfRange {
+                value: x as f32,
+                min: min.unwrap_or(f64::NEG_INFINITY) as f32,
+                max: max.unwrap_or(f64::INFINITY) as f32,
+            });
+        }
+
+        if let Some(inc) = bound(schema, "multipleOf") {
+            let steps = x / inc;
+            if (steps - steps.round()).abs() > 1e-2 + steps.abs() * 1e-6 {
+                return error(ErrorKind::NotAnIncrement { value: x as f32, inc: inc as f32 });
+            }
+        }
+    }
+
+    if let Value::Array(items) = value {
+        let min = schema.get("minItems").and_then(|x| x.as_u64()).unwrap_or(0) as usize;
+        let max = schema.get("maxItems").and_then(|x| x.as_u64()).map(|x| x as usize);
+        if items.len() < min || max.map(|max| items.len() > max).unwrap_or(false) {
+            return error(ErrorKind::LengthOutOfRange { len: items.len(), min, max });
+        }
+
+        let prefix = match schema.get("prefixItems") {
+            Some(Value::Array(prefix)) => prefix.as_slice(),
+            _ => &[],
+        };
+
+        for (i, item) in items.iter().enumerate() {
+            let item_schema = match prefix.get(i) {
+                Some(item_schema) => item_schema,
+                None => match schema.get("items") {
+                    Some(Value::Bool(false)) => return error(ErrorKind::IndexOutOfBounds(i)).map_err(|e| e.at(PathSegment::Index(i))),
+                    Some(item_schema) => item_schema,
+                    None => continue,
+                },
+            };
+            validate(item_schema, item).map_err(|e| e.at(PathSegment::Index(i)))?;
+        }
+    }
+
+    if let Value::Object(map) = value {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(|x| x.as_str()) {
+                if !map.contains_key(name) {
+                    return error(ErrorKind::MissingField(name.to_string()));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(|x| x.as_object());
+        for (key, item) in map {
+            let segment = || PathSegment::Field(key.clone());
+            match properties.and_then(|p| p.get(key)) {
+                Some(item_schema) => validate(item_schema, item).map_err(|e| e.at(segment()))?,
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => return error(ErrorKind::UnknownField(key.clone())),
+                    Some(item_schema) => validate(item_schema, item).map_err(|e| e.at(segment()))?,
+                    None => {}
+                },
+            }
+        }
+    }
+
+    if let Some(Value::Array(all)) = schema.get("allOf") {
+        for sub in all {
+            validate(sub, value)?;
+        }
+    }
+
+    if let Some(Value::Array(any)) = schema.get("anyOf") {
+        if !any.iter().any(|sub| validate(sub, value).is_ok()) {
+            return error(ErrorKind::NoMatch);
+        }
+    }
+
+    if let Some(Value::Array(one)) = schema.get("oneOf") {
+        let errors: Vec<SettingsError> = one.iter().filter_map(|sub| validate(sub, value).err()).collect();
+        let matched = one.len() - errors.len();
+        if matched != 1 {
+            // The alternative that got furthest is most likely the one that was meant
+            return match errors.into_iter().max_by_key(|e| e.path.len()) {
+                Some(e) if matched == 0 && !e.path.is_empty() => Err(e),
+                _ => error(ErrorKind::NoMatch),
+            };
+        }
+    }
+
+    Ok(())
+}
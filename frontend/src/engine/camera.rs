@@ -123,6 +123,10 @@ impl Camera {
         Matrix4::from_translation(self.entity.position().into()) * self.entity.mat_rotation()
     }
 
+    pub fn position(&self) -> Vector3<f32> {
+        self.entity.position().into()
+    }
+
     pub fn handle(&self) -> CameraHandle {
         CameraHandle {
             tx: self.tx.clone(),
@@ -198,4 +202,13 @@ impl Camera {
     pub fn world_view_projection_matrix(&self) -> Matrix4<f32> {
         self.world_view_projection_matrix
     }
+
+    /// Without the projection, world to camera space
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        self.world_matrix().invert().unwrap()
+    }
+
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        self.projection_matrix
+    }
 }
@@ -4,22 +4,29 @@ use serde::{Deserialize, Serialize};
 
 // was settings
 #[derive(Debug, Clone, Serialize, Deserialize, AddGetterVal, AddSetter, Settings)]
+#[settings(default)]
 pub struct Entity {
     #[get_val]
     #[set]
+    #[settings(unit = "units", order = 0)]
     position: Vec3,
     #[get_val]
     #[set]
+    #[settings(unit = "units/s", group = "Motion")]
     speed: Vec3,
     #[get_val]
     #[set]
+    #[settings(unit = "degrees", order = 1)]
     rotation: Vec3,
+    /// Rotation per second around every axis
     #[get_val]
     #[set]
+    #[settings(unit = "degrees/s", group = "Motion")]
     ang_speed: Vec3,
 
     #[get_val]
     #[set]
+    #[settings(order = 2)]
     scale: Vec3,
 }
 
@@ -37,19 +44,7 @@ impl Default for Entity {
 
 impl Entity {
     pub fn is_hit(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> bool {
-        let pos: Vector3<f32> = self.position.into();
-        let scale_max = self.scale.max();
-
-        let o_min_c = origin - pos;
-        let big_d = cgmath::dot(direction, o_min_c).powi(2) - (o_min_c.magnitude2() - scale_max.powi(2));
-        if big_d < 0.0 {
-            return false;
-        }
-
-        let big_d_sqrt = big_d.sqrt();
-        let distance = - cgmath::dot(direction, o_min_c) + big_d_sqrt;
-
-        distance > 0.0
+        sphere_hit(self.position.into(), self.scale.max(), origin, direction)
     }
 
     pub fn with_position(mut self, position: Vector3<f32>) -> Self {
@@ -89,9 +84,25 @@ impl Entity {
 
     pub fn update(&mut self, dt: f32) {
         self.position += self.speed * dt;
+        self.rotate(dt);
+    }
+
+    /// Only turn, for when something else moves it
+    pub fn rotate(&mut self, dt: f32) {
         self.rotation += self.ang_speed * dt;
     }
 
+    /// Somewhere between `self` and `next`, 0 is `self` and 1 is `next`
+    pub fn lerp(&self, next: &Entity, alpha: f32) -> Entity {
+        let mix = |a: Vec3, b: Vec3| a * (1.0 - alpha) + b * alpha;
+        Entity {
+            position: mix(self.position, next.position),
+            rotation: mix(self.rotation, next.rotation),
+            scale: mix(self.scale, next.scale),
+            ..next.clone()
+        }
+    }
+
     /// Matrix to transform vertices to the correct location in the world
     #[inline]
     pub fn world_matrix(&self) -> Matrix4<f32> {
@@ -101,23 +112,73 @@ impl Entity {
     }
 }
 
+/// Does the ray from `origin` along `direction` hit the sphere
+pub fn sphere_hit(center: Vector3<f32>, radius: f32, origin: Vector3<f32>, direction: Vector3<f32>) -> bool {
+    let o_min_c = origin - center;
+    let big_d = cgmath::dot(direction, o_min_c).powi(2) - (o_min_c.magnitude2() - radius.powi(2));
+    if big_d < 0.0 {
+        return false;
+    }
+
+    let big_d_sqrt = big_d.sqrt();
+    let distance = - cgmath::dot(direction, o_min_c) + big_d_sqrt;
+
+    distance > 0.0
+}
+
 pub use vec3::Vec3;
 mod vec3 {
     use cgmath::Vector3;
 
     use std::ops::{Add, AddAssign, Mul, MulAssign};
 
-    use pw_derive::Settings;
+    use pw_settings::{FieldTrait, FieldType, ObjectSchema, PathSegment, SettingsError, Value, Vec3Config};
     use serde::{Deserialize, Serialize};
 
     // was settings
-    #[derive(Serialize, Deserialize, Debug, Clone, Copy, Settings)]
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
     pub struct Vec3 {
         pub x: f32,
         pub y: f32,
         pub z: f32,
     }
 
+    /// Shown as a single vec3 widget, paths like `position.x` still edit one axis
+    impl FieldTrait for Vec3 {
+        type Config = Vec3Config;
+
+        fn default_self(config: &Self::Config) -> Self {
+            let [x, y, z] = <[f32; 3]>::default_self(config);
+            Self::new(x, y, z)
+        }
+
+        fn to_field(&self, config: &Self::Config) -> FieldType {
+            [self.x, self.y, self.z].to_field(config)
+        }
+
+        fn from_field(field: &FieldType, config: &Self::Config) -> Result<Self, SettingsError> {
+            <[f32; 3]>::from_field(field, config).map(|[x, y, z]| Self::new(x, y, z))
+        }
+
+        fn apply_path(&mut self, path: &[PathSegment], value: Value, config: &Self::Config) -> Result<(), SettingsError> {
+            let mut xyz = [self.x, self.y, self.z];
+            xyz.apply_path(path, value, config)?;
+
+            let [x, y, z] = xyz;
+            *self = Self::new(x, y, z);
+            Ok(())
+        }
+
+        /// Serialized as an object, not like `[f32; 3]`
+        fn field_schema(config: &Self::Config) -> Value {
+            let mut schema = ObjectSchema::new("vec3");
+            schema.add_property("x", f32::field_schema(&config.x), true);
+            schema.add_property("y", f32::field_schema(&config.y), true);
+            schema.add_property("z", f32::field_schema(&config.z), true);
+            schema.into_value()
+        }
+    }
+
     impl Vec3 {
         #[inline]
         pub fn zero() -> Self {
@@ -0,0 +1,166 @@
+//! Runs the simulation at a fixed tick rate, whatever the frame rate is.
+//! Frames draw in between ticks, interpolated from the last two.
+
+use crate::set_timings;
+use crate::util;
+
+/// Simulation ticks per second
+pub const TICK_RATE: f64 = 60.0;
+
+/// Longer frames are cut short, like when the tab was in the background.
+/// The simulation slows down instead of spending every frame catching up.
+const MAX_TICKS: u32 = 8;
+
+/// Steps of the same size, time that doesn't fill a step is kept for the next frame
+#[derive(Debug, Clone)]
+pub struct FixedStep {
+    step: f64,
+    left: f64,
+}
+
+impl FixedStep {
+    pub fn new(step: f64) -> Self {
+        Self { step, left: 0.0 }
+    }
+
+    /// Size of a step in seconds
+    pub fn step(&self) -> f64 {
+        self.step
+    }
+
+    /// Add `dt` seconds, returns how many steps have to be taken now.
+    pub fn advance(&mut self, dt: f64) -> u32 {
+        self.left += dt.max(0.0);
+
+        let steps = (self.left / self.step).floor() as u32;
+        if steps > MAX_TICKS {
+            // The skipped steps are gone, the part of a step that's left still counts
+            self.left %= self.step;
+            return MAX_TICKS;
+        }
+
+        self.left -= steps as f64 * self.step;
+        steps
+    }
+
+    /// How far into the next step, 0 is right at the last one and 1 at the next
+    pub fn alpha(&self) -> f64 {
+        (self.left / self.step).min(1.0)
+    }
+}
+
+/// Frame and tick times, averaged over a second and shown on the page
+#[derive(Default)]
+pub struct FpsCounter {
+    frames: u32,
+    ticks: u32,
+    time: f64,
+    tick_time: f64,
+}
+
+impl FpsCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn frame(&mut self, dt: f64) {
+        self.time += dt;
+        self.frames += 1;
+
+        if self.time > 1.0 {
+            let frame_ms = self.time * 1000.0 / self.frames as f64;
+            let tick_ms = if self.ticks > 0 { self.tick_time / self.ticks as f64 } else { 0.0 };
+            unsafe { set_timings(self.frames as f32 / self.time as f32, frame_ms as f32, tick_ms as f32) };
+
+            *self = Self::new();
+        }
+    }
+
+    /// `ms` is how long all `ticks` took together
+    pub fn ticks(&mut self, ticks: u32, ms: f64) {
+        self.ticks += ticks;
+        self.tick_time += ms;
+    }
+}
+
+pub struct GameLoop {
+    stepper: FixedStep,
+    fps_counter: FpsCounter,
+}
+
+impl Default for GameLoop {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameLoop {
+    pub fn new() -> Self {
+        Self {
+            stepper: FixedStep::new(1.0 / TICK_RATE),
+            fps_counter: FpsCounter::new(),
+        }
+    }
+
+    /// Seconds per tick
+    pub fn tick_time(&self) -> f64 {
+        self.stepper.step()
+    }
+
+    /// A new frame, `dt` seconds after the last one. `tick` is called for every tick that is due,
+    /// returns how far the frame is between the last two ticks.
+    pub fn frame<F: FnMut(f64)>(&mut self, dt: f64, mut tick: F) -> f64 {
+        self.fps_counter.frame(dt);
+
+        let ticks = self.stepper.advance(dt);
+        let start = util::now();
+        for _ in 0..ticks {
+            tick(self.stepper.step());
+        }
+        self.fps_counter.ticks(ticks, util::now() - start);
+
+        self.stepper.alpha()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_add_up() {
+        let mut fixed = FixedStep::new(0.25);
+
+        assert_eq!(fixed.advance(0.1), 0);
+        assert_eq!(fixed.advance(0.1), 0);
+        assert_eq!(fixed.advance(0.1), 1);
+        assert!((fixed.alpha() - 0.2).abs() < 1e-9);
+
+        assert_eq!(fixed.advance(0.7), 3);
+        assert!(fixed.alpha().abs() < 1e-9);
+        // Time doesn't run backwards
+        assert_eq!(fixed.advance(-1.0), 0);
+    }
+
+    #[test]
+    fn long_frames_are_cut_short() {
+        let mut fixed = FixedStep::new(0.25);
+
+        assert_eq!(fixed.advance(10.125), MAX_TICKS);
+        // Half a step was left over
+        assert_eq!(fixed.alpha(), 0.5);
+        assert_eq!(fixed.advance(0.125), 1);
+        assert_eq!(fixed.alpha(), 0.0);
+    }
+
+    #[test]
+    fn alpha_is_the_part_of_a_step() {
+        let mut fixed = FixedStep::new(0.25);
+        assert_eq!(fixed.alpha(), 0.0);
+
+        fixed.advance(0.2);
+        assert_eq!(fixed.alpha(), 0.8);
+        fixed.advance(0.05);
+        assert_eq!(fixed.alpha(), 0.0);
+    }
+}
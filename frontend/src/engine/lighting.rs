@@ -0,0 +1,137 @@
+//! Lights are the same for everything in a frame, they go to the renderer's globals.
+//! Materials are per object, they say how much of that light it reflects.
+
+use cgmath::{InnerSpace, Vector3};
+use pw_derive::Settings;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::uniform::{Uniform, Uniform1fv, Uniform1i, Uniform3f, Uniform3fv, Uniform4fv};
+
+/// Has to match `MAX_LIGHTS` in `basic.frag`, lights after that are ignored
+pub const MAX_LIGHTS: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Light {
+    /// Infinitely far away, `direction` points towards the light
+    Directional { direction: Vector3<f32>, color: [f32; 3] },
+    /// Fades with distance, at `range` it's half as bright
+    Point { position: Vector3<f32>, color: [f32; 3], range: f32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct Lights {
+    /// Reaches everything, so back sides aren't pitch black
+    pub ambient: [f32; 3],
+    lights: Vec<Light>,
+}
+
+impl Default for Lights {
+    fn default() -> Self {
+        let mut lights = Self::new([0.15, 0.15, 0.15]);
+        lights.push(Light::Directional {
+            direction: Vector3::new(0.28735632183908044, 0.4022988505747126, 0.5747126436781609),
+            color: [1.0, 1.0, 1.0],
+        });
+        lights
+    }
+}
+
+impl Lights {
+    pub fn new(ambient: [f32; 3]) -> Self {
+        Self {
+            ambient,
+            lights: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    /// For `basic.frag`, where a w of 0 in `u_lightPosition` means directional
+    pub fn uniforms(&self) -> HashMap<String, Box<dyn Uniform>> {
+        let mut positions = Vec::with_capacity(MAX_LIGHTS * 4);
+        let mut colors = Vec::with_capacity(MAX_LIGHTS * 3);
+        let mut ranges = Vec::with_capacity(MAX_LIGHTS);
+
+        for light in self.lights.iter().take(MAX_LIGHTS) {
+            match *light {
+                Light::Directional { direction, color } => {
+                    let d = direction.normalize();
+                    positions.extend_from_slice(&[d.x, d.y, d.z, 0.0]);
+                    colors.extend_from_slice(&color);
+                    ranges.push(1.0);
+                }
+                Light::Point { position, color, range } => {
+                    positions.extend_from_slice(&[position.x, position.y, position.z, 1.0]);
+                    colors.extend_from_slice(&color);
+                    ranges.push(range.max(0.001));
+                }
+            }
+        }
+
+        // The arrays are always full length, unused lights are skipped by the count
+        let count = ranges.len();
+        positions.resize(MAX_LIGHTS * 4, 0.0);
+        colors.resize(MAX_LIGHTS * 3, 0.0);
+        ranges.resize(MAX_LIGHTS, 1.0);
+
+        let [r, g, b] = self.ambient;
+        let mut uniforms: HashMap<String, Box<dyn Uniform>> = HashMap::new();
+        uniforms.insert("u_ambient".into(), Box::new(Uniform3f::new(r, g, b)));
+        uniforms.insert("u_lightCount".into(), Box::new(Uniform1i::new(count as i32)));
+        uniforms.insert("u_lightPosition".into(), Box::new(Uniform4fv::new(positions)));
+        uniforms.insert("u_lightColor".into(), Box::new(Uniform3fv::new(colors)));
+        uniforms.insert("u_lightRange".into(), Box::new(Uniform1fv::new(ranges)));
+        uniforms
+    }
+}
+
+/// How an object reflects light, every term scales its color
+#[derive(Debug, Clone, Serialize, Deserialize, Settings)]
+#[settings(default)]
+pub struct Material {
+    #[settings(max = 1.0, inc = 0.01)]
+    pub ambient: f32,
+    #[settings(max = 1.0, inc = 0.01)]
+    pub diffuse: f32,
+    /// Highlights, they're white instead of the object's color
+    #[settings(max = 1.0, inc = 0.01)]
+    pub specular: f32,
+    /// Higher is a smaller, sharper highlight
+    #[settings(min = 1.0, max = 256.0, inc = 1.0)]
+    pub shininess: f32,
+    /// Glows on its own, without any light
+    #[settings(max = 1.0, inc = 0.01)]
+    pub emissive: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            ambient: 1.0,
+            diffuse: 1.0,
+            specular: 0.2,
+            shininess: 16.0,
+            emissive: 0.0,
+        }
+    }
+}
+
+impl Material {
+    /// For `UniformsHandle::batch`
+    pub fn uniforms(&self) -> [(&'static str, f32); 5] {
+        [
+            ("u_material.ambient", self.ambient),
+            ("u_material.diffuse", self.diffuse),
+            ("u_material.specular", self.specular),
+            ("u_material.shininess", self.shininess),
+            ("u_material.emissive", self.emissive),
+        ]
+    }
+}
@@ -7,7 +7,16 @@ pub use entity::*;
 
 pub mod objects;
 
-pub use objects::{Object, ObjectConfig, ObjectFactory};
+mod game_loop;
+pub use game_loop::*;
+
+pub use objects::{ObjectConfig, ObjectFactory};
+
+mod scene;
+pub use scene::*;
+
+mod lighting;
+pub use lighting::*;
 
 pub type Index = usize;
 pub type Float = f32;
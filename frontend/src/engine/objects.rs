@@ -1,15 +1,13 @@
-use super::{Camera, Entity};
-use crate::uniform::{Uniform3f, UniformMat4};
+use super::{Entity, Material, NodeId, Scene};
 use crate::webgl::buffer::{IndexBuffer, VertexArray, VertexBuffer, VertexBufferLayout};
 use crate::webgl::renderer::{DefaultRenderable, Renderer};
 use crate::webgl::shader::ShaderFactory;
-use crate::webgl::uniform::UniformsHandle;
 
+use pw_derive::Settings;
 use std::collections::HashMap;
 use web_sys::WebGlRenderingContext as GL;
 
 use super::{Float, Index, Mesh, Vector};
-use cgmath::Vector3;
 
 #[inline]
 fn normalize([x, y, z]: Vector<Float>) -> Vector<Float> {
@@ -132,9 +130,12 @@ pub fn build_vertices(
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+/// How normals are built, per face or averaged over the faces around a vertex
+#[derive(Copy, Clone, Debug, Settings)]
 pub enum ObjectConfig {
+    #[settings(name = "Flat")]
     Simple,
+    #[settings(name = "Smooth")]
     Mean,
 }
 
@@ -173,22 +174,23 @@ impl ObjectFactory {
         self.mesh = mesh;
     }
 
-    // TODO this "u_reverseLightDirection" shouldn't be here
-    pub fn create(&self, gl: &GL, renderer: &mut Renderer, entity: Entity) -> Option<Object> {
+    /// A renderable of its own, shown as a new node in `scene`
+    pub fn create(
+        &self,
+        gl: &GL,
+        renderer: &mut Renderer,
+        scene: &mut Scene,
+        parent: Option<NodeId>,
+        entity: Entity,
+    ) -> Option<NodeId> {
         let renderable = self.create_renderable(gl)?;
 
         let uniforms = renderable.handle();
-        uniforms.single(
-            "u_reverseLightDirection",
-            Uniform3f::new(0.28735632183908044, 0.4022988505747126, 0.5747126436781609),
-        );
-        uniforms.single(
-            "u_color",
-            Uniform3f::new(1.0, 1.0, 1.0),
-        );
+        uniforms.batch(Material::default().uniforms());
+        uniforms.single("u_color", [1.0, 1.0, 1.0]);
         renderer.add_renderable(renderable, 0);
 
-        Some(Object { uniforms, entity })
+        scene.add(parent, entity, Some(uniforms))
     }
 
     pub fn create_renderable(&self, gl: &GL) -> Option<DefaultRenderable> {
@@ -221,59 +223,6 @@ impl ObjectFactory {
         vao.add_buffer(vertex_buffer, layout);
         vao.add_buffer(normal_buffer, normal_layout);
 
-        Some(DefaultRenderable::new(index_buffer, vao, shader, None))
-    }
-}
-
-pub struct Object {
-    uniforms: UniformsHandle,
-    entity: Entity,
-}
-
-impl Object {
-    pub fn new(uniforms: UniformsHandle, entity: Entity) -> Self {
-        Self { uniforms, entity }
-    }
-    pub fn update(&mut self, dt: f32, camera: &Camera) {
-        self.entity.update(dt);
-
-        self.uniforms.single(
-            "u_worldViewProjection",
-            UniformMat4::new_mat4(camera.world_view_projection_matrix()),
-        );
-        self.uniforms
-            .single("u_world", UniformMat4::new_mat4(self.entity.world_matrix()));
-    }
-    pub fn enable(&self) {
-        self.uniforms.enable();
-    }
-    pub fn disable(&self) {
-        self.uniforms.disable();
-    }
-
-    pub fn set_entity(&mut self, entity: Entity) {
-        self.entity = entity;
-    }
-
-    pub fn uniform_handle(&self) -> UniformsHandle {
-        self.uniforms.clone()
-    }
-
-    pub fn click_hit(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> bool {
-        self.entity.is_hit(origin, direction)
-    }
-}
-
-use std::ops::{Deref, DerefMut};
-impl Deref for Object {
-    type Target = Entity;
-    fn deref(&self) -> &Entity {
-        &self.entity
-    }
-}
-
-impl DerefMut for Object {
-    fn deref_mut(&mut self) -> &mut Entity {
-        &mut self.entity
+        Some(DefaultRenderable::new(index_buffer, vao, shader))
     }
 }
@@ -0,0 +1,320 @@
+//! Everything that is drawn, as a tree. Every node has a transform relative to its parent,
+//! so moons can follow their planet and a cockpit can follow the camera.
+
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector3, Vector4};
+
+use super::Entity;
+use crate::instances::{UniformSlot, UniformsHandle};
+use crate::slotmap::{Key, SlotMap};
+
+/// A node in a `Scene`, it stays valid until the node is removed.
+/// Ids of removed nodes don't find the nodes that take their place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(Key);
+
+struct Node {
+    /// Relative to the parent
+    entity: Entity,
+    /// Before the last tick, frames are drawn in between
+    previous: Entity,
+    /// Nodes without uniforms only group others
+    uniforms: Option<UniformsHandle>,
+    /// `u_world` of `uniforms`, set every frame
+    world_slot: Option<UniformSlot<Matrix4<f32>>>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    /// Its own flag, it's only shown when all its parents are enabled too
+    enabled: bool,
+}
+
+#[derive(Default)]
+pub struct Scene {
+    nodes: SlotMap<Node>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn node(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.0)
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.get_mut(id.0)
+    }
+
+    /// Add a node under `parent`, or at the top. Adding under a removed node returns `None`.
+    pub fn add(&mut self, parent: Option<NodeId>, entity: Entity, uniforms: Option<UniformsHandle>) -> Option<NodeId> {
+        let visible = match parent {
+            Some(parent) => self.is_enabled(parent)?,
+            None => true,
+        };
+
+        if let (false, Some(uniforms)) = (visible, &uniforms) {
+            uniforms.disable();
+        }
+
+        let node = Node {
+            previous: entity.clone(),
+            entity,
+            world_slot: uniforms.as_ref().map(|u| u.slot("u_world")),
+            uniforms,
+            parent,
+            children: Vec::new(),
+            enabled: true,
+        };
+
+        let id = NodeId(self.nodes.insert(node));
+
+        match parent {
+            Some(parent) => self.node_mut(parent)?.children.push(id),
+            None => self.roots.push(id),
+        }
+
+        Some(id)
+    }
+
+    /// Remove a node with everything under it, their renderables are dropped too
+    pub fn remove(&mut self, id: NodeId) {
+        let parent = match self.node(id) {
+            Some(node) => node.parent,
+            None => return,
+        };
+
+        match parent.and_then(|p| self.node_mut(p)) {
+            Some(parent) => parent.children.retain(|c| *c != id),
+            None => self.roots.retain(|c| *c != id),
+        }
+
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.nodes.remove(id.0) {
+                if let Some(uniforms) = &node.uniforms {
+                    uniforms.remove();
+                }
+                stack.extend(node.children);
+            }
+        }
+    }
+
+    pub fn entity(&self, id: NodeId) -> Option<&Entity> {
+        self.node(id).map(|n| &n.entity)
+    }
+
+    /// Changes during a tick are interpolated, use `set_entity` to jump
+    pub fn entity_mut(&mut self, id: NodeId) -> Option<&mut Entity> {
+        self.node_mut(id).map(|n| &mut n.entity)
+    }
+
+    pub fn set_entity(&mut self, id: NodeId, entity: Entity) {
+        if let Some(node) = self.node_mut(id) {
+            node.previous = entity.clone();
+            node.entity = entity;
+        }
+    }
+
+    pub fn uniforms(&self, id: NodeId) -> Option<&UniformsHandle> {
+        self.node(id)?.uniforms.as_ref()
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id)?.parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        self.node(id).map(|n| n.children.as_slice()).unwrap_or(&[])
+    }
+
+    /// Whether it's shown, so whether it and all its parents are enabled
+    pub fn is_enabled(&self, id: NodeId) -> Option<bool> {
+        let node = self.node(id)?;
+        match node.parent {
+            Some(parent) => Some(node.enabled && self.is_enabled(parent)?),
+            None => Some(node.enabled),
+        }
+    }
+
+    /// Enabling a node shows the nodes under it again, unless they were disabled themselves
+    pub fn set_enabled(&mut self, id: NodeId, enabled: bool) {
+        match self.node_mut(id) {
+            Some(node) if node.enabled != enabled => node.enabled = enabled,
+            _ => return,
+        }
+
+        let visible = self.is_enabled(id).unwrap_or(false);
+        self.show(id, visible);
+    }
+
+    fn show(&self, id: NodeId, parent_visible: bool) {
+        let node = match self.node(id) {
+            Some(node) => node,
+            None => return,
+        };
+
+        let visible = parent_visible && node.enabled;
+        if let Some(uniforms) = &node.uniforms {
+            if visible {
+                uniforms.enable();
+            } else {
+                uniforms.disable();
+            }
+        }
+
+        for child in &node.children {
+            self.show(*child, visible);
+        }
+    }
+
+    /// Start of a tick, every node moves with its own speeds.
+    /// Whatever happens to them afterwards in the same tick is interpolated too.
+    pub fn tick(&mut self, dt: f32) {
+        for node in self.nodes.values_mut() {
+            node.previous = node.entity.clone();
+            node.entity.update(dt);
+        }
+    }
+
+    /// Where the node is in the world, after the last tick
+    pub fn world_matrix(&self, id: NodeId) -> Option<Matrix4<f32>> {
+        let node = self.node(id)?;
+        let local = node.entity.world_matrix();
+        match node.parent {
+            Some(parent) => Some(self.world_matrix(parent)? * local),
+            None => Some(local),
+        }
+    }
+
+    /// Does the ray hit the bounding sphere of the node
+    pub fn click_hit(&self, id: NodeId, origin: Vector3<f32>, direction: Vector3<f32>) -> bool {
+        let world = match self.world_matrix(id) {
+            Some(world) => world,
+            None => return false,
+        };
+
+        let center = (world * Vector4::new(0.0, 0.0, 0.0, 1.0)).truncate();
+        let radius = world.x.truncate().magnitude().max(world.y.truncate().magnitude()).max(world.z.truncate().magnitude());
+
+        super::sphere_hit(center, radius, origin, direction)
+    }
+
+    /// Draw every shown node `alpha` of the way from the previous tick to the last one.
+    /// Only world matrices are set, the camera is in the renderer's globals.
+    pub fn update_uniforms(&self, alpha: f32) {
+        for root in &self.roots {
+            self.update_node(*root, Matrix4::identity(), alpha);
+        }
+    }
+
+    fn update_node(&self, id: NodeId, parent: Matrix4<f32>, alpha: f32) {
+        let node = match self.node(id) {
+            Some(node) if node.enabled => node,
+            _ => return,
+        };
+
+        let world = parent * node.previous.lerp(&node.entity, alpha).world_matrix();
+        if let (Some(uniforms), Some(slot)) = (&node.uniforms, node.world_slot) {
+            uniforms.set(slot, world);
+            uniforms.center((world * Vector4::new(0.0, 0.0, 0.0, 1.0)).truncate());
+        }
+
+        for child in &node.children {
+            self.update_node(*child, world, alpha);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instances::Instances;
+
+    fn at(x: f32, y: f32, z: f32) -> Entity {
+        Entity::default().with_position(Vector3::new(x, y, z))
+    }
+
+    fn origin(world: Matrix4<f32>) -> Vector3<f32> {
+        (world * Vector4::new(0.0, 0.0, 0.0, 1.0)).truncate()
+    }
+
+    #[test]
+    fn removes_whole_subtrees() {
+        let instances = Instances::new();
+        let mut scene = Scene::new();
+
+        let handles: Vec<_> = (0..4).map(|_| instances.push()).collect();
+
+        let planet = scene.add(None, at(0.0, 0.0, 0.0), Some(handles[0].clone())).unwrap();
+        let moon = scene.add(Some(planet), at(1.0, 0.0, 0.0), Some(handles[1].clone())).unwrap();
+        let crater = scene.add(Some(moon), at(0.0, 1.0, 0.0), Some(handles[2].clone())).unwrap();
+        scene.add(None, at(5.0, 0.0, 0.0), Some(handles[3].clone())).unwrap();
+
+        scene.remove(moon);
+        assert!(scene.children(planet).is_empty());
+        assert!(scene.entity(moon).is_none());
+        assert!(scene.entity(crater).is_none());
+
+        let removed: Vec<_> = handles.iter().map(|h| h.is_removed()).collect();
+        assert_eq!(removed, vec![false, true, true, false]);
+
+        // Nodes added in their place don't answer to the old ids
+        let again = scene.add(Some(planet), at(2.0, 0.0, 0.0), None).unwrap();
+        assert_ne!(again, moon);
+        assert!(scene.add(Some(moon), Entity::default(), None).is_none());
+        assert!(scene.world_matrix(crater).is_none());
+        assert_eq!(scene.children(planet), &[again]);
+    }
+
+    #[test]
+    fn disabled_parents_hide_their_children() {
+        let instances = Instances::new();
+        let mut scene = Scene::new();
+
+        let planet = scene.add(None, Entity::default(), Some(instances.push())).unwrap();
+        let moon = scene.add(Some(planet), Entity::default(), Some(instances.push())).unwrap();
+        let crater = scene.add(Some(moon), Entity::default(), Some(instances.push())).unwrap();
+        let shown = |scene: &Scene, id| scene.uniforms(id).unwrap().is_enabled();
+
+        scene.set_enabled(planet, false);
+        assert_eq!(scene.is_enabled(crater), Some(false));
+        assert!(!shown(&scene, moon) && !shown(&scene, crater));
+
+        // Added under a hidden node, hidden from the start
+        let late = scene.add(Some(moon), Entity::default(), Some(instances.push())).unwrap();
+        assert!(!shown(&scene, late));
+
+        // A child disabled on its own stays hidden when its parent comes back
+        scene.set_enabled(moon, false);
+        scene.set_enabled(planet, true);
+        assert_eq!(scene.is_enabled(planet), Some(true));
+        assert_eq!(scene.is_enabled(moon), Some(false));
+        assert!(shown(&scene, planet) && !shown(&scene, crater));
+
+        scene.set_enabled(moon, true);
+        assert_eq!(scene.is_enabled(crater), Some(true));
+        assert!(shown(&scene, crater) && shown(&scene, late));
+    }
+
+    #[test]
+    fn world_matrices_compose() {
+        let mut scene = Scene::new();
+
+        let planet = Entity::default()
+            .with_position(Vector3::new(10.0, 0.0, 0.0))
+            .with_hom_scale(2.0);
+        let planet = scene.add(None, planet, None).unwrap();
+        let moon = scene.add(Some(planet), at(1.0, 0.0, 0.0), None).unwrap();
+
+        let world = scene.world_matrix(moon).unwrap();
+        assert_eq!(world, scene.world_matrix(planet).unwrap() * scene.entity(moon).unwrap().world_matrix());
+        // Scaled by its planet, one unit away is two
+        assert!((origin(world) - Vector3::new(12.0, 0.0, 0.0)).magnitude() < 1e-5);
+
+        // Children follow their parent
+        scene.entity_mut(planet).unwrap().set_position(Vector3::new(0.0, 5.0, 0.0).into());
+        let world = scene.world_matrix(moon).unwrap();
+        assert!((origin(world) - Vector3::new(2.0, 5.0, 0.0)).magnitude() < 1e-5);
+    }
+}
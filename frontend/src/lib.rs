@@ -16,6 +16,8 @@ pub use webgl::*;
 
 pub mod settings;
 
+pub mod presets;
+
 pub mod engine;
 
 pub mod models;
@@ -24,6 +26,10 @@ pub mod delaunay;
 
 pub mod universe;
 
+pub mod physics;
+
+pub mod spectator;
+
 pub mod util;
 
 #[wasm_bindgen]
@@ -37,6 +43,9 @@ extern "C" {
     #[wasm_bindgen]
     fn set_info(x: f32, y: f32, z: f32, angl_x: f32, angl_y: f32, angl_z: f32);
 
+    #[wasm_bindgen]
+    fn set_timings(fps: f32, frame_ms: f32, tick_ms: f32);
+
     #[wasm_bindgen]
     fn set_settings(settings: JsValue);
 }
@@ -0,0 +1,201 @@
+//! How planets move: in a straight line, on a fixed orbit around another planet,
+//! or pulled around by the gravity of the others.
+
+mod orbit;
+pub use orbit::Orbit;
+mod nbody;
+pub use nbody::{Body, NBody, G};
+
+use cgmath::{Deg, Rad, Vector3};
+use pw_derive::Settings;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::Vec3;
+use crate::universe::Planet;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Settings)]
+pub enum Motion {
+    /// Keeps going at its speed
+    #[default]
+    Linear,
+    /// Circles the planet named `parent`, its own position and speed are ignored
+    Orbit {
+        /// Name of the planet in the middle
+        parent: String,
+        /// Half of the longest diameter
+        #[settings(unit = "units", min = 0.0, max = 1000.0, inc = 0.1, value = 10.0)]
+        distance: f32,
+        #[settings(unit = "s", min = 0.1, max = 1000.0, inc = 0.1, value = 60.0)]
+        period: f32,
+        /// 0 is a circle, closer to 1 is more stretched
+        #[settings(max = 0.99, inc = 0.01)]
+        eccentricity: f32,
+        /// Tilt from the xz-plane
+        #[settings(unit = "degrees", min = -90.0, max = 90.0, inc = 0.1)]
+        inclination: f32,
+        /// Where on the orbit it starts
+        #[settings(unit = "degrees", max = 360.0, inc = 0.1)]
+        phase: f32,
+    },
+    /// Pulls on and is pulled by every other planet with gravity, starting at its position and speed
+    Gravity {
+        #[settings(min = 0.0, max = 100000.0, inc = 10.0, value = 1000.0)]
+        mass: f32,
+    },
+}
+
+/// A planet's motion, with its parent looked up
+#[derive(Debug, Clone)]
+enum Mover {
+    Linear { speed: Vector3<f64> },
+    Orbit { parent: usize, orbit: Orbit },
+    /// Index into the bodies
+    Gravity(usize),
+}
+
+/// Positions of all planets over time, one tick at a time.
+/// Rebuilt whenever the planets change, so it always starts from their settings.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    time: f64,
+    positions: Vec<Vector3<f64>>,
+    movers: Vec<Mover>,
+    /// Parents come before the planets orbiting them
+    order: Vec<usize>,
+    bodies: NBody,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+impl Simulation {
+    pub fn new(planets: &[Planet]) -> Self {
+        let mut bodies = Vec::new();
+        let mut movers: Vec<Mover> = planets
+            .iter()
+            .map(|planet| {
+                let speed = to_f64(planet.location.speed());
+                match &planet.motion {
+                    Motion::Gravity { mass } if !planet.disabled => {
+                        bodies.push(Body {
+                            position: to_f64(planet.location.position()),
+                            velocity: speed,
+                            mass: *mass as f64,
+                        });
+                        Mover::Gravity(bodies.len() - 1)
+                    }
+                    Motion::Orbit { parent, distance, period, eccentricity, inclination, phase } => {
+                        match planets.iter().position(|p| &p.name == parent) {
+                            Some(parent) => Mover::Orbit {
+                                parent,
+                                orbit: Orbit {
+                                    distance: *distance as f64,
+                                    period: *period as f64,
+                                    eccentricity: *eccentricity as f64,
+                                    inclination: Rad::from(Deg(*inclination as f64)).0,
+                                    phase: Rad::from(Deg(*phase as f64)).0,
+                                },
+                            },
+                            None => {
+                                console_log!("Planet {} orbits unknown planet {}", planet.name, parent);
+                                Mover::Linear { speed }
+                            }
+                        }
+                    }
+                    _ => Mover::Linear { speed },
+                }
+            })
+            .collect();
+
+        let order = parents_first(&mut movers, planets);
+
+        let mut out = Self {
+            time: 0.0,
+            positions: planets.iter().map(|p| to_f64(p.location.position())).collect(),
+            movers,
+            order,
+            bodies: NBody::new(bodies),
+        };
+        out.place(0.0);
+        out
+    }
+
+    /// Move on by `dt` seconds. Gravity is only stable for small steps, so this should be a fixed tick.
+    pub fn tick(&mut self, dt: f64) {
+        self.time += dt;
+        self.bodies.step(dt);
+        self.place(dt);
+    }
+
+    /// Seconds simulated since the start
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Energy of the planets under gravity, it should stay about the same
+    pub fn energy(&self) -> f64 {
+        self.bodies.energy()
+    }
+
+    /// Current position of planet `index`
+    pub fn position(&self, index: usize) -> Option<Vec3> {
+        self.positions.get(index).map(|p| Vec3::new(p.x as f32, p.y as f32, p.z as f32))
+    }
+
+    fn place(&mut self, dt: f64) {
+        for &i in &self.order {
+            self.positions[i] = match &self.movers[i] {
+                Mover::Linear { speed } => self.positions[i] + speed * dt,
+                Mover::Orbit { parent, orbit } => self.positions[*parent] + orbit.offset(self.time),
+                Mover::Gravity(body) => self.bodies.bodies()[*body].position,
+            };
+        }
+    }
+}
+
+/// Order in which parents are placed before their children.
+/// Orbits that go around in a circle can't be placed at all, they move in a straight line instead.
+fn parents_first(movers: &mut [Mover], planets: &[Planet]) -> Vec<usize> {
+    let mut order = Vec::with_capacity(movers.len());
+    let mut placed = vec![false; movers.len()];
+
+    for start in 0..movers.len() {
+        let mut chain = Vec::new();
+
+        'walk: loop {
+            chain.clear();
+            let mut at = start;
+
+            while !placed[at] {
+                if chain.contains(&at) {
+                    console_log!("Planet {} orbits itself", planets[at].name);
+                    movers[at] = Mover::Linear { speed: to_f64(planets[at].location.speed()) };
+                    continue 'walk;
+                }
+                chain.push(at);
+
+                match movers[at] {
+                    Mover::Orbit { parent, .. } => at = parent,
+                    _ => break,
+                }
+            }
+            break;
+        }
+
+        for &i in chain.iter().rev() {
+            if !placed[i] {
+                placed[i] = true;
+                order.push(i);
+            }
+        }
+    }
+
+    order
+}
+
+fn to_f64(v: Vec3) -> Vector3<f64> {
+    Vector3::new(v.x as f64, v.y as f64, v.z as f64)
+}
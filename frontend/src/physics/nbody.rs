@@ -0,0 +1,153 @@
+use cgmath::{InnerSpace, Vector3, Zero};
+
+/// Gravitational constant in universe units, masses are scaled to it
+pub const G: f64 = 1.0;
+
+/// Keeps close encounters from flinging bodies away, forces and energy use the same one
+const SOFTENING: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Body {
+    pub position: Vector3<f64>,
+    pub velocity: Vector3<f64>,
+    pub mass: f64,
+}
+
+/// Bodies that all pull on each other
+#[derive(Debug, Clone, Default)]
+pub struct NBody {
+    bodies: Vec<Body>,
+    accelerations: Vec<Vector3<f64>>,
+}
+
+impl NBody {
+    pub fn new(bodies: Vec<Body>) -> Self {
+        let accelerations = accelerations(&bodies);
+        Self { bodies, accelerations }
+    }
+
+    pub fn bodies(&self) -> &[Body] {
+        &self.bodies
+    }
+
+    /// Velocity Verlet (kick, drift, kick). It's symplectic, so the energy wobbles a bit
+    /// but doesn't drift away over time like with Euler steps.
+    pub fn step(&mut self, dt: f64) {
+        for (body, acc) in self.bodies.iter_mut().zip(&self.accelerations) {
+            body.velocity += acc * (dt * 0.5);
+            body.position += body.velocity * dt;
+        }
+
+        self.accelerations = accelerations(&self.bodies);
+
+        for (body, acc) in self.bodies.iter_mut().zip(&self.accelerations) {
+            body.velocity += acc * (dt * 0.5);
+        }
+    }
+
+    /// Kinetic plus potential energy, stays about the same while stepping
+    pub fn energy(&self) -> f64 {
+        let kinetic: f64 = self.bodies.iter().map(|b| 0.5 * b.mass * b.velocity.magnitude2()).sum();
+
+        let mut potential = 0.0;
+        for (i, a) in self.bodies.iter().enumerate() {
+            for b in &self.bodies[i + 1..] {
+                let distance = ((b.position - a.position).magnitude2() + SOFTENING * SOFTENING).sqrt();
+                potential -= G * a.mass * b.mass / distance;
+            }
+        }
+
+        kinetic + potential
+    }
+}
+
+fn accelerations(bodies: &[Body]) -> Vec<Vector3<f64>> {
+    let mut out = vec![Vector3::zero(); bodies.len()];
+
+    for (i, a) in bodies.iter().enumerate() {
+        for (j, b) in bodies.iter().enumerate().skip(i + 1) {
+            let delta = b.position - a.position;
+            let distance2 = delta.magnitude2() + SOFTENING * SOFTENING;
+            let pull = delta * (G / (distance2 * distance2.sqrt()));
+
+            out[i] += pull * b.mass;
+            out[j] -= pull * a.mass;
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Game ticks are 1/60 s
+    const DT: f64 = 1.0 / 60.0;
+    const TICKS: usize = 60 * 60;
+
+    fn body(position: [f64; 3], velocity: [f64; 3], mass: f64) -> Body {
+        Body {
+            position: position.into(),
+            velocity: velocity.into(),
+            mass,
+        }
+    }
+
+    /// A light planet on a circle around a heavy sun
+    fn two_body() -> Vec<Body> {
+        let speed = (G * 1000.0 / 10.0).sqrt();
+        vec![body([0.0; 3], [0.0; 3], 1000.0), body([10.0, 0.0, 0.0], [0.0, 0.0, speed], 1.0)]
+    }
+
+    /// Two planets on different orbits that pull on each other too
+    fn three_body() -> Vec<Body> {
+        let speed = |r: f64| (G * 1000.0 / r).sqrt();
+        vec![
+            body([0.0; 3], [0.0; 3], 1000.0),
+            body([10.0, 0.0, 0.0], [0.0, 0.0, speed(10.0)], 10.0),
+            body([0.0, 1.0, -16.0], [speed(16.0), 0.0, 0.0], 5.0),
+        ]
+    }
+
+    fn drift(start: f64, end: f64) -> f64 {
+        ((end - start) / start).abs()
+    }
+
+    /// Relative energy drift over a minute of ticks
+    fn verlet_drift(bodies: Vec<Body>) -> f64 {
+        let mut system = NBody::new(bodies);
+        let start = system.energy();
+        for _ in 0..TICKS {
+            system.step(DT);
+        }
+        drift(start, system.energy())
+    }
+
+    fn euler_drift(bodies: Vec<Body>) -> f64 {
+        let mut system = NBody::new(bodies);
+        let start = system.energy();
+        for _ in 0..TICKS {
+            let accelerations = accelerations(&system.bodies);
+            for (body, acc) in system.bodies.iter_mut().zip(accelerations) {
+                body.position += body.velocity * DT;
+                body.velocity += acc * DT;
+            }
+        }
+        drift(start, system.energy())
+    }
+
+    #[test]
+    fn two_body_energy_stays_put() {
+        let verlet = verlet_drift(two_body());
+        assert!(verlet < 1e-4, "drifted by {}", verlet);
+        assert!(verlet * 100.0 < euler_drift(two_body()));
+    }
+
+    #[test]
+    fn three_body_energy_stays_put() {
+        let verlet = verlet_drift(three_body());
+        assert!(verlet < 1e-3, "drifted by {}", verlet);
+        assert!(verlet * 10.0 < euler_drift(three_body()));
+    }
+}
@@ -0,0 +1,74 @@
+use cgmath::Vector3;
+
+use std::f64::consts::PI;
+
+/// Newton iterations for Kepler's equation, it converges long before this for e < 1
+const KEPLER_ITERATIONS: usize = 16;
+
+/// A Kepler orbit around a parent, only the relative position is known here.
+/// The orbit lies in the xz-plane, tilted around the x axis by `inclination`.
+#[derive(Debug, Clone, Copy)]
+pub struct Orbit {
+    /// Semi-major axis
+    pub distance: f64,
+    /// Seconds for one round
+    pub period: f64,
+    pub eccentricity: f64,
+    /// In radians
+    pub inclination: f64,
+    /// Where on the orbit it starts, as mean anomaly in radians
+    pub phase: f64,
+}
+
+impl Orbit {
+    /// Position relative to the parent, `time` seconds after the start
+    pub fn offset(&self, time: f64) -> Vector3<f64> {
+        let e = self.eccentricity;
+        let mean = self.phase + 2.0 * PI * time / self.period;
+        let anomaly = eccentric_anomaly(mean % (2.0 * PI), e);
+
+        let x = self.distance * (anomaly.cos() - e);
+        let z = self.distance * (1.0 - e * e).sqrt() * anomaly.sin();
+
+        let (sin, cos) = self.inclination.sin_cos();
+        Vector3::new(x, z * sin, z * cos)
+    }
+}
+
+/// Solve `E - e sin E = M` for `E`
+fn eccentric_anomaly(mean: f64, e: f64) -> f64 {
+    // Starting at pi is safe for very eccentric orbits
+    let mut anomaly = if e > 0.8 { PI } else { mean };
+
+    for _ in 0..KEPLER_ITERATIONS {
+        let delta = (anomaly - e * anomaly.sin() - mean) / (1.0 - e * anomaly.cos());
+        anomaly -= delta;
+        if delta.abs() < 1e-12 {
+            break;
+        }
+    }
+
+    anomaly
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+
+    #[test]
+    fn repeats_every_period() {
+        let orbit = Orbit {
+            distance: 12.0,
+            period: 7.5,
+            eccentricity: 0.6,
+            inclination: 0.3,
+            phase: 1.0,
+        };
+
+        for &time in &[0.0, 1.3, 5.0, 20.1, 100.0] {
+            let gap = (orbit.offset(time + orbit.period) - orbit.offset(time)).magnitude();
+            assert!(gap < 1e-9, "off by {} at {}", gap, time);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use pw_settings::{Preset, Presets};
+use wasm_bindgen::JsValue;
+
+use crate::util;
+
+/// Presets of every class are kept in `localStorage` under this key
+const STORAGE_KEY: &str = "pw-presets";
+pub const PRESET_DIR: &str = "presets";
+
+fn storage() -> Result<web_sys::Storage, JsValue> {
+    web_sys::window()
+        .ok_or("no window")?
+        .local_storage()?
+        .ok_or_else(|| "localStorage is not available".into())
+}
+
+/// Presets saved in this browser. Broken storage is treated as empty, it's only a cache of changes.
+pub fn load_local() -> Presets {
+    let stored = storage().and_then(|s| s.get_item(STORAGE_KEY)).ok().flatten();
+
+    match stored.map(|x| serde_json::from_str(&x)) {
+        Some(Ok(presets)) => presets,
+        Some(Err(e)) => {
+            console_log!("Ignoring stored presets {:?}", e);
+            Presets::new()
+        }
+        None => Presets::new(),
+    }
+}
+
+pub fn save_local(presets: &Presets) -> Result<(), JsValue> {
+    let json = serde_json::to_string(presets).map_err(|e| format!("Serde failed {:?}", e))?;
+    storage()?.set_item(STORAGE_KEY, &json)
+}
+
+/// Presets shared through the backend, one file per class
+pub async fn load_remote(class: &str) -> Result<Presets, JsValue> {
+    let text = util::fetch(&format!("{}/{}.json", PRESET_DIR, class)).await?;
+    serde_json::from_str(&text).map_err(|e| format!("Invalid presets {:?}", e).into())
+}
+
+pub async fn save_remote(class: &str, presets: &Presets) -> Result<(), JsValue> {
+    let json = serde_json::to_string(presets).map_err(|e| format!("Serde failed {:?}", e))?;
+    util::put(&format!("{}/{}.json", PRESET_DIR, class), &json).await
+}
+
+/// Add the backend's presets to the local ones, local ones win when both have the same name.
+pub async fn sync_remote(class: &str) -> Result<(), JsValue> {
+    let remote = load_remote(class).await?;
+    let mut local = load_local();
+
+    for name in remote.names(class) {
+        if local.get(class, name).is_none() {
+            local.insert(remote.get(class, name).unwrap().clone());
+        }
+    }
+
+    save_local(&local)
+}
+
+/// Save `preset` locally, and on the backend when `remote` is set.
+pub fn save(preset: Preset, remote: bool) -> Result<(), JsValue> {
+    let class = preset.class.clone();
+
+    let mut local = load_local();
+    local.insert(preset);
+    save_local(&local)?;
+
+    if remote {
+        // Only this class goes to the backend, the file is per class
+        let mut shared = Presets::new();
+        for name in local.names(&class) {
+            shared.insert(local.get(&class, name).unwrap().clone());
+        }
+
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(e) = save_remote(&class, &shared).await {
+                console_log!("Saving presets failed {:?}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
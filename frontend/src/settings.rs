@@ -1,4 +1,5 @@
 use pw_derive::Settings;
+use pw_settings::SettingsTrait;
 use serde::{Deserialize, Serialize};
 
 #[derive(Settings, Debug, Serialize, Deserialize, Clone)]
@@ -9,31 +10,28 @@ pub struct InnerSettings {
 
 #[derive(Settings, Serialize, Deserialize, Debug)]
 pub struct TheseSettings {
-    // #[settings(name = "Slidy", value = 0.4, inc = 0.01)]
+    #[settings(name = "Slidy", value = 0.4, inc = 0.01)]
     pub inner_diameter: f32,
-    // #[settings(name = "Count", value = 12.0, max = 128.0, inc = 1.0)]
+    #[settings(name = "Count", value = 12.0, max = 128.0, inc = 1.0)]
     pub count: f32,
-    // #[settings(value=[0.4, 0.1, 0.7])]
     pub vector: Vec<f32>,
 
-    // #[settings(value = "Hello world")]
+    #[settings(value = "Hellow world")]
     pub some_text: String,
 
-    // #[settings(data = true, value = "Private very private")]
+    #[settings(read_only, value = "Hellow world")]
     pub private_text: String,
 
+    #[settings(x = [ty = [f32], value = 0.8], y = [ty = [f32], value = 0.4])]
     pub location: InnerSettings,
 }
 
 impl TheseSettings {
+    /// Defaults come from the attributes, only `vector` can't be set there
     pub fn new() -> Self {
         TheseSettings {
-            inner_diameter: 0.4,
-            count: 12.0,
             vector: vec![0.2, 0.5, 0.1],
-            some_text: "Hellow world".into(),
-            private_text: "Hellow world".into(),
-            location: InnerSettings { x: 0.8, y: 0.4 },
+            ..Self::default_settings(None)
         }
     }
 }
@@ -0,0 +1,114 @@
+use crate::universe::GameState;
+
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{MessageEvent, WebSocket};
+
+/// Client for the backend relay, collects the frames of a live match.
+pub struct Spectator {
+    socket: WebSocket,
+    latest: Rc<RefCell<Option<String>>>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+}
+
+impl Spectator {
+    pub fn connect(url: &str) -> Result<Self, JsValue> {
+        let socket = WebSocket::new(url)?;
+        let latest = Rc::new(RefCell::new(None));
+
+        let on_message = {
+            let latest = latest.clone();
+            Closure::wrap(Box::new(move |e: MessageEvent| {
+                if let Some(frame) = e.data().as_string() {
+                    // Only the newest state is interesting, older frames are overwritten
+                    *latest.borrow_mut() = Some(frame);
+                }
+            }) as Box<dyn FnMut(MessageEvent)>)
+        };
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        Ok(Self {
+            socket,
+            latest,
+            _on_message: on_message,
+        })
+    }
+
+    /// The newest frame since the last call, if any.
+    pub fn poll(&self) -> Option<GameState> {
+        let frame = self.latest.borrow_mut().take()?;
+
+        match serde_json::from_str(&frame) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                console_log!("Invalid frame {:?}", e);
+                None
+            }
+        }
+    }
+}
+
+impl Drop for Spectator {
+    fn drop(&mut self) {
+        self.socket.set_onmessage(None);
+        let _ = self.socket.close();
+    }
+}
+
+/// A recorded match, as written by `backend match`.
+#[derive(Deserialize, Debug)]
+pub struct Replay {
+    pub players: Vec<String>,
+    /// The classic map it was played on
+    pub map: String,
+    pub winner: Option<usize>,
+    pub turns: Vec<GameState>,
+}
+
+/// Seconds a single turn is shown
+pub const TURN_TIME: f64 = 0.5;
+
+pub struct ReplayPlayer {
+    replay: Replay,
+    turn: Option<usize>,
+    time: f64,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            turn: None,
+            time: 0.0,
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, JsValue> {
+        let replay = serde_json::from_str(text).map_err(|e| JsValue::from(format!("{:?}", e)))?;
+        Ok(Self::new(replay))
+    }
+
+    pub fn map(&self) -> &str {
+        &self.replay.map
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.turn.map(|t| t + 1 >= self.replay.turns.len()).unwrap_or(false)
+    }
+
+    /// The state to show, only when the turn changed.
+    pub fn update(&mut self, dt: f64) -> Option<&GameState> {
+        self.time += dt;
+
+        let turn = ((self.time / TURN_TIME) as usize).min(self.replay.turns.len().checked_sub(1)?);
+        if self.turn == Some(turn) {
+            return None;
+        }
+
+        self.turn = Some(turn);
+        self.replay.turns.get(turn)
+    }
+}
@@ -0,0 +1,212 @@
+//! Fleets in flight: a line along the route they still have to go,
+//! and a ribbon fading out behind them. Rebuilt every frame, planets move.
+//! Routes are `GL::LINES`, which WebGL only guarantees 1px wide for, so they stay thin on purpose.
+//! Anything that needs a width is a ribbon, like the trails.
+
+use cgmath::{InnerSpace, Vector3};
+use std::collections::HashMap;
+use wasm_bindgen::JsValue;
+use web_sys::WebGlRenderingContext as GL;
+
+use super::{owner_color, GameState};
+use crate::instances::UniformsHandle;
+use crate::util::fetch;
+use crate::webgl::buffer::{BufferHandle, VertexArray, VertexBuffer, VertexBufferLayout};
+use crate::webgl::render_state::RenderState;
+use crate::webgl::renderer::{DefaultRenderable, Renderer};
+use crate::webgl::shader::Shader;
+
+use super::PLANET_LAYER;
+
+const ROUTE_ALPHA: f32 = 0.4;
+const TRAIL_ALPHA: f32 = 0.9;
+
+/// Where a fleet is between its planets
+#[derive(Debug, Clone)]
+struct Flight {
+    origin: String,
+    destination: String,
+    color: [f32; 3],
+    /// 0 just left, 1 arrived
+    progress: f32,
+}
+
+pub struct Fleets {
+    routes: BufferHandle<Vec<f32>>,
+    route_uniforms: UniformsHandle,
+    trails: BufferHandle<Vec<f32>>,
+    trail_uniforms: UniformsHandle,
+    /// Part of the route the trail covers behind the fleet
+    trail: f32,
+    flights: Vec<Flight>,
+    /// The buffers have something in them
+    drawn: bool,
+}
+
+impl Fleets {
+    pub async fn new(gl: &GL, renderer: &mut Renderer) -> Result<Self, JsValue> {
+        let line_frag = fetch("shaders/line.frag").await?;
+
+        let (routes, route_uniforms) = {
+            let vert_source = fetch("shaders/line.vert").await?;
+            let shader = Shader::factory(line_frag.clone(), vert_source)
+                .create_shader(gl, HashMap::new())
+                .ok_or("Failed to create route shader")?;
+
+            let mut layout = VertexBufferLayout::new();
+            layout.push(GL::FLOAT, 3, 4, "a_position", false);
+            layout.push(GL::FLOAT, 4, 4, "a_color", false);
+
+            create_renderable(gl, renderer, shader, layout, GL::LINES).ok_or("Failed to create route renderable")?
+        };
+
+        let (trails, trail_uniforms) = {
+            let vert_source = fetch("shaders/ribbon.vert").await?;
+            let shader = Shader::factory(line_frag, vert_source)
+                .create_shader(gl, HashMap::new())
+                .ok_or("Failed to create trail shader")?;
+
+            let mut layout = VertexBufferLayout::new();
+            layout.push(GL::FLOAT, 3, 4, "a_position", false);
+            layout.push(GL::FLOAT, 3, 4, "a_direction", false);
+            layout.push(GL::FLOAT, 1, 4, "a_side", false);
+            layout.push(GL::FLOAT, 4, 4, "a_color", false);
+
+            create_renderable(gl, renderer, shader, layout, GL::TRIANGLES).ok_or("Failed to create trail renderable")?
+        };
+
+        let fleets = Self {
+            routes,
+            route_uniforms,
+            trails,
+            trail_uniforms,
+            trail: 0.3,
+            flights: Vec::new(),
+            drawn: false,
+        };
+        fleets.set_width(2.0);
+
+        Ok(fleets)
+    }
+
+    /// Of the trails, in world units
+    pub fn set_width(&self, width: f32) {
+        self.trail_uniforms.single("u_width", width.max(0.0));
+    }
+
+    /// How much of the route the trail covers, 1 is all the way back to the origin
+    pub fn set_trail(&mut self, trail: f32) {
+        self.trail = trail.clamp(0.0, 1.0);
+    }
+
+    /// Trips take as many turns as the planets are apart in the state
+    pub fn set_state(&mut self, state: &GameState) {
+        let planets: HashMap<&str, (f32, f32)> =
+            state.planets.iter().map(|p| (p.name.as_str(), (p.x, p.y))).collect();
+
+        self.flights = state
+            .expeditions
+            .iter()
+            .map(|e| {
+                let turns = match (planets.get(e.origin.as_str()), planets.get(e.destination.as_str())) {
+                    (Some((x1, y1)), Some((x2, y2))) => ((x2 - x1).hypot(y2 - y1)).ceil(),
+                    _ => 0.0,
+                };
+                let progress = if turns > 0.0 {
+                    1.0 - e.turns_remaining as f32 / turns
+                } else {
+                    1.0
+                };
+
+                Flight {
+                    origin: e.origin.clone(),
+                    destination: e.destination.clone(),
+                    color: owner_color(Some(e.owner)),
+                    progress: progress.clamp(0.0, 1.0),
+                }
+            })
+            .collect();
+    }
+
+    /// `position` finds planets by name, fleets to or from planets it can't find aren't drawn
+    pub fn update<F: Fn(&str) -> Option<Vector3<f32>>>(&mut self, position: F) {
+        if self.flights.is_empty() && !self.drawn {
+            return;
+        }
+
+        let mut routes = Vec::new();
+        let mut trails = Vec::new();
+        // Sums of the midpoints, both are sorted with the other transparent things by their average
+        let mut route_center = Vector3::new(0.0, 0.0, 0.0);
+        let mut trail_center = Vector3::new(0.0, 0.0, 0.0);
+        let mut count = 0.0;
+
+        for flight in &self.flights {
+            let (origin, destination) = match (position(&flight.origin), position(&flight.destination)) {
+                (Some(origin), Some(destination)) => (origin, destination),
+                _ => continue,
+            };
+
+            let direction = destination - origin;
+            if direction.magnitude2() == 0.0 {
+                continue;
+            }
+
+            let [r, g, b] = flight.color;
+            let ship = origin + direction * flight.progress;
+            let tail = origin + direction * (flight.progress - self.trail).max(0.0);
+
+            for point in &[ship, destination] {
+                routes.extend_from_slice(&[point.x, point.y, point.z, r, g, b, ROUTE_ALPHA]);
+            }
+            route_center += (ship + destination) * 0.5;
+            trail_center += (tail + ship) * 0.5;
+            count += 1.0;
+
+            // Two triangles, it fades out towards the tail
+            let mut push = |point: Vector3<f32>, side: f32, alpha: f32| {
+                trails.extend_from_slice(&[point.x, point.y, point.z]);
+                trails.extend_from_slice(&[direction.x, direction.y, direction.z]);
+                trails.extend_from_slice(&[side, r, g, b, alpha]);
+            };
+            push(tail, -1.0, 0.0);
+            push(ship, 1.0, TRAIL_ALPHA);
+            push(tail, 1.0, 0.0);
+            push(tail, -1.0, 0.0);
+            push(ship, -1.0, TRAIL_ALPHA);
+            push(ship, 1.0, TRAIL_ALPHA);
+        }
+
+        if count > 0.0 {
+            self.route_uniforms.center(route_center / count);
+            self.trail_uniforms.center(trail_center / count);
+        }
+
+        self.drawn = !routes.is_empty();
+        self.routes.reset(routes);
+        self.trails.reset(trails);
+    }
+}
+
+/// A see-through renderable drawing `primitive`s from a single buffer, with a handle to fill it
+fn create_renderable(
+    gl: &GL,
+    renderer: &mut Renderer,
+    shader: Shader,
+    layout: VertexBufferLayout,
+    primitive: u32,
+) -> Option<(BufferHandle<Vec<f32>>, UniformsHandle)> {
+    let buffer = VertexBuffer::vertex_buffer(gl, Vec::new())?;
+    let handle = buffer.handle();
+
+    let mut vao = VertexArray::new();
+    vao.add_buffer(buffer, layout);
+
+    let renderable = DefaultRenderable::new(None, vao, shader)
+        .with_primitive(primitive)
+        .with_render_state(RenderState::transparent());
+    let uniforms = renderable.handle();
+    renderer.add_renderable(renderable, PLANET_LAYER);
+
+    Some((handle, uniforms))
+}
@@ -0,0 +1,311 @@
+//! Undo and redo for edits to the planets.
+//! Every edit keeps what it replaced, so it can be put back exactly.
+
+use std::collections::VecDeque;
+
+use pw_settings::{parse_path, Access, PathSegment, SettingsError, SettingsTrait, Value};
+
+use super::Planets;
+
+/// Older edits are forgotten
+const MAX_HISTORY: usize = 100;
+
+/// Edits to the same field closer together than this are one step, like a slider drag.
+const GROUP_MS: f64 = 500.0;
+
+/// How an edit is taken back
+#[derive(Debug, Clone)]
+enum Undo {
+    /// Write `before` back at `path`, which can be above the edited field.
+    /// It may have been hidden or disabled since, so it goes back in as a restore.
+    Value { path: Vec<PathSegment>, before: Value },
+    /// Array operations can't be taken back by writing one value, so they keep a copy.
+    Snapshot(Box<Planets>),
+}
+
+impl Undo {
+    fn apply(&self, planets: &mut Planets) -> Result<(), SettingsError> {
+        match self {
+            Undo::Value { path, before } => {
+                planets.apply_field_as(path, before.clone(), &Default::default(), Access::Restore)
+            }
+            Undo::Snapshot(snapshot) => {
+                *planets = (**snapshot).clone();
+                Ok(())
+            }
+        }
+    }
+}
+
+/// One step, made by `History::apply`
+#[derive(Debug, Clone)]
+pub struct Edit {
+    path: Vec<PathSegment>,
+    value: Value,
+    undo: Undo,
+    time: f64,
+}
+
+impl Edit {
+    /// Only plain values are merged, array operations are steps of their own
+    fn continues(&self, path: &[PathSegment], value: &Value, time: f64) -> bool {
+        self.path == path && !is_compound(value) && time - self.time < GROUP_MS
+    }
+}
+
+fn is_compound(value: &Value) -> bool {
+    value.is_object() || value.is_array()
+}
+
+fn undo_for(planets: &Planets, path: &[PathSegment], value: &Value) -> Undo {
+    if is_compound(value) {
+        return Undo::Snapshot(Box::new(planets.clone()));
+    }
+
+    let path = match path.split_last() {
+        // Switching back to a variant starts from its defaults, so keep the whole thing
+        Some((PathSegment::Field(name), parent)) if name == "variant" => parent.to_vec(),
+        _ => path.to_vec(),
+    };
+    let before = planets.to_settings(None).value_at(&path).unwrap_or(Value::Null);
+
+    Undo::Value { path, before }
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    sealed: bool,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply an edit to `planets`, the returned `Edit` is recorded with `push` once it's committed.
+    /// `time` is in milliseconds, it's only used for grouping.
+    pub fn apply(&self, planets: &mut Planets, path: &str, value: Value, time: f64) -> Result<Edit, SettingsError> {
+        let path = parse_path(path)?;
+        let undo = undo_for(planets, &path, &value);
+
+        planets.apply_field_with(&path, value.clone(), &Default::default())?;
+        Ok(Edit { path, value, undo, time })
+    }
+
+    pub fn push(&mut self, edit: Edit) {
+        self.redo.clear();
+
+        if let (false, Some(last)) = (self.sealed, self.undo.back_mut()) {
+            if last.continues(&edit.path, &edit.value, edit.time) {
+                last.value = edit.value;
+                last.time = edit.time;
+                return;
+            }
+        }
+
+        self.sealed = false;
+        self.undo.push_back(edit);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.pop_front();
+        }
+    }
+
+    /// The next edit starts a new step, even when it is to the same field.
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    /// Returns `false` when there is nothing to undo
+    pub fn undo(&mut self, planets: &mut Planets) -> Result<bool, SettingsError> {
+        let edit = match self.undo.pop_back() {
+            Some(edit) => edit,
+            None => return Ok(false),
+        };
+
+        if let Err(e) = edit.undo.apply(planets) {
+            self.undo.push_back(edit);
+            return Err(e);
+        }
+
+        self.sealed = true;
+        self.redo.push(edit);
+        Ok(true)
+    }
+
+    /// Returns `false` when there is nothing to redo
+    pub fn redo(&mut self, planets: &mut Planets) -> Result<bool, SettingsError> {
+        let edit = match self.redo.pop() {
+            Some(edit) => edit,
+            None => return Ok(false),
+        };
+
+        // Already accepted once, the fields it needs are the way they were then
+        if let Err(e) = planets.apply_field_as(&edit.path, edit.value.clone(), &Default::default(), Access::Restore) {
+            self.redo.push(edit);
+            return Err(e);
+        }
+
+        self.sealed = true;
+        self.undo.push_back(edit);
+        Ok(true)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// For when the planets are replaced as a whole, old edits don't apply to the new ones.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::Motion;
+    use serde_json::json;
+
+    /// Apply and record an edit like the settings panel does
+    fn edit(history: &mut History, planets: &mut Planets, path: &str, value: Value, time: f64) {
+        let edit = history.apply(planets, path, value, time).unwrap();
+        history.push(edit);
+    }
+
+    /// A history with one planet in it, inserting it is the first step
+    fn one_planet() -> (History, Planets) {
+        let mut history = History::new();
+        let mut planets = Planets::default();
+        edit(&mut history, &mut planets, "planets", json!({ "insert": { "index": 0 } }), 0.0);
+        (history, planets)
+    }
+
+    fn ships(planets: &Planets) -> u32 {
+        planets.planets[0].ships
+    }
+
+    #[test]
+    fn groups_quick_edits_to_a_field() {
+        let (mut history, mut planets) = one_planet();
+
+        // Each edit is measured from the one before, so a long drag stays one step
+        for (i, time) in [1000.0, 1400.0, 1800.0].iter().enumerate() {
+            edit(&mut history, &mut planets, "planets[0].ships", json!(i + 1), *time);
+        }
+        edit(&mut history, &mut planets, "planets[0].ships", json!(10), 2300.0);
+        // Another field is another step, however quick
+        edit(&mut history, &mut planets, "planets[0].owner", json!(1), 2310.0);
+
+        history.undo(&mut planets).unwrap();
+        assert_eq!(planets.planets[0].owner, 0);
+        history.undo(&mut planets).unwrap();
+        assert_eq!(ships(&planets), 3);
+        history.undo(&mut planets).unwrap();
+        assert_eq!(ships(&planets), 0);
+
+        history.undo(&mut planets).unwrap();
+        assert!(planets.planets.is_empty());
+        assert!(!history.can_undo());
+        assert!(!history.undo(&mut planets).unwrap());
+    }
+
+    #[test]
+    fn sealing_starts_a_new_step() {
+        let (mut history, mut planets) = one_planet();
+
+        edit(&mut history, &mut planets, "planets[0].ships", json!(1), 1000.0);
+        history.seal();
+        edit(&mut history, &mut planets, "planets[0].ships", json!(2), 1010.0);
+        edit(&mut history, &mut planets, "planets[0].ships", json!(3), 1020.0);
+
+        history.undo(&mut planets).unwrap();
+        assert_eq!(ships(&planets), 1);
+
+        // Undo seals too, so the redone edit doesn't swallow the next one
+        history.redo(&mut planets).unwrap();
+        edit(&mut history, &mut planets, "planets[0].ships", json!(4), 1030.0);
+        history.undo(&mut planets).unwrap();
+        assert_eq!(ships(&planets), 3);
+    }
+
+    #[test]
+    fn forgets_old_edits() {
+        let (mut history, mut planets) = one_planet();
+
+        for i in 1..=150 {
+            edit(&mut history, &mut planets, "planets[0].ships", json!(i), i as f64 * 1000.0);
+        }
+
+        let mut undone = 0;
+        while history.undo(&mut planets).unwrap() {
+            undone += 1;
+        }
+        assert_eq!(undone, MAX_HISTORY);
+        // The insert and the first 50 edits are gone
+        assert_eq!(ships(&planets), 50);
+    }
+
+    #[test]
+    fn undoes_array_operations() {
+        let (mut history, mut planets) = one_planet();
+        edit(&mut history, &mut planets, "planets", json!({ "insert": { "index": 1 } }), 1000.0);
+        edit(&mut history, &mut planets, "planets[0].ships", json!(5), 2000.0);
+
+        // Array operations are never grouped, even on the same path right away
+        edit(&mut history, &mut planets, "planets", json!({ "move": { "from": 0, "to": 1 } }), 3000.0);
+        edit(&mut history, &mut planets, "planets", json!({ "remove": { "index": 0 } }), 3010.0);
+        assert_eq!(planets.planets.len(), 1);
+        assert_eq!(ships(&planets), 5);
+
+        history.undo(&mut planets).unwrap();
+        assert_eq!(planets.planets.len(), 2);
+        assert_eq!(planets.planets[1].ships, 5);
+
+        history.undo(&mut planets).unwrap();
+        assert_eq!(ships(&planets), 5);
+
+        history.redo(&mut planets).unwrap();
+        history.redo(&mut planets).unwrap();
+        assert_eq!(planets.planets.len(), 1);
+    }
+
+    #[test]
+    fn undoes_variants_as_a_whole() {
+        let (mut history, mut planets) = one_planet();
+        edit(&mut history, &mut planets, "planets[0].motion.variant", json!("Orbit"), 1000.0);
+        edit(&mut history, &mut planets, "planets[0].motion.distance", json!(20.0), 2000.0);
+        edit(&mut history, &mut planets, "planets[0].motion.variant", json!("Gravity"), 3000.0);
+
+        // Switching back alone would start the orbit from its defaults
+        history.undo(&mut planets).unwrap();
+        match &planets.planets[0].motion {
+            Motion::Orbit { distance, .. } => assert_eq!(*distance, 20.0),
+            motion => panic!("{:?} should be an orbit", motion),
+        }
+
+        history.undo(&mut planets).unwrap();
+        history.undo(&mut planets).unwrap();
+        assert!(matches!(planets.planets[0].motion, Motion::Linear));
+    }
+
+    #[test]
+    fn new_edits_clear_redo() {
+        let (mut history, mut planets) = one_planet();
+        edit(&mut history, &mut planets, "planets[0].ships", json!(1), 1000.0);
+
+        history.undo(&mut planets).unwrap();
+        assert!(history.can_redo());
+
+        edit(&mut history, &mut planets, "planets[0].ships", json!(2), 2000.0);
+        assert!(!history.can_redo());
+        assert!(!history.redo(&mut planets).unwrap());
+        assert_eq!(ships(&planets), 2);
+    }
+}
@@ -0,0 +1,271 @@
+use super::{Planet, Planets};
+use crate::engine::Entity;
+
+use cgmath::Vector3;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Planets are scaled by their growth, a planet without growth gets this size
+pub const PLANET_SIZE: f32 = 5.0;
+
+/// A planet as in the classic Planet Wars map format, owner 0 is neutral.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassicPlanet {
+    pub x: f32,
+    pub y: f32,
+    pub owner: u32,
+    pub ships: u32,
+    pub growth: u32,
+}
+
+/// Classic 2D map, one `P x y owner ships growth` line per planet.
+/// The order of the planets is their game id.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClassicMap {
+    pub planets: Vec<ClassicPlanet>,
+}
+
+impl ClassicMap {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut planets = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let error = || format!("Invalid line {}: '{}'", number + 1, line);
+
+            if parts[0] != "P" || parts.len() != 6 {
+                return Err(error());
+            }
+
+            let float = |i: usize| parts[i].parse::<f32>().map_err(|_| error());
+            let int = |i: usize| parts[i].parse::<u32>().map_err(|_| error());
+
+            planets.push(ClassicPlanet {
+                x: float(1)?,
+                y: float(2)?,
+                owner: int(3)?,
+                ships: int(4)?,
+                growth: int(5)?,
+            });
+        }
+
+        Ok(Self { planets })
+    }
+
+    fn center(&self) -> (f32, f32) {
+        if self.planets.is_empty() {
+            return (0.0, 0.0);
+        }
+
+        let count = self.planets.len() as f32;
+        let x = self.planets.iter().map(|p| p.x).sum::<f32>() / count;
+        let y = self.planets.iter().map(|p| p.y).sum::<f32>() / count;
+        (x, y)
+    }
+}
+
+impl fmt::Display for ClassicMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for p in &self.planets {
+            writeln!(f, "P {} {} {} {} {}", p.x, p.y, p.owner, p.ships, p.growth)?;
+        }
+        Ok(())
+    }
+}
+
+/// How 2D map coordinates become 3D positions.
+/// Map coordinates are centered around the middle of the map and multiplied by `scale` first.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Layout {
+    /// Everything on the xz plane
+    Flat { scale: f32 },
+    /// Like `Flat`, but planets with more growth float higher
+    GrowthHeight { scale: f32, height: f32 },
+    /// Map coordinates are distances on the surface of a sphere.
+    /// Maps have to fit between the poles and can't go around, otherwise planets would overlap.
+    Spherical { scale: f32, radius: f32 },
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::Flat { scale: 20.0 }
+    }
+}
+
+impl Layout {
+    fn scale(&self) -> f32 {
+        match *self {
+            Layout::Flat { scale } => scale,
+            Layout::GrowthHeight { scale, .. } => scale,
+            Layout::Spherical { scale, .. } => scale,
+        }
+    }
+
+    /// Position of a planet with centered map coordinates `x` and `y`
+    pub fn place(&self, x: f32, y: f32, growth: u32) -> Result<Vector3<f32>, String> {
+        let (sx, sy) = (x * self.scale(), y * self.scale());
+
+        match *self {
+            Layout::Flat { .. } => Ok(Vector3::new(sx, 0.0, sy)),
+            Layout::GrowthHeight { height, .. } => Ok(Vector3::new(sx, growth as f32 * height, sy)),
+            Layout::Spherical { radius, .. } => {
+                let longitude = sx / radius;
+                let latitude = sy / radius;
+
+                // Past the poles or halfway around `unplace` can't tell where a planet came from
+                if longitude.abs() >= std::f32::consts::PI || latitude.abs() >= std::f32::consts::FRAC_PI_2 {
+                    return Err(format!(
+                        "{} {} from the center of the map doesn't fit on a sphere of radius {}, \
+                         use a larger radius or a smaller scale",
+                        x, y, radius
+                    ));
+                }
+
+                let (lat_sin, lat_cos) = latitude.sin_cos();
+                let (lon_sin, lon_cos) = longitude.sin_cos();
+                Ok(Vector3::new(
+                    radius * lat_cos * lon_cos,
+                    radius * lat_sin,
+                    radius * lat_cos * lon_sin,
+                ))
+            }
+        }
+    }
+
+    /// Inverse of `place`, centered map coordinates of a position
+    pub fn unplace(&self, position: Vector3<f32>) -> (f32, f32) {
+        let (x, y) = match *self {
+            Layout::Flat { .. } | Layout::GrowthHeight { .. } => (position.x, position.z),
+            Layout::Spherical { radius, .. } => {
+                let longitude = position.z.atan2(position.x);
+                let latitude = (position.y / radius).clamp(-1.0, 1.0).asin();
+                (longitude * radius, latitude * radius)
+            }
+        };
+
+        (x / self.scale(), y / self.scale())
+    }
+}
+
+pub fn planet_name(index: usize) -> String {
+    format!("planet {}", index)
+}
+
+impl Planets {
+    /// Planets in map order, so game ids line up with `Universe` object indices.
+    /// Fails when the map doesn't fit the layout.
+    pub fn from_classic(map: &ClassicMap, layout: Layout) -> Result<Self, String> {
+        let (cx, cy) = map.center();
+
+        let planets = map
+            .planets
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let location = Entity::default()
+                    .with_position(layout.place(p.x - cx, p.y - cy, p.growth)?)
+                    .with_hom_scale(PLANET_SIZE * (1.0 + p.growth as f32).sqrt());
+
+                let mut planet = Planet::new(planet_name(i), location);
+                planet.owner = p.owner;
+                planet.ships = p.ships;
+                planet.growth = p.growth;
+                Ok(planet)
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self {
+            planets,
+            map_center: [cx, cy],
+        })
+    }
+
+    /// Flatten back to a classic map, keeping the planet order.
+    pub fn to_classic(&self, layout: Layout) -> ClassicMap {
+        let [cx, cy] = self.map_center;
+        let planets = self
+            .planets
+            .iter()
+            .map(|p| {
+                let (x, y) = layout.unplace(p.location.position().into());
+                ClassicPlanet {
+                    x: x + cx,
+                    y: y + cy,
+                    owner: p.owner,
+                    ships: p.ships,
+                    growth: p.growth,
+                }
+            })
+            .collect();
+
+        ClassicMap { planets }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = "P 11.5 3 1 100 5\nP 20 9.25 2 100 5\nP 15.75 6 0 10 2\nP 14 14 0 30 0\n";
+
+    fn assert_round_trip(layout: Layout) {
+        let map = ClassicMap::parse(MAP).unwrap();
+        let planets = Planets::from_classic(&map, layout).unwrap();
+        let back = ClassicMap::parse(&planets.to_classic(layout).to_string()).unwrap();
+
+        assert_eq!(map.planets.len(), back.planets.len());
+        for (a, b) in map.planets.iter().zip(&back.planets) {
+            assert!((a.x - b.x).abs() < 1e-3 && (a.y - b.y).abs() < 1e-3, "{:?} became {:?}", a, b);
+            assert_eq!((a.owner, a.ships, a.growth), (b.owner, b.ships, b.growth));
+        }
+    }
+
+    #[test]
+    fn round_trips_flat() {
+        assert_round_trip(Layout::Flat { scale: 20.0 });
+    }
+
+    #[test]
+    fn round_trips_growth_height() {
+        assert_round_trip(Layout::GrowthHeight { scale: 20.0, height: 10.0 });
+    }
+
+    #[test]
+    fn round_trips_spherical() {
+        assert_round_trip(Layout::Spherical { scale: 20.0, radius: 300.0 });
+    }
+
+    #[test]
+    fn rejects_maps_off_the_sphere() {
+        // 100 wide and 10 tall, going around once takes a radius of 100 * 20 / 2π
+        let map = ClassicMap::parse("P 0 0 1 100 5\nP 100 10 2 100 5\nP 50 5 0 10 2\n").unwrap();
+        let fits = |radius| Planets::from_classic(&map, Layout::Spherical { scale: 20.0, radius }).is_ok();
+
+        assert!(!fits(300.0));
+        assert!(fits(1000.0 / std::f32::consts::PI + 1.0));
+
+        // Too tall only, the poles are a quarter of the way around
+        let tall = ClassicMap::parse("P 0 0 1 100 5\nP 0 100 2 100 5\n").unwrap();
+        assert!(Planets::from_classic(&tall, Layout::Spherical { scale: 20.0, radius: 600.0 }).is_err());
+        assert!(Planets::from_classic(&tall, Layout::Spherical { scale: 20.0, radius: 700.0 }).is_ok());
+    }
+
+    #[test]
+    fn round_trips_wide_spherical() {
+        let map = "P 0 0 1 100 5\nP 100 10 2 100 5\nP 50 5 0 10 2\nP 99 -3 0 30 0\n";
+        let map = ClassicMap::parse(map).unwrap();
+        // 62 map units either side of the center, almost all the way around
+        let layout = Layout::Spherical { scale: 20.0, radius: 420.0 };
+
+        let back = Planets::from_classic(&map, layout).unwrap().to_classic(layout);
+        for (a, b) in map.planets.iter().zip(&back.planets) {
+            assert!((a.x - b.x).abs() < 1e-2 && (a.y - b.y).abs() < 1e-2, "{:?} became {:?}", a, b);
+        }
+    }
+}
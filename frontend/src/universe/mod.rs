@@ -1,37 +1,70 @@
 mod planet;
-use crate::engine::Camera;
-use crate::engine::{Object, ObjectConfig, ObjectFactory};
-use crate::models::gen_sphere_faces;
-use crate::uniform::Uniform3f;
-use crate::webgl::renderer::{BatchRenderable, BatchRenderableHandle};
+mod state;
+pub use state::*;
+mod map;
+pub use map::*;
+mod history;
+pub use history::History;
+mod fleets;
+pub use fleets::Fleets;
+use crate::engine::{Entity, Lights, NodeId, ObjectConfig, ObjectFactory, Scene};
+use crate::physics::Simulation;
+use crate::models::{gen_circle, gen_sphere_faces};
+use crate::webgl::buffer::{VertexArray, VertexBuffer, VertexBufferLayout};
+use crate::webgl::render_state::RenderState;
+use crate::webgl::renderer::{BatchRenderable, BatchRenderableHandle, DefaultRenderable};
+use crate::webgl::shader::ShaderFactory;
 
 use crate::util::*;
-use crate::uniform::UniformsHandle;
+use crate::instances::UniformsHandle;
 use crate::{shader::Shader, webgl::renderer::Renderer};
-pub use planet::Planet;
+pub use planet::{Planet, Ring, Sun};
 use pw_derive::Settings;
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::JsValue;
 
-use cgmath::Vector3;
+use cgmath::{Vector3, Vector4};
 
 use serde_json;
+use std::collections::HashMap;
 use web_sys::WebGlRenderingContext as GL;
 
-#[derive(Debug, Clone, Settings, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Settings, Serialize, Deserialize)]
 pub struct Planets {
     planets: Vec<Planet>,
+    /// Middle of the classic map these came from, imports are centered around it
+    #[settings(skip)]
+    #[serde(default)]
+    map_center: [f32; 2],
 }
 
-impl Default for Planets {
-    fn default() -> Self {
-        Self {
-            planets: Vec::new(),
-        }
+impl Planets {
+    /// The order of the planets is their game id, and their index in `Universe`.
+    pub fn planets(&self) -> &[Planet] {
+        &self.planets
     }
 }
 
+pub const UNIVERSE_DIR: &str = "universes";
+pub const DEFAULT_UNIVERSE: &str = "universes/universe.json";
+
 impl Planets {
+    /// Universes the backend knows about.
+    /// Static hosts can't list directories, so this can be empty.
+    pub async fn available() -> Vec<String> {
+        match list_dir(UNIVERSE_DIR).await {
+            Ok(entries) => entries
+                .into_iter()
+                .filter(|e| !e.dir && e.name.ends_with(".json"))
+                .map(|e| format!("{}/{}", UNIVERSE_DIR, e.name))
+                .collect(),
+            Err(e) => {
+                console_log!("Listing universes failed {:?}", e);
+                Vec::new()
+            }
+        }
+    }
+
     pub async fn load(location: &str) -> Self {
         let ms = fetch(location).await;
         match ms.and_then(|s| {
@@ -53,39 +86,72 @@ impl Planets {
 pub struct Universe {
     last_clicked: Vec<usize>,
     uniforms: Vec<UniformsHandle>,
-    objects: Vec<Object>,
+    colors: Vec<[f32; 3]>,
+    /// All planets hang under this node
+    root: NodeId,
+    /// One per planet
+    nodes: Vec<NodeId>,
+    /// One per planet, under its planet's node
+    rings: Vec<NodeId>,
+    suns: Vec<Sun>,
+    /// Planet names, fleets fly between them
+    names: Vec<String>,
+    /// Created by `init`
+    fleets: Option<Fleets>,
+    simulation: Simulation,
     planet_factory: BatchRenderableHandle,
+    ring_factory: BatchRenderableHandle,
 }
 
 pub const PLANET_LAYER: usize = 0;
 
+/// Inner diameter of the ring mesh, `circle.vert` stretches it to the ring's own
+const RING_MESH_INNER: f32 = 0.5;
+const RING_DIAMONDS: usize = 64;
+
+fn create_ring_renderable(gl: &GL, shader_factory: &ShaderFactory) -> Option<DefaultRenderable> {
+    let shader = shader_factory.create_shader(gl, HashMap::new())?;
+    let vertex_buffer = VertexBuffer::vertex_buffer(gl, gen_circle(RING_MESH_INNER, RING_DIAMONDS))?;
+
+    let mut layout = VertexBufferLayout::new();
+    layout.push(GL::FLOAT, 3, 4, "a_position", false);
+    layout.push(GL::FLOAT, 3, 4, "a_color", false);
+
+    let mut vao = VertexArray::new();
+    vao.add_buffer(vertex_buffer, layout);
+
+    Some(DefaultRenderable::new(None, vao, shader).with_render_state(RenderState::transparent()))
+}
+
 impl Universe {
     /// Creates a non functional Universe, like the real one.
     /// Call and wait for `Universe::init` before use!
-    pub fn place_holder() -> Self {
+    pub fn place_holder(scene: &mut Scene) -> Self {
         Self {
             last_clicked: Vec::new(),
             uniforms: Vec::new(),
-            objects: Vec::new(),
+            colors: Vec::new(),
+            root: scene.add(None, Entity::default(), None).unwrap(),
+            nodes: Vec::new(),
+            rings: Vec::new(),
+            suns: Vec::new(),
+            names: Vec::new(),
+            fleets: None,
+            simulation: Simulation::default(),
             planet_factory: BatchRenderableHandle::place_holder(),
+            ring_factory: BatchRenderableHandle::place_holder(),
         }
     }
 
-    pub fn handle_click(&mut self, origin: Vector3<f32>, direction: Vector3<f32>) {
+    pub fn handle_click(&mut self, scene: &Scene, origin: Vector3<f32>, direction: Vector3<f32>) {
         for i in self.last_clicked.drain(..) {
-            self.uniforms[i].single(
-                "u_color",
-                Uniform3f::new(1.0, 1.0, 1.0),
-            );
+            self.uniforms[i].single("u_color", self.colors[i]);
         }
 
-        for (i, (o, u)) in self.objects.iter().zip(&self.uniforms).enumerate() {
-            if o.click_hit(origin, direction) {
+        for (i, (node, u)) in self.nodes.iter().zip(&self.uniforms).enumerate() {
+            if scene.is_enabled(*node) == Some(true) && scene.click_hit(*node, origin, direction) {
                 self.last_clicked.push(i);
-                u.single(
-                    "u_color",
-                    Uniform3f::new(1.0, 0.0, 1.0),
-                );
+                u.single("u_color", [1.0, 0.0, 1.0]);
             }
         }
     }
@@ -94,6 +160,7 @@ impl Universe {
         &mut self,
         gl: &GL,
         renderer: &mut Renderer,
+        scene: &mut Scene,
         location: &str,
     ) -> Result<Planets, JsValue> {
         self.planet_factory = {
@@ -110,57 +177,203 @@ impl Universe {
 
             let ship_renderable = BatchRenderable::new(renderable);
             let handle = ship_renderable.handle();
-            renderer.add_renderable(ship_renderable, 0);
+            renderer.add_renderable(ship_renderable, PLANET_LAYER);
             handle
         };
 
+        self.ring_factory = {
+            let vert_source = fetch("shaders/circle.vert").await?;
+            let frag_source = fetch("shaders/circle.frag").await?;
+            let shader_factory = Shader::factory(frag_source, vert_source);
+
+            let renderable = create_ring_renderable(gl, &shader_factory)
+                .ok_or("Failed to create ring renderable")?;
+
+            let ring_renderable = BatchRenderable::new(renderable);
+            let handle = ring_renderable.handle();
+            renderer.add_renderable(ring_renderable, PLANET_LAYER);
+            handle
+        };
+
+        self.fleets = Some(Fleets::new(gl, renderer).await?);
+
         let planets = Planets::load(location).await;
-        self.set_planets(&planets)?;
+        self.set_planets(scene, &planets)?;
 
         Ok(planets)
     }
 
-    pub fn set_planets(&mut self, planets: &Planets) -> Result<(), JsValue> {
-        // Set visable or not, or create new Objects
-        for planet in &planets.planets[self.objects.len()..] {
+    pub fn set_planets(&mut self, scene: &mut Scene, planets: &Planets) -> Result<(), JsValue> {
+        // Create nodes for new planets
+        for planet in &planets.planets[self.nodes.len()..] {
             let handle = self
                 .planet_factory
                 .push()
                 .ok_or("Couldn't push hard enough")?;
-            handle.single(
-                "u_reverseLightDirection",
-                Uniform3f::new(0.28735632183908044, 0.4022988505747126, 0.5747126436781609),
-            );
-            handle.single(
-                "u_color",
-                Uniform3f::new(1.0, 1.0, 1.0),
-            );
-            let obj = Object::new(handle, planet.location.clone());
-            self.uniforms.push(obj.uniform_handle());
-            self.objects.push(obj);
+            let node = scene
+                .add(Some(self.root), planet.location.clone(), Some(handle.clone()))
+                .ok_or("Universe root is gone")?;
+            let ring_handle = self
+                .ring_factory
+                .push()
+                .ok_or("Couldn't push hard enough")?;
+            let ring = scene
+                .add(Some(node), planet.ring.entity(), Some(ring_handle))
+                .ok_or("Planet node is gone")?;
+
+            self.uniforms.push(handle);
+            self.colors.push(planet.color.0);
+            self.nodes.push(node);
+            self.rings.push(ring);
         }
 
+        // Planets that were removed take their nodes with them
+        let count = planets.planets.len();
+        for node in self.nodes.drain(count.min(self.nodes.len())..) {
+            scene.remove(node);
+        }
+        // Rings went with their planet's node
+        self.rings.truncate(count);
+        self.suns = planets.planets.iter().map(|p| p.sun.clone()).collect();
+        self.names = planets.planets.iter().map(|p| p.name.clone()).collect();
+        self.uniforms.truncate(count);
+        self.colors.truncate(count);
+        self.last_clicked.retain(|i| *i < count);
+
         // Set new planet's entities
-        for (planet, object) in planets.planets.iter().zip(&mut self.objects) {
-            if planet.disabled {
-                object.disable();
-            } else {
-                object.enable();
+        for (i, ((planet, node), ring)) in planets.planets.iter().zip(&self.nodes).zip(&self.rings).enumerate() {
+            scene.set_enabled(*node, !planet.disabled);
+            scene.set_entity(*node, planet.location.clone());
+            self.uniforms[i].batch(planet.material().uniforms());
+
+            scene.set_enabled(*ring, planet.ring.enabled);
+            scene.set_entity(*ring, planet.ring.entity());
+            if let Some(uniforms) = scene.uniforms(*ring) {
+                uniforms.single("u_color", planet.ring.color.0);
+                uniforms.single("u_alpha", planet.ring.alpha);
+                uniforms.single("u_inner", planet.ring.inner_ratio());
             }
 
-            object.set_entity(planet.location.clone());
+            self.colors[i] = planet.color.0;
+            if !self.last_clicked.contains(&i) {
+                self.uniforms[i].single("u_color", planet.color.0);
+            }
         }
 
-        for object in &self.objects[planets.planets.len()..] {
-            object.disable();
-        }
+        // Starts over from the edited planets
+        self.simulation = Simulation::new(&planets.planets);
 
         Ok(())
     }
 
-    pub fn update(&mut self, dt: f64, camera: &Camera) {
-        self.objects
-            .iter_mut()
-            .for_each(|o| o.update(dt as f32, camera));
+    /// Color planets by their owner, planets are matched by index.
+    pub fn apply_state(&mut self, state: &GameState) {
+        for (i, planet) in state.planets.iter().enumerate().take(self.nodes.len()) {
+            let color = owner_color(planet.owner);
+            self.colors[i] = color;
+
+            if !self.last_clicked.contains(&i) {
+                self.uniforms[i].single("u_color", color);
+            }
+        }
+
+        if let Some(fleets) = &mut self.fleets {
+            fleets.set_state(state);
+        }
+    }
+
+    pub fn fleets_mut(&mut self) -> Option<&mut Fleets> {
+        self.fleets.as_mut()
+    }
+
+    /// Fleets follow their planets, call it every frame
+    pub fn update_fleets(&mut self, scene: &Scene) {
+        let (names, nodes) = (&self.names, &self.nodes);
+        let position = |name: &str| {
+            let i = names.iter().position(|n| n == name)?;
+            let world = scene.world_matrix(*nodes.get(i)?)?;
+            Some((world * Vector4::new(0.0, 0.0, 0.0, 1.0)).truncate())
+        };
+
+        if let Some(fleets) = &mut self.fleets {
+            fleets.update(position);
+        }
+    }
+
+    /// Shining suns become point lights where they are now
+    pub fn add_lights(&self, scene: &Scene, lights: &mut Lights) {
+        for (node, sun) in self.nodes.iter().zip(&self.suns) {
+            if !sun.enabled || scene.is_enabled(*node) != Some(true) {
+                continue;
+            }
+
+            if let Some(world) = scene.world_matrix(*node) {
+                let position = (world * Vector4::new(0.0, 0.0, 0.0, 1.0)).truncate();
+                lights.push(sun.light(position));
+            }
+        }
+    }
+
+    /// One fixed tick of `dt` seconds, after `Scene::tick`. Planets go where the simulation puts them.
+    pub fn tick(&mut self, scene: &mut Scene, dt: f64) {
+        self.simulation.tick(dt);
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            if let (Some(entity), Some(position)) = (scene.entity_mut(*node), self.simulation.position(i)) {
+                entity.set_position(position);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pw_settings::{validate, Preset, SettingsTrait, Value};
+
+    #[test]
+    fn new_planets_are_valid() {
+        let mut planets = Planets::default();
+        planets
+            .apply_field("planets", serde_json::json!({ "insert": { "index": 0 } }), None)
+            .unwrap();
+
+        let planet = &planets.planets[0];
+        assert!(planet.location.scale().max() > 0.0);
+        assert_eq!(planet.sun.range, Sun::default().range);
+        assert_eq!(planet.ring.alpha, Ring::default().alpha);
+
+        let value: Value = serde_json::to_value(&planets).unwrap();
+        validate(&Planets::json_schema(None), &value).unwrap();
+    }
+    #[test]
+    fn neutral_and_disabled_planets_refuse_edits() {
+        let mut planets = Planets::default();
+        planets
+            .apply_field("planets", serde_json::json!({ "insert": { "index": 0 } }), None)
+            .unwrap();
+
+        assert!(planets.apply_field("planets[0].growth", Value::from(2), None).is_err());
+        planets.apply_field("planets[0].owner", Value::from(1), None).unwrap();
+        planets.apply_field("planets[0].growth", Value::from(2), None).unwrap();
+
+        planets.apply_field("planets[0].disabled", Value::from(true), None).unwrap();
+        assert!(planets.apply_field("planets[0].location.ang_speed.y", Value::from(10.0), None).is_err());
+        planets.apply_field("planets[0].location.speed.y", Value::from(10.0), None).unwrap();
+    }
+    #[test]
+    fn shipped_schema_is_current() {
+        // Regenerate it with `WebGl.universe_schema()` when this fails
+        let shipped: Value = serde_json::from_str(include_str!("../../static/universe.schema.json")).unwrap();
+        assert_eq!(shipped, Planets::json_schema(None));
+    }
+    #[test]
+    fn presets_round_trip() {
+        let planets: Planets = serde_json::from_str(include_str!("../../static/universes/universe.json")).unwrap();
+
+        let preset = Preset::capture("shipped", &planets, None);
+        let (loaded, errors): (Planets, _) = preset.load(None);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&planets).unwrap());
     }
 }
@@ -1,7 +1,10 @@
+use cgmath::{Deg, Vector3};
 use pw_derive::Settings;
+use pw_settings::Color;
 use serde::{Deserialize, Serialize};
 
-use crate::engine::{Entity, Vec3};
+use crate::engine::{Entity, Light, Material, Vec3};
+use crate::physics::Motion;
 
 const MIN_SIZE: f32 = -1000.0;
 const INC_SIZE: f32 = 0.1;
@@ -9,6 +12,8 @@ const MAX_SIZE: f32 = 1000.0;
 
 #[derive(Debug, Serialize, Deserialize, Settings, Clone)]
 pub struct Planet {
+    /// Game states refer to planets by name, so this is fixed
+    #[settings(read_only)]
     pub name: String,
     #[settings(
         position = [
@@ -30,7 +35,8 @@ pub struct Planet {
             x = [inc = INC_SIZE, min = MIN_SIZE, max = MAX_SIZE, ty=[f32]],
             y = [inc = INC_SIZE, min = MIN_SIZE, max = MAX_SIZE, ty=[f32]],
             z = [inc = INC_SIZE, min = MIN_SIZE, max = MAX_SIZE, ty=[f32]],
-            ty = [Vec3]],
+            ty = [Vec3],
+            enabled_if = "!disabled"],
         scale = [
             x = [inc = INC_SIZE, min = 0.0,      max = MAX_SIZE, ty=[f32]],
             y = [inc = INC_SIZE, min = 0.0,      max = MAX_SIZE, ty=[f32]],
@@ -38,15 +44,154 @@ pub struct Planet {
             ty = [Vec3]]
     )]
     pub location: Entity,
+    /// Straight, on an orbit or with gravity
+    #[serde(default)]
+    pub motion: Motion,
+    /// Disabled planets are not drawn, so their rotation speed can't be edited
     pub disabled: bool,
+
+    /// Base color, game states color planets by owner instead
+    #[serde(default)]
+    pub color: Color,
+    #[serde(default)]
+    #[settings(group = "Look")]
+    pub material: Material,
+    /// Lights up the planets around it
+    #[serde(default)]
+    #[settings(group = "Look")]
+    pub sun: Sun,
+
+    /// Game attributes, like in a classic map. Owner 0 is neutral.
+    #[serde(default)]
+    #[settings(max = 8, group = "Game")]
+    pub owner: u32,
+    /// Ships on the planet when the game starts
+    #[serde(default)]
+    #[settings(max = 1000, group = "Game")]
+    pub ships: u32,
+    /// Only owned planets produce ships
+    #[serde(default)]
+    #[settings(max = 10, unit = "ships/turn", group = "Game", visible_if = "owner != 0")]
+    pub growth: u32,
+
+    /// A flat ring around the equator
+    #[serde(default)]
+    #[settings(group = "Look")]
+    pub ring: Ring,
+}
+
+/// A point light in the middle of the planet, suns glow themselves too
+#[derive(Debug, Serialize, Deserialize, Settings, Clone)]
+#[settings(default)]
+pub struct Sun {
+    pub enabled: bool,
+    #[settings(visible_if = "enabled")]
+    pub color: Color,
+    #[settings(max = 10.0, inc = 0.1, visible_if = "enabled")]
+    pub intensity: f32,
+    /// The light is half as bright this far away
+    #[settings(unit = "units", min = 1.0, max = 5000.0, inc = 1.0, visible_if = "enabled")]
+    pub range: f32,
+}
+
+impl Default for Sun {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: Color([1.0, 0.95, 0.8]),
+            intensity: 1.0,
+            range: 500.0,
+        }
+    }
+}
+
+impl Sun {
+    pub fn light(&self, position: Vector3<f32>) -> Light {
+        let [r, g, b] = self.color.0;
+        let i = self.intensity;
+        Light::Point {
+            position,
+            color: [r * i, g * i, b * i],
+            range: self.range,
+        }
+    }
+}
+
+/// Sizes are in planet radii, so the ring grows with its planet
+#[derive(Debug, Serialize, Deserialize, Settings, Clone)]
+#[settings(default)]
+pub struct Ring {
+    pub enabled: bool,
+    #[settings(min = 1.0, max = 10.0, inc = 0.05, visible_if = "enabled")]
+    pub inner: f32,
+    #[settings(min = 1.0, max = 10.0, inc = 0.05, visible_if = "enabled")]
+    pub outer: f32,
+    /// Away from the equator
+    #[settings(unit = "degrees", min = -90.0, max = 90.0, inc = 0.1, visible_if = "enabled")]
+    pub tilt: f32,
+    #[settings(visible_if = "enabled")]
+    pub color: Color,
+    /// 0 is invisible, 1 is solid
+    #[settings(max = 1.0, inc = 0.01, visible_if = "enabled")]
+    pub alpha: f32,
+}
+
+impl Default for Ring {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            inner: 1.5,
+            outer: 2.5,
+            tilt: 0.0,
+            color: Color([0.8, 0.7, 0.5]),
+            alpha: 0.6,
+        }
+    }
+}
+
+impl Ring {
+    /// Relative to the planet, the ring mesh lies in the xy-plane with radius 1
+    pub fn entity(&self) -> Entity {
+        let outer = self.outer.max(self.inner);
+        Entity::default()
+            .with_rotation::<Deg<f32>>(Vector3::new(90.0 + self.tilt, 0.0, 0.0))
+            .with_hom_scale(outer)
+    }
+
+    /// Inner radius as a part of the outer one
+    pub fn inner_ratio(&self) -> f32 {
+        let outer = self.outer.max(self.inner);
+        if outer > 0.0 {
+            self.inner / outer
+        } else {
+            0.0
+        }
+    }
 }
 
 impl Planet {
+    /// Suns glow, however their material is set
+    pub fn material(&self) -> Material {
+        let mut material = self.material.clone();
+        if self.sun.enabled {
+            material.emissive = material.emissive.max(1.0);
+        }
+        material
+    }
+
     pub fn new<S: Into<String>>(name: S, location: Entity) -> Self {
         Self {
             name: name.into(),
             location,
+            motion: Motion::default(),
             disabled: false,
+            color: Color::default(),
+            material: Material::default(),
+            sun: Sun::default(),
+            owner: 0,
+            ships: 0,
+            growth: 0,
+            ring: Ring::default(),
         }
     }
 }
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+/// A single Planet Wars game state, as sent by a match runner.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameState {
+    pub planets: Vec<PlanetState>,
+    #[serde(default)]
+    pub expeditions: Vec<Expedition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanetState {
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+    pub owner: Option<usize>,
+    pub ship_count: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Expedition {
+    pub id: u32,
+    pub origin: String,
+    pub destination: String,
+    pub owner: usize,
+    pub ship_count: u32,
+    pub turns_remaining: u32,
+}
+
+/// Colors for neutral planets and the different players
+pub const NEUTRAL_COLOR: [f32; 3] = [0.6, 0.6, 0.6];
+pub const OWNER_COLORS: [[f32; 3]; 6] = [
+    [0.9, 0.2, 0.2],
+    [0.2, 0.4, 0.9],
+    [0.2, 0.8, 0.3],
+    [0.9, 0.8, 0.2],
+    [0.7, 0.3, 0.9],
+    [0.2, 0.8, 0.8],
+];
+
+/// Players are numbered from 1.
+pub fn owner_color(owner: Option<usize>) -> [f32; 3] {
+    match owner {
+        Some(owner) if owner > 0 => OWNER_COLORS[(owner - 1) % OWNER_COLORS.len()],
+        _ => NEUTRAL_COLOR,
+    }
+}
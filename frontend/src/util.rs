@@ -1,8 +1,9 @@
+use serde::Deserialize;
 use wasm_bindgen::JsCast;
 use wasm_bindgen::JsValue;
 use wasm_bindgen_futures::JsFuture;
 
-const SHIP_BYTES: &'static [u8] = include_bytes!("../res/ship.obj");
+const SHIP_BYTES: &[u8] = include_bytes!("../res/ship.obj");
 
 pub async fn fetch(url: &str) -> Result<String, JsValue> {
     use web_sys::{Request, RequestInit, RequestMode, Response};
@@ -30,6 +31,41 @@ pub async fn fetch(url: &str) -> Result<String, JsValue> {
     Ok(text)
 }
 
+/// Store `body` on the backend, the error is the reason it was refused.
+pub async fn put(url: &str, body: &str) -> Result<(), JsValue> {
+    use web_sys::{Request, RequestInit, RequestMode, Response};
+
+    let mut opts = RequestInit::new();
+    opts.method("PUT");
+    opts.mode(RequestMode::Cors);
+    opts.body(Some(&JsValue::from_str(body)));
+
+    let request = Request::new_with_str_and_init(url, &opts)?;
+    request.headers().set("Content-Type", "application/json")?;
+
+    let window = web_sys::window().unwrap();
+    let resp: Response = JsFuture::from(window.fetch_with_request(&request)).await?.dyn_into()?;
+
+    if !resp.ok() {
+        let reason = JsFuture::from(resp.text()?).await?;
+        return Err(reason);
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DirEntry {
+    pub name: String,
+    pub dir: bool,
+}
+
+/// List a directory on the backend, this only works for the directories the backend allows.
+pub async fn list_dir(dir: &str) -> Result<Vec<DirEntry>, JsValue> {
+    let text = fetch(&format!("{}/", dir.trim_end_matches('/'))).await?;
+    serde_json::from_str(&text).map_err(|e| JsValue::from(format!("{:?}", e)))
+}
+
 pub async fn load_ship() -> Option<(Vec<[f32; 3]>, Vec<[usize; 3]>)> {
     use std::io::Cursor;
 
@@ -67,24 +103,10 @@ pub async fn load_ship() -> Option<(Vec<[f32; 3]>, Vec<[usize; 3]>)> {
     Some((verts, faces))
 }
 
-pub struct FpsCounter {
-    fps: u32,
-    time: f64,
-}
-
-impl FpsCounter {
-    pub fn new() -> Self {
-        Self { fps: 0, time: 0.0 }
-    }
-
-    pub fn update(&mut self, dt: f64) {
-        self.time += dt;
-        self.fps += 1;
-
-        if self.time > 1.0 {
-            self.time = 0.0;
-            console_log!("Fps {}", self.fps);
-            self.fps = 0;
-        }
-    }
+/// Milliseconds, precise enough to time a single tick
+pub fn now() -> f64 {
+    web_sys::window()
+        .and_then(|w| w.performance())
+        .map(|p| p.now())
+        .unwrap_or_else(js_sys::Date::now)
 }
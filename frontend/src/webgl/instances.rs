@@ -0,0 +1,268 @@
+//! Per instance uniforms, shared by a renderable and the handles to its instances.
+//! Everything runs on the one wasm thread, so handles write straight into the store.
+//! Uniform names become slots once, every frame after that is just an index.
+
+use super::slotmap::{Key, SlotMap};
+use super::uniform::UniformValue;
+use super::Shader;
+
+use cgmath::{InnerSpace, Vector3};
+use std::{cell::RefCell, cmp::Ordering, fmt, marker::PhantomData, rc::Rc};
+use web_sys::{WebGlRenderingContext as GL, WebGlUniformLocation};
+
+/// A uniform of type `T`, only valid for handles of the renderable it came from
+pub struct UniformSlot<T> {
+    index: usize,
+    _type: PhantomData<T>,
+}
+
+impl<T> Clone for UniformSlot<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for UniformSlot<T> {}
+
+impl<T> fmt::Debug for UniformSlot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UniformSlot({})", self.index)
+    }
+}
+
+#[derive(Debug)]
+struct Instance {
+    /// By slot, unset ones are drawn as zero
+    values: Vec<Option<UniformValue>>,
+    disabled: bool,
+    center: Vector3<f32>,
+}
+
+#[derive(Debug, Default)]
+struct Store {
+    /// Slot index to uniform name
+    names: Vec<String>,
+    /// Slot index to a zero of the type it was set with.
+    /// GL keeps uniforms between draws, instances that never set a slot get this instead of the one before.
+    zeros: Vec<Option<UniformValue>>,
+    instances: SlotMap<Instance>,
+    /// Instances were added or removed since the renderable last looked
+    changed: bool,
+}
+
+impl Store {
+    fn slot(&mut self, name: &str) -> usize {
+        match self.names.iter().position(|n| n == name) {
+            Some(index) => index,
+            None => {
+                self.names.push(name.to_string());
+                self.names.len() - 1
+            }
+        }
+    }
+
+    fn set(&mut self, key: Key, index: usize, value: UniformValue) -> Option<()> {
+        let instance = self.instances.get_mut(key)?;
+        if instance.values.len() <= index {
+            instance.values.resize(index + 1, None);
+        }
+
+        if self.zeros.len() <= index {
+            self.zeros.resize(index + 1, None);
+        }
+        if self.zeros[index].is_none() {
+            self.zeros[index] = Some(value.zero());
+        }
+
+        instance.values[index] = Some(value);
+        Some(())
+    }
+}
+
+/// The instances of one renderable
+#[derive(Debug, Clone, Default)]
+pub struct Instances {
+    store: Rc<RefCell<Store>>,
+}
+
+impl Instances {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self) -> UniformsHandle {
+        let mut store = self.store.borrow_mut();
+        store.changed = true;
+        let key = store.instances.insert(Instance {
+            values: Vec::new(),
+            disabled: false,
+            center: Vector3::new(0.0, 0.0, 0.0),
+        });
+
+        UniformsHandle {
+            store: self.store.clone(),
+            key,
+        }
+    }
+
+    pub fn slot<T: Into<UniformValue>>(&self, name: &str) -> UniformSlot<T> {
+        UniformSlot {
+            index: self.store.borrow_mut().slot(name),
+            _type: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.store.borrow().instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.borrow().instances.is_empty()
+    }
+
+    /// New instances since the last call, then `order` is every instance again
+    pub(crate) fn update_order(&self, order: &mut Vec<Key>) {
+        let mut store = self.store.borrow_mut();
+        if store.changed {
+            store.changed = false;
+            order.clear();
+            order.extend(store.instances.keys());
+        }
+    }
+
+    /// Find the locations of slots that were added since the last call
+    pub(crate) fn resolve(&self, gl: &GL, shader: &mut Shader, locations: &mut Vec<Option<WebGlUniformLocation>>) {
+        let store = self.store.borrow();
+        for name in &store.names[locations.len().min(store.names.len())..] {
+            let location = shader.get_uniform_location(gl, name);
+            if location.is_none() {
+                console_log!("Shader has no uniform {}", name);
+            }
+            locations.push(location);
+        }
+    }
+
+    /// Set the uniforms of every shown instance in `order` and `draw` it, the shader has to be bound
+    pub(crate) fn draw<F: FnMut()>(&self, gl: &GL, order: &[Key], locations: &[Option<WebGlUniformLocation>], mut draw: F) {
+        let store = self.store.borrow();
+        for key in order {
+            let instance = match store.instances.get(*key) {
+                Some(instance) if !instance.disabled => instance,
+                _ => continue,
+            };
+
+            for (index, location) in locations.iter().enumerate() {
+                let value = instance.values.get(index).and_then(Option::as_ref);
+                let value = value.or_else(|| store.zeros.get(index)?.as_ref());
+                if let (Some(value), Some(location)) = (value, location) {
+                    value.set(gl, location);
+                }
+            }
+
+            draw();
+        }
+    }
+
+    /// Of the farthest shown instance
+    pub(crate) fn distance2(&self, eye: Vector3<f32>) -> f32 {
+        self.store
+            .borrow()
+            .instances
+            .iter()
+            .filter(|(_, i)| !i.disabled)
+            .map(|(_, i)| (i.center - eye).magnitude2())
+            .fold(0.0, f32::max)
+    }
+
+    /// Farthest from `eye` first
+    pub(crate) fn sort(&self, order: &mut [Key], eye: Vector3<f32>) {
+        let store = self.store.borrow();
+        let distance2 = |key: &Key| {
+            store
+                .instances
+                .get(*key)
+                .map(|i| (i.center - eye).magnitude2())
+                .unwrap_or(0.0)
+        };
+        order.sort_by(|a, b| distance2(b).partial_cmp(&distance2(a)).unwrap_or(Ordering::Equal));
+    }
+}
+
+/// One instance of a renderable. Setters return `None` once it's removed.
+#[derive(Debug, Clone)]
+pub struct UniformsHandle {
+    store: Rc<RefCell<Store>>,
+    key: Key,
+}
+
+impl UniformsHandle {
+    /// Look a name up once, then `set` it every frame
+    pub fn slot<T: Into<UniformValue>>(&self, name: &str) -> UniformSlot<T> {
+        UniformSlot {
+            index: self.store.borrow_mut().slot(name),
+            _type: PhantomData,
+        }
+    }
+
+    pub fn set<T: Into<UniformValue>>(&self, slot: UniformSlot<T>, value: T) -> Option<()> {
+        self.store.borrow_mut().set(self.key, slot.index, value.into())
+    }
+
+    /// Looks the name up every time, fine for things that don't change every frame
+    pub fn single<T: Into<UniformValue>>(&self, name: &str, value: T) -> Option<()> {
+        let mut store = self.store.borrow_mut();
+        let index = store.slot(name);
+        store.set(self.key, index, value.into())
+    }
+
+    pub fn batch<I, S, T>(&self, uniforms: I) -> Option<()>
+    where
+        I: IntoIterator<Item = (S, T)>,
+        S: AsRef<str>,
+        T: Into<UniformValue>,
+    {
+        let mut store = self.store.borrow_mut();
+        for (name, value) in uniforms {
+            let index = store.slot(name.as_ref());
+            store.set(self.key, index, value.into())?;
+        }
+        Some(())
+    }
+
+    pub fn enable(&self) -> Option<()> {
+        self.store.borrow_mut().instances.get_mut(self.key)?.disabled = false;
+        Some(())
+    }
+
+    pub fn disable(&self) -> Option<()> {
+        self.store.borrow_mut().instances.get_mut(self.key)?.disabled = true;
+        Some(())
+    }
+
+    /// Stop drawing it for good, the slot goes to the next instance
+    pub fn remove(&self) -> Option<()> {
+        let mut store = self.store.borrow_mut();
+        store.instances.remove(self.key)?;
+        store.changed = true;
+        Some(())
+    }
+
+    /// Where it is in the world, transparent renderables are sorted by it
+    pub fn center(&self, center: Vector3<f32>) -> Option<()> {
+        self.store.borrow_mut().instances.get_mut(self.key)?.center = center;
+        Some(())
+    }
+
+    /// Not removed or disabled
+    pub fn is_enabled(&self) -> bool {
+        self.store.borrow().instances.get(self.key).map(|i| !i.disabled).unwrap_or(false)
+    }
+
+    pub fn is_removed(&self) -> bool {
+        !self.store.borrow().instances.contains(self.key)
+    }
+
+    pub(crate) fn key(&self) -> Key {
+        self.key
+    }
+}
@@ -6,5 +6,8 @@ use shader::Shader;
 
 pub mod buffer;
 
+pub mod instances;
+pub mod render_state;
 pub mod renderer;
+pub mod slotmap;
 pub mod uniform;
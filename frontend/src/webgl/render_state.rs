@@ -0,0 +1,110 @@
+//! GL state a renderable wants while it's drawn.
+//! The renderer switches between them, so renderables don't have to clean up after themselves.
+
+use web_sys::WebGlRenderingContext as GL;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Overwrites what's behind it
+    None,
+    /// Mixes with what's behind it by its alpha
+    Alpha,
+    /// Adds to what's behind it, for glows
+    Additive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CullMode {
+    /// Both sides are drawn
+    None,
+    Back,
+    Front,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderState {
+    pub blend: BlendMode,
+    pub depth_write: bool,
+    pub cull: CullMode,
+}
+
+impl Default for RenderState {
+    /// What `init_renderer` sets up, solid and back faces culled
+    fn default() -> Self {
+        Self::opaque()
+    }
+}
+
+impl RenderState {
+    pub fn opaque() -> Self {
+        Self {
+            blend: BlendMode::None,
+            depth_write: true,
+            cull: CullMode::Back,
+        }
+    }
+
+    /// See-through from both sides, it doesn't hide what's drawn after it
+    pub fn transparent() -> Self {
+        Self {
+            blend: BlendMode::Alpha,
+            depth_write: false,
+            cull: CullMode::None,
+        }
+    }
+
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn with_depth_write(mut self, depth_write: bool) -> Self {
+        self.depth_write = depth_write;
+        self
+    }
+
+    pub fn with_cull(mut self, cull: CullMode) -> Self {
+        self.cull = cull;
+        self
+    }
+
+    /// Blended renderables are drawn after the solid ones, farthest first
+    pub fn is_transparent(&self) -> bool {
+        self.blend != BlendMode::None
+    }
+
+    /// Only changes what differs from `current`, `None` sets everything
+    pub fn apply(&self, gl: &GL, current: Option<&RenderState>) {
+        if current.map(|c| c.blend) != Some(self.blend) {
+            match self.blend {
+                BlendMode::None => gl.disable(GL::BLEND),
+                BlendMode::Alpha => {
+                    gl.enable(GL::BLEND);
+                    gl.blend_func(GL::SRC_ALPHA, GL::ONE_MINUS_SRC_ALPHA);
+                }
+                BlendMode::Additive => {
+                    gl.enable(GL::BLEND);
+                    gl.blend_func(GL::SRC_ALPHA, GL::ONE);
+                }
+            }
+        }
+
+        if current.map(|c| c.depth_write) != Some(self.depth_write) {
+            gl.depth_mask(self.depth_write);
+        }
+
+        if current.map(|c| c.cull) != Some(self.cull) {
+            match self.cull {
+                CullMode::None => gl.disable(GL::CULL_FACE),
+                CullMode::Back => {
+                    gl.enable(GL::CULL_FACE);
+                    gl.cull_face(GL::BACK);
+                }
+                CullMode::Front => {
+                    gl.enable(GL::CULL_FACE);
+                    gl.cull_face(GL::FRONT);
+                }
+            }
+        }
+    }
+}
@@ -1,23 +1,63 @@
 use super::{
     buffer::{BufferTrait, IndexBuffer, VertexArray},
+    instances::{Instances, UniformsHandle},
+    render_state::RenderState,
+    slotmap::Key,
     uniform::Uniform,
     Shader,
 };
-use crate::uniform::{UniformUpdate, UniformsHandle};
+use cgmath::Vector3;
 use std::{
+    cmp::Ordering,
     collections::{BTreeSet, HashMap},
-    sync::mpsc,
 };
-use web_sys::WebGlRenderingContext as GL;
-
-static SHOW_UNIFORMS: bool = false;
+use web_sys::{WebGlRenderingContext as GL, WebGlUniformLocation};
 
 pub trait Renderable {
-    fn render(&mut self, gl: &GL);
+    fn render(&mut self, gl: &GL, globals: &Globals);
     fn update(&mut self, gl: &GL) -> Option<()>;
     fn is_disabled(&self) -> bool {
         false
     }
+    /// The renderer drops it before the next frame
+    fn is_removed(&self) -> bool {
+        false
+    }
+    fn render_state(&self) -> RenderState {
+        RenderState::opaque()
+    }
+    /// Squared distance to the camera at `eye`, transparent renderables are drawn farthest first
+    fn distance2(&self, _eye: Vector3<f32>) -> f32 {
+        0.0
+    }
+    /// Called before a transparent renderable is drawn, so it can draw its own parts farthest first
+    fn sort(&mut self, _eye: Vector3<f32>) {}
+}
+
+/// Uniforms shared by everything drawn in a frame, like the camera and the lights.
+/// They're set once per shader bind, shaders that don't use one just skip it.
+/// WebGL2 could keep them in a uniform buffer, but this is a WebGL1 context.
+#[derive(Debug, Default)]
+pub struct Globals {
+    uniforms: HashMap<String, Box<dyn Uniform>>,
+}
+
+impl Globals {
+    pub fn set<S: Into<String>, U: Uniform + 'static>(&mut self, name: S, uniform: U) {
+        self.uniforms.insert(name.into(), Box::new(uniform));
+    }
+
+    pub fn extend(&mut self, uniforms: HashMap<String, Box<dyn Uniform>>) {
+        self.uniforms.extend(uniforms);
+    }
+
+    fn apply(&self, gl: &GL, shader: &mut Shader) {
+        for (name, uniform) in self.uniforms.iter() {
+            if let Some(location) = shader.get_uniform_location(gl, name) {
+                uniform.set_uniform(gl, &location);
+            }
+        }
+    }
 }
 
 pub trait BatchRenderableTrait: Renderable {
@@ -26,63 +66,55 @@ pub trait BatchRenderableTrait: Renderable {
     fn shader(&mut self) -> &mut Shader;
 }
 
+/// A single thing on screen, with its own buffers and shader
 pub struct DefaultRenderable {
     ibo: Option<IndexBuffer>,
     vao: VertexArray,
     shader: Shader,
-    uniforms: HashMap<String, Box<dyn Uniform>>,
-
-    disabled: bool,
-
-    tx: mpsc::Sender<UniformUpdate>,
-    rx: mpsc::Receiver<UniformUpdate>,
+    /// `GL::TRIANGLES`, `GL::LINES`, ...
+    primitive: u32,
+
+    state: RenderState,
+    instances: Instances,
+    /// The one instance, every handle is a clone of it
+    instance: UniformsHandle,
+    locations: Vec<Option<WebGlUniformLocation>>,
 }
 
 impl DefaultRenderable {
-    pub fn new<I: Into<Option<IndexBuffer>>, U: Into<Option<HashMap<String, Box<dyn Uniform>>>>>(
-        ibo: I,
-        vao: VertexArray,
-        shader: Shader,
-        uniforms: U,
-    ) -> Self {
-        let (tx, rx) = mpsc::channel();
+    pub fn new<I: Into<Option<IndexBuffer>>>(ibo: I, vao: VertexArray, shader: Shader) -> Self {
+        let instances = Instances::new();
+        let instance = instances.push();
 
         Self {
             ibo: ibo.into(),
             vao,
-            disabled: false,
             shader,
-            uniforms: uniforms.into().unwrap_or(HashMap::new()),
-            tx,
-            rx,
+            primitive: GL::TRIANGLES,
+            state: RenderState::opaque(),
+            instances,
+            instance,
+            locations: Vec::new(),
         }
     }
 
+    pub fn with_render_state(mut self, state: RenderState) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn with_primitive(mut self, primitive: u32) -> Self {
+        self.primitive = primitive;
+        self
+    }
+
     pub fn handle(&self) -> UniformsHandle {
-        UniformsHandle::new(self.tx.clone())
+        self.instance.clone()
     }
 }
 
 impl Renderable for DefaultRenderable {
     fn update(&mut self, gl: &GL) -> Option<()> {
-        loop {
-            match self.rx.try_recv() {
-                Ok(UniformUpdate::Batch(context)) => {
-                    self.uniforms.extend(context.into_iter());
-                }
-                Ok(UniformUpdate::Single(name, uniform)) => {
-                    self.uniforms.insert(name, uniform);
-                }
-                Ok(UniformUpdate::Disable) => {
-                    self.disabled = true;
-                }
-                Ok(UniformUpdate::Enable) => {
-                    self.disabled = false;
-                }
-                Err(mpsc::TryRecvError::Disconnected) => return None,
-                Err(mpsc::TryRecvError::Empty) => break,
-            }
-        }
         if let Some(ibo) = &mut self.ibo {
             ibo.flush(gl)?;
         }
@@ -90,39 +122,47 @@ impl Renderable for DefaultRenderable {
         self.vao.update(gl)?;
         Some(())
     }
-    fn render(&mut self, gl: &GL) {
+    fn render(&mut self, gl: &GL, globals: &Globals) {
         self.vao.bind(gl, &mut self.shader);
+        globals.apply(gl, &mut self.shader);
+        self.instances.resolve(gl, &mut self.shader, &mut self.locations);
 
-        for (name, uniform) in self.uniforms.iter() {
-            if SHOW_UNIFORMS {
-                console_log!("Setting uniform {} {:?}", name, uniform);
-            }
+        let (ibo, vao, primitive) = (&self.ibo, &self.vao, self.primitive);
+        self.instances.draw(gl, &[self.instance.key()], &self.locations, || {
+            if let Some(ibo) = ibo {
+                ibo.bind(gl);
 
-            if self.shader.uniform(gl, &name, &uniform).is_none() {
-                console_log!("Failed etting uniform {} {:?}", name, uniform);
+                gl.draw_elements_with_i32(primitive, ibo.get_count() as i32, GL::UNSIGNED_SHORT, 0);
+            } else {
+                gl.draw_arrays(primitive, 0, vao.get_count())
             }
-        }
+        });
+    }
 
-        if let Some(ibo) = &self.ibo {
-            ibo.bind(gl);
+    fn is_disabled(&self) -> bool {
+        !self.instance.is_enabled()
+    }
 
-            gl.draw_elements_with_i32(GL::TRIANGLES, ibo.get_count() as i32, GL::UNSIGNED_SHORT, 0);
-        } else {
-            gl.draw_arrays(GL::TRIANGLES, 0, self.vao.get_count())
-        }
+    /// Removing its one instance removes all of it
+    fn is_removed(&self) -> bool {
+        self.instance.is_removed()
     }
 
-    fn is_disabled(&self) -> bool {
-        self.disabled
+    fn render_state(&self) -> RenderState {
+        self.state
+    }
+
+    fn distance2(&self, eye: Vector3<f32>) -> f32 {
+        self.instances.distance2(eye)
     }
 }
 
 impl BatchRenderableTrait for DefaultRenderable {
     fn draw(&mut self, gl: &GL) {
         if let Some(ibo) = &self.ibo {
-            gl.draw_elements_with_i32(GL::TRIANGLES, ibo.get_count() as i32, GL::UNSIGNED_SHORT, 0);
+            gl.draw_elements_with_i32(self.primitive, ibo.get_count() as i32, GL::UNSIGNED_SHORT, 0);
         } else {
-            gl.draw_arrays(GL::TRIANGLES, 0, self.vao.get_count())
+            gl.draw_arrays(self.primitive, 0, self.vao.get_count())
         }
     }
     fn bind(&mut self, gl: &GL) -> std::option::Option<()> {
@@ -137,136 +177,100 @@ impl BatchRenderableTrait for DefaultRenderable {
     }
 }
 
-enum BatchRenderableHandleUpdate {
-    Create(mpsc::Sender<UniformUpdate>, mpsc::Receiver<UniformUpdate>),
-}
-
+/// Adds instances to a `BatchRenderable` after it went into the renderer
 #[derive(Clone)]
 pub struct BatchRenderableHandle {
-    inner: mpsc::Sender<BatchRenderableHandleUpdate>,
+    instances: Option<Instances>,
 }
 
 impl BatchRenderableHandle {
+    /// Pushing on it does nothing
     pub fn place_holder() -> Self {
-        let (tx, _) = mpsc::channel();
-        Self { inner: tx }
+        Self { instances: None }
     }
     pub fn push(&self) -> Option<UniformsHandle> {
-        let (tx, rx) = mpsc::channel();
-        self.inner
-            .send(BatchRenderableHandleUpdate::Create(tx.clone(), rx))
-            .ok()?;
-        Some(UniformsHandle::new(tx))
+        self.instances.as_ref().map(|i| i.push())
     }
 }
 
+/// Draws the same mesh once per instance, binding it only once
 pub struct BatchRenderable<R: BatchRenderableTrait> {
     inner: R,
-    uniforms: Vec<(
-        mpsc::Receiver<UniformUpdate>,
-        HashMap<String, Box<dyn Uniform>>,
-    )>,
-    disabled: Vec<bool>,
-    senders: Vec<mpsc::Sender<UniformUpdate>>,
-    handle: (
-        mpsc::Sender<BatchRenderableHandleUpdate>,
-        mpsc::Receiver<BatchRenderableHandleUpdate>,
-    ),
+    instances: Instances,
+    /// By slot, resolved once per name
+    locations: Vec<Option<WebGlUniformLocation>>,
+    /// Instances in drawing order
+    order: Vec<Key>,
 }
 
 impl<R: BatchRenderableTrait> BatchRenderable<R> {
     pub fn new(inner: R) -> Self {
         Self {
             inner,
-            uniforms: Vec::new(),
-            senders: Vec::new(),
-            disabled: Vec::new(),
-            handle: mpsc::channel(),
+            instances: Instances::new(),
+            locations: Vec::new(),
+            order: Vec::new(),
         }
     }
 
     pub fn handle(&self) -> BatchRenderableHandle {
         BatchRenderableHandle {
-            inner: self.handle.0.clone(),
+            instances: Some(self.instances.clone()),
         }
     }
 
     pub fn push(&mut self) -> UniformsHandle {
-        let (tx, rx) = mpsc::channel();
-        self.uniforms.push((rx, HashMap::new()));
-        self.senders.push(tx.clone());
-        self.disabled.push(false);
-        UniformsHandle::new(tx)
+        self.instances.push()
     }
 }
 
 impl<R: BatchRenderableTrait> Renderable for BatchRenderable<R> {
-    fn render(&mut self, gl: &GL) {
-        if self.uniforms.is_empty() {
+    fn render(&mut self, gl: &GL, globals: &Globals) {
+        if self.instances.is_empty() {
             return;
         }
 
         self.inner.bind(gl);
-        for ((_, uniforms), disabled) in self.uniforms.iter_mut().zip(&self.disabled) {
-            if *disabled {
-                continue;
-            }
+        globals.apply(gl, self.inner.shader());
+        self.instances.resolve(gl, self.inner.shader(), &mut self.locations);
 
-            let shader = self.inner.shader();
-            for (name, uniform) in uniforms.iter() {
-                if shader.uniform(gl, &name, &uniform).is_none() {
-                    console_log!("Failed etting uniform {} {:?}", name, uniform);
-                }
-            }
-
-            self.inner.draw(gl);
-        }
+        let inner = &mut self.inner;
+        self.instances.draw(gl, &self.order, &self.locations, || inner.draw(gl));
     }
     fn update(&mut self, gl: &GL) -> Option<()> {
-        loop {
-            match self.handle.1.try_recv() {
-                Ok(BatchRenderableHandleUpdate::Create(tx, rx)) => {
-                    self.uniforms.push((rx, HashMap::new()));
-                    self.senders.push(tx.clone());
-                    self.disabled.push(false);
-                }
-                Err(mpsc::TryRecvError::Disconnected) => return None,
-                Err(mpsc::TryRecvError::Empty) => break,
-            }
-        }
-        self.inner.update(gl)?;
-
-        for ((rx, uniforms), ref mut disabled) in self.uniforms.iter_mut().zip(&mut self.disabled) {
-            loop {
-                match rx.try_recv() {
-                    Ok(UniformUpdate::Batch(context)) => {
-                        uniforms.extend(context.into_iter());
-                    }
-                    Ok(UniformUpdate::Single(name, uniform)) => {
-                        uniforms.insert(name, uniform);
-                    }
-                    Ok(UniformUpdate::Disable) => {
-                        **disabled = true;
-                    }
-                    Ok(UniformUpdate::Enable) => {
-                        **disabled = false;
-                    }
-                    Err(mpsc::TryRecvError::Disconnected) => return None,
-                    Err(mpsc::TryRecvError::Empty) => break,
-                }
-            }
-        }
-
-        Some(())
+        self.instances.update_order(&mut self.order);
+        self.inner.update(gl)
     }
     fn is_disabled(&self) -> bool {
         false
     }
+    fn render_state(&self) -> RenderState {
+        self.inner.render_state()
+    }
+    /// The farthest instance that's shown
+    fn distance2(&self, eye: Vector3<f32>) -> f32 {
+        self.instances.distance2(eye)
+    }
+    fn sort(&mut self, eye: Vector3<f32>) {
+        self.instances.sort(&mut self.order, eye);
+    }
+}
+
+/// A renderable in a `Renderer`, for taking it out again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RenderableId {
+    layer: usize,
+    id: usize,
 }
 
+/// Renderables in drawing order, with their ids
+type Layer = Vec<(usize, Box<dyn Renderable>)>;
+
 pub struct Renderer {
-    layers: HashMap<usize, Vec<Box<dyn Renderable>>>,
+    layers: HashMap<usize, Layer>,
     sorted_layers: BTreeSet<usize>,
+    globals: Globals,
+    next_id: usize,
 }
 
 impl Renderer {
@@ -275,25 +279,45 @@ impl Renderer {
         Self {
             layers: HashMap::new(),
             sorted_layers: BTreeSet::new(),
+            globals: Globals::default(),
+            next_id: 0,
         }
     }
 
-    pub fn add_renderable<R: Renderable + 'static>(&mut self, item: R, layer: usize) -> usize {
+    pub fn add_renderable<R: Renderable + 'static>(&mut self, item: R, layer: usize) -> RenderableId {
         if self.sorted_layers.insert(layer) {
             self.layers.insert(layer, Vec::new());
         }
 
-        let layer = self.layers.get_mut(&layer).unwrap();
-        let out = layer.len();
-        layer.push(Box::new(item));
+        let id = self.next_id;
+        self.next_id += 1;
+        self.layers.get_mut(&layer).unwrap().push((id, Box::new(item)));
+
+        RenderableId { layer, id }
+    }
+
+    /// Returns `false` when it was removed already
+    pub fn remove_renderable(&mut self, id: RenderableId) -> bool {
+        let layer = match self.layers.get_mut(&id.layer) {
+            Some(layer) => layer,
+            None => return false,
+        };
 
-        out
+        let len = layer.len();
+        layer.retain(|(other, _)| *other != id.id);
+        layer.len() != len
+    }
+
+    /// Set them every frame, they stay until they are overwritten
+    pub fn globals_mut(&mut self) -> &mut Globals {
+        &mut self.globals
     }
 
     pub fn update(&mut self, gl: &GL) -> Option<()> {
         for layer_idx in self.sorted_layers.iter() {
             if let Some(layer) = self.layers.get_mut(layer_idx) {
-                for renderable in layer.iter_mut() {
+                layer.retain(|(_, renderable)| !renderable.is_removed());
+                for (_, renderable) in layer.iter_mut() {
                     // FIXME maybe only update if renderable is enabled?
                     renderable.update(gl)?;
                 }
@@ -302,15 +326,44 @@ impl Renderer {
         Some(())
     }
 
-    pub fn render(&mut self, gl: &GL) {
+    /// Solid renderables first by layer, then the transparent ones farthest from `eye` first
+    pub fn render(&mut self, gl: &GL, eye: Vector3<f32>) {
+        let mut current = None;
+        let mut transparent = Vec::new();
+
         for layer_idx in self.sorted_layers.iter() {
             if let Some(layer) = self.layers.get_mut(layer_idx) {
-                for renderable in layer.iter_mut() {
-                    if !renderable.is_disabled() {
-                        renderable.render(gl);
+                for (i, (_, renderable)) in layer.iter_mut().enumerate() {
+                    if renderable.is_disabled() {
+                        continue;
+                    }
+
+                    let state = renderable.render_state();
+                    if state.is_transparent() {
+                        transparent.push((renderable.distance2(eye), *layer_idx, i));
+                        continue;
                     }
+
+                    state.apply(gl, current.as_ref());
+                    current = Some(state);
+                    renderable.render(gl, &self.globals);
                 }
             }
         }
+
+        // Stable, so equally far renderables keep their layer order
+        transparent.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+        for (_, layer_idx, i) in transparent {
+            let renderable = &mut self.layers.get_mut(&layer_idx).unwrap()[i].1;
+            renderable.sort(eye);
+
+            let state = renderable.render_state();
+            state.apply(gl, current.as_ref());
+            current = Some(state);
+            renderable.render(gl, &self.globals);
+        }
+
+        // Whoever draws next expects the defaults
+        RenderState::default().apply(gl, current.as_ref());
     }
 }
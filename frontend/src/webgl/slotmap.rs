@@ -0,0 +1,170 @@
+//! A generational arena. Removed slots are reused, but keys of the old value
+//! have an older generation, so they don't find the new one.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Debug)]
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+#[derive(Debug)]
+pub struct SlotMap<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl<T> Default for SlotMap<T> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T> SlotMap<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, value: T) -> Key {
+        self.len += 1;
+
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.value = Some(value);
+                Key {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: Some(value),
+                });
+                Key {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: Key) -> Option<T> {
+        let slot = self.slots.get_mut(key.index as usize)?;
+        if slot.generation != key.generation {
+            return None;
+        }
+
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(key.index);
+        self.len -= 1;
+        Some(value)
+    }
+
+    pub fn get(&self, key: Key) -> Option<&T> {
+        let slot = self.slots.get(key.index as usize)?;
+        if slot.generation != key.generation {
+            return None;
+        }
+        slot.value.as_ref()
+    }
+
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut T> {
+        let slot = self.slots.get_mut(key.index as usize)?;
+        if slot.generation != key.generation {
+            return None;
+        }
+        slot.value.as_mut()
+    }
+
+    pub fn contains(&self, key: Key) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// In slot order, not insertion order
+    pub fn iter(&self) -> impl Iterator<Item = (Key, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let key = Key {
+                index: index as u32,
+                generation: slot.generation,
+            };
+            slot.value.as_ref().map(|value| (key, value))
+        })
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = Key> + '_ {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.slots.iter_mut().filter_map(|slot| slot.value.as_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_keys_miss() {
+        let mut map = SlotMap::new();
+        let a = map.insert("a");
+        let b = map.insert("b");
+
+        assert_eq!(map.remove(a), Some("a"));
+        assert_eq!(map.remove(a), None);
+
+        // Same slot, newer generation
+        let c = map.insert("c");
+        assert_eq!(c.index, a.index);
+        assert_ne!(c, a);
+
+        assert_eq!(map.get(a), None);
+        assert!(!map.contains(a));
+        assert!(map.get_mut(a).is_none());
+        assert_eq!(map.remove(a), None);
+        assert_eq!(map.get(c), Some(&"c"));
+        assert_eq!(map.get(b), Some(&"b"));
+    }
+
+    #[test]
+    fn counts_and_iterates_live_values() {
+        let mut map = SlotMap::new();
+        let keys: Vec<_> = (0..4).map(|i| map.insert(i)).collect();
+        map.remove(keys[1]);
+        map.remove(keys[2]);
+        assert_eq!(map.len(), 2);
+
+        let five = map.insert(5);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec![keys[0], five, keys[3]]);
+
+        map.values_mut().for_each(|x| *x *= 10);
+        assert_eq!(map.iter().map(|(_, x)| *x).collect::<Vec<_>>(), vec![0, 50, 30]);
+
+        for key in map.keys().collect::<Vec<_>>() {
+            map.remove(key);
+        }
+        assert!(map.is_empty());
+    }
+}
@@ -1,39 +1,99 @@
 use web_sys::WebGlRenderingContext as GL;
 use web_sys::*;
 
-use cgmath::{Matrix4, Vector4};
-use std::{collections::HashMap, fmt::Debug, ops::Deref, sync::mpsc};
+use cgmath::{Matrix4, Vector3, Vector4};
+use std::{fmt::Debug, ops::Deref};
 
-#[derive(Debug, Clone)]
-pub struct UniformsHandle {
-    inner: mpsc::Sender<UniformUpdate>,
+/// A uniform without a box or a heap, what instances store
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UniformValue {
+    Float(f32),
+    Int(i32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    Mat4([f32; 16]),
 }
 
-impl UniformsHandle {
-    pub fn new(tx: mpsc::Sender<UniformUpdate>) -> Self {
-        Self { inner: tx }
+impl UniformValue {
+    /// The shader has to be bound already
+    pub fn set(&self, gl: &GL, location: &WebGlUniformLocation) {
+        match self {
+            UniformValue::Float(x) => gl.uniform1f(Some(location), *x),
+            UniformValue::Int(x) => gl.uniform1i(Some(location), *x),
+            UniformValue::Vec2([x, y]) => gl.uniform2f(Some(location), *x, *y),
+            UniformValue::Vec3([x, y, z]) => gl.uniform3f(Some(location), *x, *y, *z),
+            UniformValue::Vec4([x, y, z, w]) => gl.uniform4f(Some(location), *x, *y, *z, *w),
+            UniformValue::Mat4(data) => gl.uniform_matrix4fv_with_f32_array(Some(location), false, data),
+        }
+    }
+    /// Same type, all zero, like a uniform nobody set
+    pub fn zero(&self) -> Self {
+        match self {
+            UniformValue::Float(_) => UniformValue::Float(0.0),
+            UniformValue::Int(_) => UniformValue::Int(0),
+            UniformValue::Vec2(_) => UniformValue::Vec2([0.0; 2]),
+            UniformValue::Vec3(_) => UniformValue::Vec3([0.0; 3]),
+            UniformValue::Vec4(_) => UniformValue::Vec4([0.0; 4]),
+            UniformValue::Mat4(_) => UniformValue::Mat4([0.0; 16]),
+        }
+    }
+}
+
+impl Uniform for UniformValue {
+    fn set_uniform(&self, gl: &GL, location: &WebGlUniformLocation) {
+        self.set(gl, location);
+    }
+}
+
+impl From<f32> for UniformValue {
+    fn from(x: f32) -> Self {
+        UniformValue::Float(x)
+    }
+}
+
+impl From<i32> for UniformValue {
+    fn from(x: i32) -> Self {
+        UniformValue::Int(x)
     }
-    pub fn single<S: Into<String>, U: Uniform + 'static>(&self, name: S, uniform: U) -> Option<()> {
-        self.inner
-            .send(UniformUpdate::Single(name.into(), Box::new(uniform)))
-            .ok()
+}
+
+impl From<bool> for UniformValue {
+    fn from(x: bool) -> Self {
+        UniformValue::Int(x as i32)
     }
-    pub fn batch(&self, uniforms: HashMap<String, Box<dyn Uniform>>) -> Option<()> {
-        self.inner.send(UniformUpdate::Batch(uniforms)).ok()
+}
+
+impl From<[f32; 2]> for UniformValue {
+    fn from(x: [f32; 2]) -> Self {
+        UniformValue::Vec2(x)
     }
-    pub fn enable(&self) -> Option<()> {
-        self.inner.send(UniformUpdate::Enable).ok()
+}
+
+impl From<[f32; 3]> for UniformValue {
+    fn from(x: [f32; 3]) -> Self {
+        UniformValue::Vec3(x)
     }
-    pub fn disable(&self) -> Option<()> {
-        self.inner.send(UniformUpdate::Disable).ok()
+}
+
+impl From<Vector3<f32>> for UniformValue {
+    fn from(x: Vector3<f32>) -> Self {
+        UniformValue::Vec3(x.into())
     }
 }
 
-pub enum UniformUpdate {
-    Single(String, Box<dyn Uniform>),
-    Batch(HashMap<String, Box<dyn Uniform>>),
-    Disable,
-    Enable,
+impl From<[f32; 4]> for UniformValue {
+    fn from(x: [f32; 4]) -> Self {
+        UniformValue::Vec4(x)
+    }
+}
+
+impl From<Matrix4<f32>> for UniformValue {
+    fn from(mat: Matrix4<f32>) -> Self {
+        let mut data = [0.0; 16];
+        data.copy_from_slice(<Matrix4<f32> as AsRef<[f32; 16]>>::as_ref(&mat));
+        UniformValue::Mat4(data)
+    }
 }
 
 /************************************************************************/
@@ -72,6 +132,36 @@ impl<A: Deref<Target = [f32]> + Debug> Uniform for Uniform3fv<A> {
         gl.uniform3fv_with_f32_array(Some(location), self.data.deref());
     }
 }
+
+#[derive(Debug)]
+pub struct Uniform4fv<A: Deref<Target = [f32]>> {
+    data: A,
+}
+impl<A: Deref<Target = [f32]>> Uniform4fv<A> {
+    pub fn new(data: A) -> Self {
+        Self { data }
+    }
+}
+impl<A: Deref<Target = [f32]> + Debug> Uniform for Uniform4fv<A> {
+    fn set_uniform(&self, gl: &GL, location: &WebGlUniformLocation) {
+        gl.uniform4fv_with_f32_array(Some(location), self.data.deref());
+    }
+}
+
+#[derive(Debug)]
+pub struct Uniform1fv<A: Deref<Target = [f32]>> {
+    data: A,
+}
+impl<A: Deref<Target = [f32]>> Uniform1fv<A> {
+    pub fn new(data: A) -> Self {
+        Self { data }
+    }
+}
+impl<A: Deref<Target = [f32]> + Debug> Uniform for Uniform1fv<A> {
+    fn set_uniform(&self, gl: &GL, location: &WebGlUniformLocation) {
+        gl.uniform1fv_with_f32_array(Some(location), self.data.deref());
+    }
+}
 #[derive(Debug)]
 
 pub struct Uniformifv<A: Deref<Target = [i32]>> {
@@ -1,20 +1,24 @@
 use crate::models::gen_cube_faces;
 use crate::models::gen_sphere_faces;
-use crate::universe::Planets;
-use crate::universe::Universe;
+use crate::universe::{ClassicMap, History, Layout, Planets, Universe, DEFAULT_UNIVERSE};
+use crate::spectator::{ReplayPlayer, Spectator};
 use crate::util;
 use crate::webgl::renderer::BatchRenderable;
 use crate::webgl::renderer::BatchRenderableHandle;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
-use crate::engine::{Object, ObjectConfig, ObjectFactory};
-use pw_settings::SettingsTrait;
+use crate::engine::{NodeId, ObjectConfig, ObjectFactory, Scene};
+use crate::presets;
+use pw_settings::{Preset, SettingsTrait};
+
+/// Class of the `Planets` settings, presets are stored per class
+const PLANETS_CLASS: &str = "planets";
 
 use super::{renderer::Renderer, Shader};
-use crate::uniform::Uniform3f;
+use crate::uniform::{Uniform1f, Uniform3f, UniformMat4};
 use crate::{
-    engine::{Camera, CameraHandle, Entity},
+    engine::{Camera, CameraHandle, Entity, GameLoop, Lights, Material},
     set_settings,
 };
 use cgmath::Vector3;
@@ -26,32 +30,44 @@ pub struct WebGl {
     canvas: HtmlCanvasElement,
     gl: GL,
 
-    objects: Vec<Object>,
+    scene: Scene,
     universe: Universe,
+    planets: Planets,
+    history: History,
 
     camera: Camera,
     camera_handle: CameraHandle,
+    /// Suns are added to these every frame
+    lights: Lights,
+    /// Seconds since the start, for shaders
+    time: f64,
 
     renderer: Renderer,
 
-    fps_counter: util::FpsCounter,
+    spectator: Option<Spectator>,
+    replay: Option<ReplayPlayer>,
+
+    game_loop: GameLoop,
 }
 
 unsafe impl Send for WebGl {}
 
 unsafe impl Sync for WebGl {}
 
-fn create_object(r: &BatchRenderableHandle, entity: Entity) -> Option<Object> {
+/// A `Layout` from JS, undefined is the default one
+fn parse_layout(layout: JsValue) -> Result<Layout, JsValue> {
+    if layout.is_undefined() || layout.is_null() {
+        Ok(Layout::default())
+    } else {
+        Ok(layout.into_serde().map_err(|e| format!("Invalid layout {:?}", e))?)
+    }
+}
+
+fn create_object(r: &BatchRenderableHandle, scene: &mut Scene, entity: Entity) -> Option<NodeId> {
     let handle = r.push()?;
-    handle.single(
-        "u_reverseLightDirection",
-        Uniform3f::new(0.28735632183908044, 0.4022988505747126, 0.5747126436781609),
-    );
-    handle.single(
-        "u_color",
-        Uniform3f::new(1.0, 1.0, 1.0),
-    );
-    Some(Object::new(handle, entity))
+    handle.batch(Material::default().uniforms());
+    handle.single("u_color", [1.0, 1.0, 1.0]);
+    scene.add(None, entity, Some(handle))
 }
 
 #[wasm_bindgen]
@@ -78,18 +94,26 @@ impl WebGl {
         let camera_handle = camera.handle();
         // camera_handle.reset_position(0.0, 0.0, 5.0);
 
+        let mut scene = Scene::new();
+
         Ok(Self {
             canvas,
             gl,
 
-            objects: Vec::new(),
-            universe: Universe::place_holder(),
+            universe: Universe::place_holder(&mut scene),
+            scene,
+            planets: Planets::default(),
+            history: History::new(),
 
             camera,
             camera_handle,
+            lights: Lights::default(),
+            time: 0.0,
 
             renderer: Renderer::new(),
-            fps_counter: util::FpsCounter::new(),
+            spectator: None,
+            replay: None,
+            game_loop: GameLoop::new(),
         })
     }
 
@@ -124,9 +148,17 @@ impl WebGl {
         gl.enable(GL::DEPTH_TEST);
 
         {
+            // The default one, unless it's gone and the backend has others
+            let available = Planets::available().await;
+            let location = if available.is_empty() || available.iter().any(|x| x == DEFAULT_UNIVERSE) {
+                DEFAULT_UNIVERSE.to_string()
+            } else {
+                available[0].clone()
+            };
+
             let planets = self
                 .universe
-                .init(gl, &mut self.renderer, "universe.json")
+                .init(gl, &mut self.renderer, &mut self.scene, &location)
                 .await?;
 
             let js_value = JsValue::from_serde(&planets.to_settings(None))
@@ -134,6 +166,13 @@ impl WebGl {
                 .unwrap();
             println!("js value {:?}", js_value);
             unsafe { set_settings(js_value) };
+
+            // Presets on the backend are optional
+            if let Err(e) = presets::sync_remote(PLANETS_CLASS).await {
+                console_log!("No shared presets {:?}", e);
+            }
+
+            self.planets = planets;
         }
 
         let shader_factory = {
@@ -165,11 +204,9 @@ impl WebGl {
             let mut sphere_entity = sphere_entity.clone();
             sphere_entity.set_position(Vector3::new(0.0, 0.0, -800.0).into());
             sphere_entity.set_scale(Vector3::new(50.0, 50.0, 50.0).into());
-            self.objects.push(
-                sphere_factory
-                    .create(gl, &mut self.renderer, sphere_entity)
-                    .ok_or("Sphere creation failed")?,
-            );
+            sphere_factory
+                .create(gl, &mut self.renderer, &mut self.scene, None, sphere_entity)
+                .ok_or("Sphere creation failed")?;
         }
 
         let ship_creation_handle = {
@@ -188,8 +225,7 @@ impl WebGl {
                 let mut sphere_entity = sphere_entity.clone();
                 sphere_entity
                     .set_position(Vector3::new((i * 50) as f32, (j * 100) as f32, -500.0).into());
-                self.objects
-                    .push(create_object(&ship_creation_handle, sphere_entity).ok_or("bla")?);
+                create_object(&ship_creation_handle, &mut self.scene, sphere_entity).ok_or("bla")?;
             }
         }
 
@@ -202,40 +238,44 @@ impl WebGl {
             .with_position(Vector3::new(-500.0, 0.0, -500.0))
             .with_hom_scale(50.0)
             .with_ang_speed(Vector3::new(30.0, 60.0, 0.0)); //.with_speed(Vector3::new(5.0, 0.0, 10.0));
-        self.objects.push(
-            sphere_factory
-                .create(gl, &mut self.renderer, sphere_entity)
-                .ok_or("Sphere creation failed")?,
-        );
+        let sphere = sphere_factory
+            .create(gl, &mut self.renderer, &mut self.scene, None, sphere_entity)
+            .ok_or("Sphere creation failed")?;
+
+        // A moon, it's placed relative to the sphere so it turns with it
+        let moon_entity = Entity::default()
+            .with_position(Vector3::new(2.0, 0.0, 0.0))
+            .with_hom_scale(0.2);
+        cube_factory
+            .create(gl, &mut self.renderer, &mut self.scene, Some(sphere), moon_entity)
+            .ok_or("Moon creation failed")?;
 
         let cube_entity = Entity::default()
             .with_position(Vector3::new(500.0, 0.0, -500.0))
             .with_hom_scale(50.0)
             .with_ang_speed(Vector3::new(10.0, 30.0, 0.0)); //.with_speed(Vector3::new(5.0, 0.0, 10.0));
-        self.objects.push(
-            cube_factory
-                .create(gl, &mut self.renderer, cube_entity)
-                .ok_or("Cube creation failed")?,
-        );
+        cube_factory
+            .create(gl, &mut self.renderer, &mut self.scene, None, cube_entity)
+            .ok_or("Cube creation failed")?;
 
         // Setup floor
         let cube_entity = Entity::default()
             .with_position(Vector3::new(0.0, -100.0, 0.0))
             .with_scale(5000.0, 5.0, 5000.0);
-        self.objects.push(
-            cube_factory
-                .create(gl, &mut self.renderer, cube_entity)
-                .ok_or("Cube creation failed")?,
-        );
+        cube_factory
+            .create(gl, &mut self.renderer, &mut self.scene, None, cube_entity)
+            .ok_or("Cube creation failed")?;
 
         Ok(self)
     }
 
     pub fn handle_client_update(&mut self, val: &JsValue) {
         match val.into_serde::<Planets>() {
-            Ok(planets) => match self.universe.set_planets(&planets) {
+            Ok(planets) => match self.universe.set_planets(&mut self.scene, &planets) {
                 Ok(_) => {
                     console_log!("Got planets {:?}", planets);
+                    self.history.clear();
+                    self.planets = planets;
                 }
                 Err(e) => {
                     console_log!("Woops something failed {:?}", e)
@@ -247,18 +287,225 @@ impl WebGl {
         }
     }
 
+    /// Apply a single edit from the settings panel, `path` is like `planets[2].location.scale.x`.
+    /// Edits that don't fit the field's config are rejected and change nothing.
+    pub fn handle_field_update(&mut self, path: String, value: JsValue) -> Result<(), JsValue> {
+        let value: pw_settings::Value = value.into_serde().map_err(|e| format!("Invalid value {:?}", e))?;
+        // Whole arrays and array ops can change the shape, so the ui has to be rebuilt
+        let compound = value.is_object() || value.is_array();
+
+        let mut planets = self.planets.clone();
+        let edit = self
+            .history
+            .apply(&mut planets, &path, value, js_sys::Date::now())
+            .map_err(|e| e.to_string())?;
+
+        self.universe.set_planets(&mut self.scene, &planets)?;
+
+        // So do fields that got hidden or disabled by this edit
+        let settings = planets.to_settings(None);
+        if compound || settings.states() != self.planets.to_settings(None).states() {
+            let js_value = JsValue::from_serde(&settings).map_err(|_| "Serde Failed")?;
+            unsafe { set_settings(js_value) };
+        }
+
+        self.history.push(edit);
+        self.planets = planets;
+        Ok(())
+    }
+
+    /// Take back the last edit, returns `false` when there was nothing to undo.
+    pub fn undo(&mut self) -> Result<bool, JsValue> {
+        let mut planets = self.planets.clone();
+        if !self.history.undo(&mut planets).map_err(|e| e.to_string())? {
+            return Ok(false);
+        }
+
+        if let Err(e) = self.show_planets(&planets) {
+            // Put the edit back where it was
+            let _ = self.history.redo(&mut planets);
+            return Err(e);
+        }
+
+        self.planets = planets;
+        Ok(true)
+    }
+
+    /// Do the last undone edit again, returns `false` when there was nothing to redo.
+    pub fn redo(&mut self) -> Result<bool, JsValue> {
+        let mut planets = self.planets.clone();
+        if !self.history.redo(&mut planets).map_err(|e| e.to_string())? {
+            return Ok(false);
+        }
+
+        if let Err(e) = self.show_planets(&planets) {
+            let _ = self.history.undo(&mut planets);
+            return Err(e);
+        }
+
+        self.planets = planets;
+        Ok(true)
+    }
+
+    /// Ends a slider drag, the next edit to the same field is a step of its own.
+    pub fn seal_history(&mut self) {
+        self.history.seal();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    /// Of the fleet trails, in world units
+    pub fn set_fleet_width(&mut self, width: f32) {
+        if let Some(fleets) = self.universe.fleets_mut() {
+            fleets.set_width(width);
+        }
+    }
+
+    /// How much of its route a fleet trail covers, 0 to 1
+    pub fn set_fleet_trail(&mut self, trail: f32) {
+        if let Some(fleets) = self.universe.fleets_mut() {
+            fleets.set_trail(trail);
+        }
+    }
+
+    /// Update the universe and the settings panel, undo can touch any field
+    fn show_planets(&mut self, planets: &Planets) -> Result<(), JsValue> {
+        self.universe.set_planets(&mut self.scene, planets)?;
+
+        let js_value = JsValue::from_serde(&planets.to_settings(None)).map_err(|_| "Serde Failed")?;
+        unsafe { set_settings(js_value) };
+        Ok(())
+    }
+
+    /// Follow a live match through the backend relay, `url` is a websocket url.
+    pub fn spectate(&mut self, url: String) -> Result<(), JsValue> {
+        self.spectator = Some(Spectator::connect(&url)?);
+        Ok(())
+    }
+
+    pub fn stop_spectating(&mut self) {
+        self.spectator = None;
+    }
+
+    /// Play back a replay written by `backend match`, `replay` is the json content.
+    /// The universe is replaced by the replay's map, laid out like in `import_map`.
+    pub fn play_replay(&mut self, replay: String, layout: JsValue) -> Result<(), JsValue> {
+        let player = ReplayPlayer::parse(&replay)?;
+        let map = ClassicMap::parse(player.map())?;
+        self.show_map(&map, parse_layout(layout)?)?;
+
+        self.replay = Some(player);
+        Ok(())
+    }
+
+    /// Replace the universe with a classic 2D map.
+    /// `layout` is a `Layout` like `{"type": "Spherical", "scale": 20, "radius": 300}`, or undefined.
+    pub fn import_map(&mut self, map: String, layout: JsValue) -> Result<(), JsValue> {
+        let map = ClassicMap::parse(&map)?;
+        self.show_map(&map, parse_layout(layout)?)
+    }
+
+    fn show_map(&mut self, map: &ClassicMap, layout: Layout) -> Result<(), JsValue> {
+        let planets = Planets::from_classic(map, layout)?;
+        self.universe.set_planets(&mut self.scene, &planets)?;
+
+        let js_value = JsValue::from_serde(&planets.to_settings(None)).map_err(|_| "Serde Failed")?;
+        unsafe { set_settings(js_value) };
+
+        self.history.clear();
+        self.planets = planets;
+        Ok(())
+    }
+
+    /// The current universe as a classic 2D map, projected with `layout` like in `import_map`.
+    pub fn export_map(&self, layout: JsValue) -> Result<String, JsValue> {
+        Ok(self.planets.to_classic(parse_layout(layout)?).to_string())
+    }
+
+    /// Save the changes to the current universe as a preset, on the backend too when `remote` is set.
+    pub fn save_preset(&self, name: String, remote: bool) -> Result<(), JsValue> {
+        presets::save(Preset::capture(&name, &self.planets, None), remote)
+    }
+
+    /// Returns the parts of the preset that don't fit the current planets anymore, as messages.
+    pub fn load_preset(&mut self, name: String) -> Result<JsValue, JsValue> {
+        let local = presets::load_local();
+        let preset = local
+            .get(PLANETS_CLASS, &name)
+            .ok_or_else(|| format!("No preset named '{}'", name))?;
+
+        let (planets, errors): (Planets, _) = preset.load(None);
+        self.universe.set_planets(&mut self.scene, &planets)?;
+
+        let js_value = JsValue::from_serde(&planets.to_settings(None)).map_err(|_| "Serde Failed")?;
+        unsafe { set_settings(js_value) };
+        self.history.clear();
+        self.planets = planets;
+
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        JsValue::from_serde(&errors).map_err(|_| "Serde Failed".into())
+    }
+
+    pub fn delete_preset(&self, name: String) -> Result<(), JsValue> {
+        let mut local = presets::load_local();
+        local.remove(PLANETS_CLASS, &name);
+        presets::save_local(&local)
+    }
+
+    pub fn preset_names(&self) -> Result<JsValue, JsValue> {
+        let local = presets::load_local();
+        let names: Vec<&str> = local.names(PLANETS_CLASS).collect();
+        JsValue::from_serde(&names).map_err(|_| "Serde Failed".into())
+    }
+
+    /// JSON Schema of universe files, `static/universe.schema.json` is this one written out.
+    pub fn universe_schema() -> Result<String, JsValue> {
+        let schema = Planets::json_schema(None);
+        serde_json::to_string_pretty(&schema).map_err(|e| format!("Serde failed {:?}", e).into())
+    }
+
+    /// A frame, `dt` seconds after the last one. Objects move in fixed ticks and are drawn in between.
     pub fn update(&mut self, dt: f64) -> Result<(), JsValue> {
-        self.fps_counter.update(dt);
+        self.time += dt;
+
+        if let Some(state) = self.spectator.as_ref().and_then(|s| s.poll()) {
+            self.universe.apply_state(&state);
+        }
+
+        if let Some(state) = self.replay.as_mut().and_then(|r| r.update(dt)) {
+            self.universe.apply_state(state);
+        }
 
         self.camera.update().ok_or("Couldn't update camera")?;
         let gl = &self.gl;
 
-        let camera = &self.camera;
-
-        self.universe.update(dt, camera);
-        self.objects
-            .iter_mut()
-            .for_each(|object| object.update(dt as f32, camera));
+        let universe = &mut self.universe;
+        let scene = &mut self.scene;
+        let alpha = self.game_loop.frame(dt, |tick| {
+            scene.tick(tick as f32);
+            universe.tick(scene, tick);
+        });
+
+        self.scene.update_uniforms(alpha as f32);
+        self.universe.update_fleets(&self.scene);
+
+        // The same for everything this frame, objects only carry their own world matrix and color
+        let mut lights = self.lights.clone();
+        self.universe.add_lights(&self.scene, &mut lights);
+        let eye = self.camera.position();
+        let globals = self.renderer.globals_mut();
+        globals.set("u_viewProjection", UniformMat4::new_mat4(self.camera.world_view_projection_matrix()));
+        globals.set("u_view", UniformMat4::new_mat4(self.camera.view_matrix()));
+        globals.set("u_projection", UniformMat4::new_mat4(self.camera.projection_matrix()));
+        globals.set("u_eye", Uniform3f::new(eye.x, eye.y, eye.z));
+        globals.set("u_time", Uniform1f::new(self.time as f32));
+        globals.extend(lights.uniforms());
 
         self.renderer
             .update(gl)
@@ -267,12 +514,12 @@ impl WebGl {
     }
 
     pub fn render_gl(&mut self) -> Result<(), JsValue> {
-        self.renderer.render(&self.gl);
+        self.renderer.render(&self.gl, self.camera.position());
         Ok(())
     }
 
     pub fn handle_click(&mut self, x: f32, y: f32) {
         let (origin, direction) = self.camera.handle_click(x, y);
-        self.universe.handle_click(origin, direction);
+        self.universe.handle_click(&self.scene, origin, direction);
     }
 }
@@ -4,3 +4,8 @@ function set_info(x, y, z, angl_x, angl_y, angl_z) {
     div.innerHTML = `<p>Position: ${x.toFixed(2)}, ${y.toFixed(2)}, ${z.toFixed(2)}</p>
         <p>Rotation: ${angl_x.toFixed(2)}, ${angl_y.toFixed(2)}, ${angl_z.toFixed(2)}</p>`;
 }
+
+const timingsDiv = document.getElementById("timings");
+function set_timings(fps, frame_ms, tick_ms) {
+    timingsDiv.innerHTML = `<p>Fps: ${fps.toFixed(0)}, frame: ${frame_ms.toFixed(2)} ms, tick: ${tick_ms.toFixed(2)} ms</p>`;
+}
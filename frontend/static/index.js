@@ -71,6 +71,18 @@ async function doInit() {
 
     let webGL = await new WebGl("canvas").init_renderer();
 
+    // Watch a live match with ?spectate=ws://host:8001
+    const spectate = new URLSearchParams(window.location.search).get("spectate");
+    if (spectate) {
+        webGL.spectate(spectate);
+    }
+
+    // Or play back a recorded match with ?replay=replays/match-123.json
+    const replay = new URLSearchParams(window.location.search).get("replay");
+    if (replay) {
+        fetch(replay).then(r => r.text()).then(text => webGL.play_replay(text, undefined));
+    }
+
     console.log(canvas);
     canvas.addEventListener("click", e => {
         console.log("CLICK");
@@ -86,6 +98,18 @@ async function doInit() {
     let handle = webGL.camera_handle();
     document.addEventListener("keydown", e => {
         if(e.target.nodeName === "INPUT") return;
+
+        if (e.ctrlKey || e.metaKey) {
+            const key = e.key.toLowerCase();
+            try {
+                if (key === "z" && !e.shiftKey) webGL.undo();
+                if ((key === "z" && e.shiftKey) || key === "y") webGL.redo();
+            } catch (err) {
+                console.error(err);
+            }
+            return;
+        }
+
         switch (e.key) {
             case "w":
                 movement.back = true;
@@ -157,11 +181,22 @@ async function doInit() {
     }
     window.requestAnimationFrame(render);
 
-    addSettingsChangeListener(debounce(v => {
-        console.log("Sending to rust")
-        console.log(v)
-        webGL.handle_client_update(v)
-    }));
+    set_presets({
+        names: () => webGL.preset_names(),
+        save: (name, remote) => webGL.save_preset(name, remote),
+        load: (name) => webGL.load_preset(name),
+        remove: (name) => webGL.delete_preset(name),
+    });
+
+    addSettingsCommitListener(() => webGL.seal_history());
+
+    addSettingsChangeListener((path, value) => {
+        try {
+            webGL.handle_field_update(path, value);
+        } catch (e) {
+            console.error(e);
+        }
+    });
 }
 
 doInit();
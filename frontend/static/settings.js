@@ -6,6 +6,12 @@ function addSettingsChangeListener(cb) {
     handlers.push(cb);
 }
 
+// Called when a drag ends, edits to the same field after that are a new undo step
+const commitHandlers = [];
+function addSettingsCommitListener(cb) {
+    commitHandlers.push(cb);
+}
+
 function _genNamedDiv(name, ...classes) {
     const div = document.createElement("div");
     div.classList.add("field", ...classes);
@@ -33,7 +39,6 @@ function genField(name, value, cb = (e) => {}, readOnly=false) {
     changeText(value);
 
     div.appendChild(valueField);
-    cb(valueField.value);
 
     return [div, changeText];
 }
@@ -43,56 +48,154 @@ function genSlider(name, content, cb = (e) => {}, readOnly=false) {
 
     const valueField = document.createElement("input");
     valueField.classList.add("input");
-    valueField.readOnly = readOnly;
+    valueField.disabled = readOnly;
     valueField.type = "range";
     valueField.min = content.min;
     valueField.max = content.max;
     valueField.step = content.inc;
 
     valueField.addEventListener("input", e => cb(parseFloat(e.target.value)));
+    valueField.addEventListener("change", _ => commitHandlers.forEach(cb => cb()));
 
     const changeText = (t) => valueField.value = t;
     changeText(content.value);
 
     div.appendChild(valueField);
-    cb(parseFloat(valueField.value));
 
     return [div, changeText];
 }
 
-function genArray(name, content, parent_cb = (e) => {}, readOnly=false) {
-    const div = _genNamedDiv(name, "array");
+function genInt(name, content, cb = (e) => {}, readOnly=false) {
+    const div = _genNamedDiv(name, "int");
 
-    const vecDiv =  document.createElement("div");
-    const values = [];
-    const setters = [];
+    const valueField = document.createElement("input");
+    valueField.classList.add("input");
+    valueField.readOnly = readOnly;
+    valueField.type = "number";
+    valueField.min = content.min;
+    valueField.max = content.max;
+    valueField.step = content.inc;
+
+    valueField.addEventListener("input", e => {
+        const value = parseInt(e.target.value);
+        if (!isNaN(value)) cb(value);
+    });
+
+    const changeText = (t) => valueField.value = t;
+    changeText(content.value);
+
+    div.appendChild(valueField);
+
+    return [div, changeText];
+}
+
+function _toHex(rgb) {
+    return "#" + rgb.map(c => Math.round(c * 255).toString(16).padStart(2, "0")).join("");
+}
+
+function genColor(name, value, cb = (e) => {}, readOnly=false) {
+    const div = _genNamedDiv(name, "color");
 
-    for (let index = 0; index < content.length; index++) {
-        const element = content[index];
+    const valueField = document.createElement("input");
+    valueField.classList.add("input");
+    valueField.disabled = readOnly;
+    valueField.type = "color";
+
+    valueField.addEventListener("input", e => cb(e.target.value));
+
+    const changeColor = (t) => valueField.value = _toHex(t);
+    changeColor(value);
+
+    div.appendChild(valueField);
+
+    return [div, changeColor];
+}
 
-        const cb = v => {
-            values[index] = v;   // This should be useless
-            parent_cb(values);
+// One slider per axis, every axis is emitted on its own like `position.x`
+function genVec3(name, content, path, emit, readOnly=false) {
+    const div = _genNamedDiv(name, "vec3");
+    const setters = [];
+
+    ["x", "y", "z"].forEach((axis, i) => {
+        const axisContent = {
+            value: content.value[i],
+            min: content.min[i],
+            max: content.max[i],
+            inc: content.inc[i],
         };
+        const [axisDiv, setter] = genSlider(axis, axisContent, v => emit(`${path}.${axis}`, v), readOnly);
+        div.appendChild(axisDiv);
+        setters.push(setter);
+    });
 
-        const fieldElement = genSetting(element, v => values[index] = v, () => parent_cb(values));
-        if(fieldElement) {
-            div.appendChild(fieldElement[0]);
-            setters.push(fieldElement[1]);
-        }
+    const changeVec = (t) => t.forEach((v, i) => setters[i](v));
+
+    return [div, changeVec];
+}
+
+function genDropdown(name, content, cb = (e) => {}, readOnly=false) {
+    const div = _genNamedDiv(name, "dropdown");
+
+    const valueField = document.createElement("select");
+    valueField.classList.add("input");
+    valueField.disabled = readOnly;
+
+    for(let option of content.options) {
+        const optionField = document.createElement("option");
+        optionField.value = option;
+        optionField.innerText = option;
+        valueField.appendChild(optionField);
     }
 
-    const changeText = (t) => {
-        for (let index = 0; index < t.length; index++) {
-            setter[index](t[index]);
-        }
-    };
+    valueField.addEventListener("change", e => cb(e.target.value));
 
-    div.appendChild(vecDiv);
+    const changeValue = (t) => valueField.value = t;
+    changeValue(content.value);
 
-    parent_cb(values);
+    div.appendChild(valueField);
 
-    return [div, changeText];
+    return [div, changeValue];
+}
+
+function _genButton(text, disabled, onClick) {
+    const button = document.createElement("button");
+    button.innerText = text;
+    button.disabled = disabled;
+    button.addEventListener("click", onClick);
+    return button;
+}
+
+// Items can be added, removed and moved, the whole array is rebuilt from the new settings afterwards
+function genArray(name, content, path, emit, readOnly=false) {
+    const div = _genNamedDiv(name, "array");
+    const items = content.items;
+    const canAdd = !readOnly && (content.max_len === null || items.length < content.max_len);
+    const canRemove = !readOnly && items.length > content.min_len;
+
+    items.forEach((item, index) => {
+        const itemDiv = document.createElement("div");
+        itemDiv.classList.add("array_item");
+
+        const fieldElement = genSetting(item, `${path}[${index}]`, emit, readOnly);
+        if(fieldElement) itemDiv.appendChild(fieldElement[0]);
+
+        const controls = document.createElement("div");
+        controls.classList.add("array_controls");
+        controls.appendChild(_genButton("up", readOnly || index == 0,
+            () => emit(path, { move: { from: index, to: index - 1 } })));
+        controls.appendChild(_genButton("down", readOnly || index == items.length - 1,
+            () => emit(path, { move: { from: index, to: index + 1 } })));
+        controls.appendChild(_genButton("remove", !canRemove,
+            () => emit(path, { remove: { index } })));
+        itemDiv.appendChild(controls);
+
+        div.appendChild(itemDiv);
+    });
+
+    div.appendChild(_genButton("add", !canAdd,
+        () => emit(path, { insert: { index: items.length } })));
+
+    return [div, (_) => {}];
 }
 
 function genCheck(name, value, cb = (e) => {}, readOnly=false) {
@@ -100,7 +203,7 @@ function genCheck(name, value, cb = (e) => {}, readOnly=false) {
 
     const valueField = document.createElement("input");
     valueField.classList.add("input");
-    valueField.readOnly = readOnly;
+    valueField.disabled = readOnly;
     valueField.type = "checkbox";
 
     valueField.addEventListener("input", e => cb(e.target.checked));
@@ -109,76 +212,221 @@ function genCheck(name, value, cb = (e) => {}, readOnly=false) {
     changeChecked(value);
 
     div.appendChild(valueField);
-    cb(valueField.checked);
 
     return [div, changeChecked];
 }
 
-function genSetting(field, setter, flush) {
-    const cb = v => {
-        setter(v);
-        flush();
+// Only the fields of the selected option are shown, switching emits `path.variant`
+function genSelect(name, content, path, emit) {
+    const div = _genNamedDiv(name, "select");
+
+    const valueField = document.createElement("select");
+    valueField.classList.add("input");
+
+    for(let option of content.options) {
+        const optionField = document.createElement("option");
+        optionField.value = option.id;
+        optionField.innerText = option.name;
+        valueField.appendChild(optionField);
+    }
+
+    const fieldsDiv = document.createElement("div");
+    const showOption = (id) => {
+        valueField.value = id;
+        fieldsDiv.innerHTML = "";
+
+        const option = content.options.find(o => o.id == id);
+        if(option) {
+            const [optionDiv, _] = genSettings("", option.settings, path, emit);
+            fieldsDiv.appendChild(optionDiv);
+        }
     };
 
+    valueField.addEventListener("change", e => {
+        emit(path ? `${path}.variant` : "variant", e.target.value);
+        showOption(e.target.value);
+    });
+
+    showOption(content.value);
+
+    div.appendChild(valueField);
+    div.appendChild(fieldsDiv);
+
+    return [div, showOption];
+}
+
+// Every widget reports its own edits as (path, value), like ("planets[2].location.scale.x", 3.5)
+function genSetting(field, path, emit, readOnly=false) {
+    const cb = v => emit(path, v);
+
     switch(field.type) {
         case "array":
-            return genArray(field.name, field.content, cb);
+            return genArray(field.name, field.content, path, emit, readOnly);
         case "text":
-            return genField(field.name, field.content, cb);
+            return genField(field.name, field.content, cb, readOnly);
         case "slider":
-            return genSlider(field.name, field.content, cb);
+            return genSlider(field.name, field.content, cb, readOnly);
+        case "int":
+            return genInt(field.name, field.content, cb, readOnly);
+        case "color":
+            return genColor(field.name, field.content, cb, readOnly);
+        case "vec3":
+            return genVec3(field.name, field.content, path, emit, readOnly);
+        case "dropdown":
+            return genDropdown(field.name, field.content, cb, readOnly);
+        case "readonly":
+            return genSetting({ ...field.content, name: field.name }, path, emit, true);
         case "settings":
-            return genSettings(field.name, field.content, cb);
+            return genSettings(field.name, field.content, path, emit, readOnly);
         case "check":
-            return genCheck(field.name, field.content, cb);
+            return genCheck(field.name, field.content, cb, readOnly);
+        case "select":
+            return genSelect(field.name, field.content, path, emit);
         case "data":
-            setter(field.content);
             break;
         default:
             console.error("Wrong field type "+ field.type);
     }
 }
 
-function genSettings(name, settings, parent_cb) {
-    console.log(settings);
+// Groups that were opened, so they stay open when the panel is rebuilt
+const openGroups = new Set();
+
+function _genGroup(group, key) {
+    const details = document.createElement("details");
+    details.classList.add("group");
+    details.open = openGroups.has(key);
+    details.addEventListener("toggle", _ => details.open ? openGroups.add(key) : openGroups.delete(key));
+
+    const summary = document.createElement("summary");
+    summary.innerText = group;
+    details.appendChild(summary);
+
+    return details;
+}
+
+// Help becomes a tooltip, the unit goes after the name
+function _describe(element, field) {
+    if(field.help) element.title = field.help;
+
+    const nameField = element.querySelector(":scope > p");
+    if(field.unit && nameField) {
+        const unit = document.createElement("span");
+        unit.classList.add("unit");
+        unit.innerText = ` (${field.unit})`;
+        nameField.appendChild(unit);
+    }
+}
+
+function genSettings(name, settings, path, emit, readOnly=false) {
     const wrapper = _genNamedDiv(name, "settings", settings.class);
     const div = document.createElement("div");
     div.classList.add("input");
     wrapper.appendChild(div);
 
-    const values = {};
-
-    for(let field of settings.fields) {
-        const fieldElement = genSetting(field, v => values[field.id] = v, () => parent_cb(values));
-        if(fieldElement)
+    // Ordered fields first, the others keep their place
+    const fields = settings.fields
+        .map((field, index) => ({ field, index }))
+        .sort((a, b) => (a.field.order ?? Infinity) - (b.field.order ?? Infinity) || a.index - b.index)
+        .map(x => x.field);
+
+    const groups = {};
+    for(let field of fields) {
+        // Conditions are evaluated in rust, the panel is rebuilt when they change
+        if(field.hidden) continue;
+
+        const fieldPath = path ? `${path}.${field.id}` : field.id;
+        const fieldElement = genSetting(field, fieldPath, emit, readOnly || !!field.disabled);
+        if(!fieldElement) continue;
+
+        _describe(fieldElement[0], field);
+        if(field.disabled) fieldElement[0].classList.add("disabled");
+
+        if(field.group) {
+            if(!groups[field.group]) {
+                groups[field.group] = _genGroup(field.group, `${path}#${field.group}`);
+                div.appendChild(groups[field.group]);
+            }
+            groups[field.group].appendChild(fieldElement[0]);
+        } else {
             div.appendChild(fieldElement[0]);
+        }
     }
 
-    // Initiate chain
-    parent_cb(values);
-
     return [wrapper, (_) => {}];
 }
 
 function set_settings(settings) {
     console.log(settings);
 
-    // FIXME: This is used to prevent loops
-    const stop_wrapper = {"inner": true};
-    const broadcast = v => {
-        if (stop_wrapper.inner) return;
-        console.log(v);
+    const broadcast = (path, value) => {
         for(cb of handlers) {
-            cb(v);
+            cb(path, value);
         }
     };
 
     const settingsDiv = document.getElementById("settings");
     settingsDiv.innerHTML = "";
 
-    const [div, _] = genSettings("", settings, broadcast);
-
-    stop_wrapper.inner = false;
+    const [div, _] = genSettings("", settings, "", broadcast);
 
     settingsDiv.appendChild(div);
 }
+
+// Save and load named presets, `store` has names(), save(name, remote), load(name) and remove(name).
+// Loading returns what didn't fit anymore, that's only reported.
+function set_presets(store) {
+    const presetsDiv = document.getElementById("presets");
+    presetsDiv.innerHTML = "";
+
+    const select = document.createElement("select");
+    const refresh = () => {
+        select.innerHTML = "";
+        for(let name of store.names()) {
+            const option = document.createElement("option");
+            option.value = name;
+            option.innerText = name;
+            select.appendChild(option);
+        }
+    };
+
+    const nameField = document.createElement("input");
+    nameField.type = "text";
+    nameField.placeholder = "preset name";
+
+    const remote = document.createElement("input");
+    remote.type = "checkbox";
+    remote.title = "Share on the server";
+
+    const report = (f) => {
+        try {
+            f();
+        } catch (e) {
+            console.error(e);
+        }
+    };
+
+    presetsDiv.appendChild(nameField);
+    presetsDiv.appendChild(remote);
+    presetsDiv.appendChild(_genButton("save", false, () => report(() => {
+        if(!nameField.value) return;
+        store.save(nameField.value, remote.checked);
+        refresh();
+        select.value = nameField.value;
+    })));
+
+    presetsDiv.appendChild(select);
+    presetsDiv.appendChild(_genButton("load", false, () => report(() => {
+        if(!select.value) return;
+        for(let skipped of store.load(select.value)) {
+            console.warn("Preset field skipped: " + skipped);
+        }
+    })));
+    presetsDiv.appendChild(_genButton("delete", false, () => report(() => {
+        if(!select.value) return;
+        store.remove(select.value);
+        refresh();
+    })));
+
+    refresh();
+}
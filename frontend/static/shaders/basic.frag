@@ -1,15 +1,61 @@
 precision mediump float;
 
+// Has to match MAX_LIGHTS in lighting.rs
+#define MAX_LIGHTS 4
+
+struct Material {
+    float ambient;
+    float diffuse;
+    float specular;
+    float shininess;
+    float emissive;
+};
+
 varying vec3 v_normal;
+varying vec3 v_position;
 
 uniform float u_time;
-uniform vec3 u_reverseLightDirection;
 uniform vec3 u_color;
+uniform Material u_material;
+
+// Shared by everything in the frame
+uniform vec3 u_eye;
+uniform vec3 u_ambient;
+uniform int u_lightCount;
+// A w of 0 is a directional light, xyz then points towards the light
+uniform vec4 u_lightPosition[MAX_LIGHTS];
+uniform vec3 u_lightColor[MAX_LIGHTS];
+// Point lights are half as bright this far away
+uniform float u_lightRange[MAX_LIGHTS];
 
 void main() {
     vec3 normal = normalize(v_normal);
-    float light = dot(normal, u_reverseLightDirection);
+    vec3 toEye = normalize(u_eye - v_position);
+
+    vec3 diffuse = vec3(0.0);
+    vec3 specular = vec3(0.0);
+    for (int i = 0; i < MAX_LIGHTS; i++) {
+        if (i >= u_lightCount) {
+            break;
+        }
+
+        vec4 light = u_lightPosition[i];
+        vec3 toLight = light.xyz - v_position * light.w;
+        float distance = length(toLight);
+        toLight /= distance;
+
+        float fade = distance / u_lightRange[i];
+        vec3 color = u_lightColor[i] * mix(1.0, 1.0 / (1.0 + fade * fade), light.w);
+
+        float lambert = dot(normal, toLight);
+        if (lambert > 0.0) {
+            diffuse += color * lambert;
+
+            vec3 halfway = normalize(toLight + toEye);
+            specular += color * pow(max(dot(normal, halfway), 0.0), u_material.shininess);
+        }
+    }
 
-    gl_FragColor = vec4(u_color, 1.0);
-    gl_FragColor.rgb *= light;
+    vec3 light = u_ambient * u_material.ambient + diffuse * u_material.diffuse + u_material.emissive;
+    gl_FragColor = vec4(u_color * light + specular * u_material.specular, 1.0);
 }
@@ -1,9 +1,11 @@
 precision mediump float;
 
 uniform mat4 u_world;
-uniform mat4 u_worldViewProjection;
+// Camera, shared by everything in the frame
+uniform mat4 u_viewProjection;
 
 varying vec3 v_normal;
+varying vec3 v_position;
 
 attribute vec3 a_position;
 attribute vec3 a_normal;
@@ -11,5 +13,8 @@ attribute vec3 a_normal;
 void main() {
     v_normal = mat3(u_world) * a_normal;
 
-    gl_Position = u_worldViewProjection * (u_world * vec4(a_position, 1.0));
+    vec4 position = u_world * vec4(a_position, 1.0);
+    v_position = position.xyz;
+
+    gl_Position = u_viewProjection * position;
 }
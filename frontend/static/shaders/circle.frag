@@ -1,7 +1,12 @@
 precision mediump float;
 
+uniform vec3 u_color;
+uniform float u_alpha;
+
 varying vec3 v_color;
 
 void main() {
-    gl_FragColor = vec4(v_color, 1.0);
+    // The mesh alternates green and red triangles, that's just enough for some bands
+    float band = 0.9 + 0.1 * v_color.g;
+    gl_FragColor = vec4(u_color * band, u_alpha);
 }
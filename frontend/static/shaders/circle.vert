@@ -1,7 +1,13 @@
 precision mediump float;
 
 uniform mat4 u_world;
-uniform mat4 u_worldViewProjection;
+// Camera, shared by everything in the frame
+uniform mat4 u_viewProjection;
+// Inner radius as a part of the outer one
+uniform float u_inner;
+
+// Must match the inner diameter the mesh was generated with
+const float MESH_INNER = 0.5;
 
 varying vec3 v_color;
 attribute vec3 a_position;
@@ -9,5 +15,11 @@ attribute vec3 a_color;
 
 void main() {
     v_color = a_color;
-    gl_Position = u_worldViewProjection * (u_world * vec4(a_position, 1.0));
+
+    // Move the vertex between u_inner and the outer edge, like it was between MESH_INNER and 1
+    float radius = length(a_position.xy);
+    float t = (radius - MESH_INNER) / (1.0 - MESH_INNER);
+    vec2 xy = a_position.xy / radius * mix(u_inner, 1.0, t);
+
+    gl_Position = u_viewProjection * (u_world * vec4(xy, a_position.z, 1.0));
 }
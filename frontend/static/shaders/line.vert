@@ -0,0 +1,15 @@
+precision mediump float;
+
+// Camera, shared by everything in the frame
+uniform mat4 u_viewProjection;
+
+varying vec4 v_color;
+
+// Already in world space
+attribute vec3 a_position;
+attribute vec4 a_color;
+
+void main() {
+    v_color = a_color;
+    gl_Position = u_viewProjection * vec4(a_position, 1.0);
+}
@@ -0,0 +1,30 @@
+precision mediump float;
+
+// Camera, shared by everything in the frame
+uniform mat4 u_viewProjection;
+uniform vec3 u_eye;
+uniform float u_width;
+
+varying vec4 v_color;
+
+// Already in world space, on the middle of the ribbon
+attribute vec3 a_position;
+// Along the ribbon
+attribute vec3 a_direction;
+// -1 or 1, which edge of the ribbon this is
+attribute float a_side;
+attribute vec4 a_color;
+
+void main() {
+    v_color = a_color;
+
+    // Sideways to both the ribbon and the camera, so its flat side faces the camera
+    vec3 across = cross(a_direction, u_eye - a_position);
+    float size = length(across);
+    if (size > 0.0001) {
+        across /= size;
+    }
+
+    vec3 position = a_position + across * a_side * u_width * 0.5;
+    gl_Position = u_viewProjection * vec4(position, 1.0);
+}